@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use ark_bls12_381::G1Projective as G1;
+use ark_std::UniformRand;
+use clap::Parser;
+use dist_primitive::utils::serializing_net::MPCSerializeNet;
+use mpc_net::{LeaderNet, LocalTestNet, MPCNet, MultiplexedStreamID, NetMode};
+
+#[derive(Parser)]
+struct Cli {
+    /// The packing size, should be 1/8 of the party size as well as a power of 2.
+    #[arg(long)]
+    l: usize,
+    /// log2 of the number of group elements aggregated by every party.
+    #[arg(long, default_value_t = 10)]
+    n: usize,
+    /// How to run the parties: local, local-multi-thread or leader.
+    #[arg(long, default_value_t = NetMode::Local)]
+    mode: NetMode,
+}
+
+fn add(a: Vec<G1>, b: Vec<G1>) -> Vec<G1> {
+    a.into_iter().zip(b).map(|(a, b)| a + b).collect()
+}
+
+/// Sums `shares` over all parties, first through the leader (star), then along a binomial tree.
+/// Returns the time and the communication of both variants.
+async fn aggregate<Net: MPCNet>(
+    shares: &Vec<G1>,
+    net: &Net,
+) -> ((Duration, (usize, usize)), (Duration, (usize, usize))) {
+    let sid = MultiplexedStreamID::Zero;
+
+    let (up, down) = net.get_comm();
+    let start = Instant::now();
+    let star = net
+        .leader_compute_element(
+            shares,
+            sid,
+            |parts| vec![parts.into_iter().reduce(add).unwrap(); net.n_parties()],
+            "Star sum",
+        )
+        .await
+        .unwrap();
+    let star_time = start.elapsed();
+    let (star_up, star_down) = net.get_comm();
+
+    let start = Instant::now();
+    let tree = net
+        .leader_compute_element_tree(shares, sid, add, |sum| sum, "Tree sum")
+        .await
+        .unwrap();
+    let tree_time = start.elapsed();
+    let (tree_up, tree_down) = net.get_comm();
+
+    if net.is_leader() {
+        assert_eq!(star, tree);
+    }
+    (
+        (star_time, (star_up - up, star_down - down)),
+        (tree_time, (tree_up - star_up, tree_down - star_down)),
+    )
+}
+
+fn main() {
+    let args = Cli::parse();
+    args.mode.runtime().unwrap().block_on(run(args));
+}
+
+async fn run(args: Cli) {
+    let rng = &mut ark_std::test_rng();
+    let shares: Vec<G1> = (0..1 << args.n).map(|_| G1::rand(rng)).collect();
+
+    let ((star_time, star_comm), (tree_time, tree_comm)) = match args.mode {
+        // Only the leader runs its part, the communication is accounted for but not performed.
+        NetMode::Leader => aggregate(&shares, &LeaderNet::new(args.l * 8)).await,
+        NetMode::Local | NetMode::LocalMultiThread => {
+            let net = LocalTestNet::new_local_testnet(args.l * 8).await.unwrap();
+            net.simulate_network_round(shares, |net, shares| async move {
+                aggregate(&shares, &net).await
+            })
+            .await[0]
+        }
+    };
+
+    println!("Parties: {}", args.l * 8);
+    println!("Star: {:?}, leader comm: {:?}", star_time, star_comm);
+    println!("Tree: {:?}, leader comm: {:?}", tree_time, tree_comm);
+}
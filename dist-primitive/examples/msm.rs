@@ -4,17 +4,29 @@ use rayon::prelude::*;
 use ark_ec::{bls12::Bls12, pairing::Pairing, CurveGroup, VariableBaseMSM};
 use ark_poly::{EvaluationDomain, Radix2EvaluationDomain};
 use ark_std::UniformRand;
+use clap::Parser;
 use dist_primitive::{dmsm::d_msm, utils::operator::transpose};
 use mpc_net::{end_timer, start_timer};
-use mpc_net::{LocalTestNet as Net, MPCNet, MultiplexedStreamID};
+use mpc_net::{LeaderNet, LocalTestNet as Net, MPCNet, MultiplexedStreamID, NetMode};
 use secret_sharing::pss::PackedSharingParams;
 
 const L: usize = 8;
 const N: usize = 16;
 type E = Bls12<ark_bls12_381::Config>;
-#[cfg_attr(feature = "single_thread", tokio::main(flavor = "current_thread"))]
-#[cfg_attr(not(feature = "single_thread"), tokio::main)]
-async fn main() {
+
+#[derive(Parser)]
+struct Cli {
+    /// How to run the parties: local, local-multi-thread or leader.
+    #[arg(long, default_value_t = NetMode::Local)]
+    mode: NetMode,
+}
+
+fn main() {
+    let args = Cli::parse();
+    args.mode.runtime().unwrap().block_on(run(args.mode));
+}
+
+async fn run(mode: NetMode) {
     for i in N..=N {
         let pp = PackedSharingParams::<Fr>::new(L);
         let _rng = &mut ark_std::test_rng();
@@ -56,6 +68,11 @@ async fn main() {
         end_timer!(nmsm);
 
         // Distributed msm
+        if mode == NetMode::Leader {
+            let net = LeaderNet::new(L * 8);
+            d_msm_test::<<E as Pairing>::G1, _>(&pp, &x_share[0], &y_share[0], &net).await;
+            continue;
+        }
         let network = Net::new_local_testnet(L * 8).await.unwrap();
         network
             .simulate_network_round(
@@ -8,9 +8,7 @@ use clap::Parser;
 use dist_primitive::dpoly_comm::PolynomialCommitmentCub;
 use dist_primitive::utils::operator::transpose;
 use mpc_net::{end_timer, start_timer};
-use mpc_net::LocalTestNet;
-use mpc_net::MPCNet;
-use mpc_net::MultiplexedStreamID;
+use mpc_net::{LeaderNet, LocalTestNet, MPCNet, MultiplexedStreamID, NetMode};
 use rayon::prelude::*;
 use secret_sharing::pss::PackedSharingParams;
 
@@ -22,19 +20,27 @@ struct Cli {
     /// log2 of the total number of variables.
     #[arg(long)]
     n: usize,
+    /// How to run the parties: local, local-multi-thread or leader.
+    #[arg(long, default_value_t = NetMode::Local)]
+    mode: NetMode,
 }
 
-#[cfg_attr(feature = "single_thread", tokio::main(flavor = "current_thread"))]
-#[cfg_attr(not(feature = "single_thread"), tokio::main)]
-async fn main() {
+fn main() {
     let args = Cli::parse();
-
-    mvpc_bench(args.n, args.l).await;
+    args.mode.runtime().unwrap().block_on(async {
+        match args.mode {
+            NetMode::Leader => {
+                mvpc_leader_bench(args.n, args.l).await;
+            }
+            NetMode::Local | NetMode::LocalMultiThread => {
+                mvpc_local_bench(args.n, args.l).await;
+            }
+        }
+    });
 }
 
 /// This benchmark just runs the leader's part of the protocol without any networking involved.
-#[cfg(feature = "leader")]
-async fn mvpc_bench(n: usize, l: usize) {
+async fn mvpc_leader_bench(n: usize, l: usize) {
     // Prepare random elements and shares.
     let rng = &mut ark_std::test_rng();
     let mut s = Vec::new();
@@ -74,7 +80,7 @@ async fn mvpc_bench(n: usize, l: usize) {
 
     // Collaborative
     {
-        let net = LocalTestNet::new_local_testnet(l * 8).await.unwrap();
+        let net = LeaderNet::new(l * 8);
         let timer = start_timer!("Collaborative");
         let pp =
             PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
@@ -83,7 +89,7 @@ async fn mvpc_bench(n: usize, l: usize) {
             .c_commit(
                 &vec![peval_share.clone()],
                 &pp,
-                &net.get_leader(),
+                &net,
                 MultiplexedStreamID::Zero,
             )
             .await
@@ -95,24 +101,22 @@ async fn mvpc_bench(n: usize, l: usize) {
                 &peval_share,
                 &u,
                 &pp,
-                &net.get_leader(),
+                &net,
                 MultiplexedStreamID::Zero,
             )
             .await
             .unwrap();
         end_timer!(open_timer);
         end_timer!(timer);
-        println!("Comm: {:?}", net.get_leader().get_comm());
+        println!("Comm: {:?}", net.get_comm());
         black_box(commit);
         black_box((value, proof));
     }
 }
 
 /// This benchmark runs the protocol in a simulation mode, all parties are involved with actual LOCAL communication.
-/// Defaultly the benchmark is to run in a multi-threaded environment.
-/// When #[tokio::main(flavor = "current_thread")] feature is enabled, the benchmark is set to run in a single thread.
-#[cfg(not(feature = "leader"))]
-async fn mvpc_bench(n: usize, l: usize) {
+/// With `--mode local` the parties run on a single thread, with `--mode local-multi-thread` on many.
+async fn mvpc_local_bench(n: usize, l: usize) {
     // Prepare random elements.
     let rng = &mut ark_std::test_rng();
     let mut s = Vec::new();
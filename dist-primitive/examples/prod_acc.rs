@@ -9,7 +9,7 @@ use clap::Parser;
 use dist_primitive::dacc_product::acc_product;
 use dist_primitive::dacc_product::c_acc_product_and_share;
 use mpc_net::{end_timer, start_timer};
-use mpc_net::{LocalTestNet, MPCNet, MultiplexedStreamID};
+use mpc_net::{LeaderNet, LocalTestNet, MPCNet, MultiplexedStreamID, NetMode};
 use rayon::prelude::*;
 use secret_sharing::pss::PackedSharingParams;
 
@@ -22,19 +22,27 @@ struct Cli {
     /// log2 of the total number of variables.
     #[arg(long)]
     n: usize,
+    /// How to run the parties: local, local-multi-thread or leader.
+    #[arg(long, default_value_t = NetMode::Local)]
+    mode: NetMode,
 }
 
-#[cfg_attr(feature = "single_thread", tokio::main(flavor = "current_thread"))]
-#[cfg_attr(not(feature = "single_thread"), tokio::main)]
-async fn main() {
+fn main() {
     let args = Cli::parse();
-
-    product_accumulator_bench(args.n, args.l).await;
+    args.mode.runtime().unwrap().block_on(async {
+        match args.mode {
+            NetMode::Leader => {
+                product_accumulator_leader_bench(args.n, args.l).await;
+            }
+            NetMode::Local | NetMode::LocalMultiThread => {
+                product_accumulator_local_bench(args.n, args.l).await;
+            }
+        }
+    });
 }
 
 /// This benchmark just runs the leader's part of the protocol without any networking involved.
-#[cfg(feature = "leader")]
-async fn product_accumulator_bench(n: usize, l: usize) {
+async fn product_accumulator_leader_bench(n: usize, l: usize) {
     // Prepare random field elements.
     let x = (0..2_usize.pow(n as u32)).into_par_iter().map(|_| Fr::rand(&mut ark_std::test_rng())).collect::<Vec<Fr>>();
     // Local
@@ -66,7 +74,7 @@ async fn product_accumulator_bench(n: usize, l: usize) {
         .map(|_| Fr::rand(&mut ark_std::test_rng()))
         .collect();
 
-    let net = LocalTestNet::new_local_testnet(l * 8).await.unwrap();
+    let net = LeaderNet::new(l * 8);
     let distributed = start_timer!("Distributed product accumulatiton");
     let _ = black_box(
         c_acc_product_and_share(
@@ -76,19 +84,17 @@ async fn product_accumulator_bench(n: usize, l: usize) {
             &unmask1,
             &unmask2,
             &pp,
-            &net.get_leader(),
+            &net,
             MultiplexedStreamID::Zero,
         ).await
     );
     end_timer!(distributed);
-    println!("Comm: {:?}", net.get_leader().get_comm());
+    println!("Comm: {:?}", net.get_comm());
 }
 
 /// This benchmark runs the protocol in a simulation mode, all parties are involved with actual LOCAL communication.
-/// Defaultly the benchmark is to run in a multi-threaded environment.
-/// When #[tokio::main(flavor = "current_thread")] feature is enabled, the benchmark is set to run in a single thread.
-#[cfg(not(feature = "leader"))]
-async fn product_accumulator_bench(n: usize, l: usize) {
+/// With `--mode local` the parties run on a single thread, with `--mode local-multi-thread` on many.
+async fn product_accumulator_local_bench(n: usize, l: usize) {
     // Prepare random field elements.
     let x = (0..2_usize.pow(n as u32)).into_par_iter().map(|_| Fr::rand(&mut ark_std::test_rng())).collect::<Vec<Fr>>();
     // Local
@@ -11,7 +11,7 @@ use dist_primitive::dsumcheck::c_sumcheck;
 use dist_primitive::dsumcheck::sumcheck_product;
 use dist_primitive::dsumcheck::sumcheck;
 use dist_primitive::utils::operator::transpose;
-use mpc_net::{LocalTestNet, MPCNet, MultiplexedStreamID};
+use mpc_net::{LeaderNet, LocalTestNet, MPCNet, MultiplexedStreamID, NetMode};
 use mpc_net::{end_timer, start_timer};
 use rayon::prelude::*;
 use secret_sharing::pss::PackedSharingParams;
@@ -87,20 +87,29 @@ struct Cli {
     /// log2 of the total number of variables.
     #[arg(long)]
     n: usize,
+    /// How to run the parties: local, local-multi-thread or leader.
+    #[arg(long, default_value_t = NetMode::Local)]
+    mode: NetMode,
 }
 
-#[cfg_attr(feature = "single_thread", tokio::main(flavor = "current_thread"))]
-#[cfg_attr(not(feature = "single_thread"), tokio::main)]
-async fn main() {
+fn main() {
     let args = Cli::parse();
-    
-    sumcheck_bench(args.n, args.l).await;
-    sumcheck_product_bench(args.n, args.l).await;
+    args.mode.runtime().unwrap().block_on(async {
+        match args.mode {
+            NetMode::Leader => {
+                sumcheck_leader_bench(args.n, args.l).await;
+                sumcheck_product_leader_bench(args.n, args.l).await;
+            }
+            NetMode::Local | NetMode::LocalMultiThread => {
+                sumcheck_local_bench(args.n, args.l).await;
+                sumcheck_product_local_bench(args.n, args.l).await;
+            }
+        }
+    });
 }
 
 /// This benchmark just runs the leader's part of the sumcheck protocol without any networking involved.
-#[cfg(feature = "leader")]
-async fn sumcheck_bench(n: usize, l: usize) {
+async fn sumcheck_leader_bench(n: usize, l: usize) {
     let pp = PackedSharingParams::<Fr>::new(l);
     let delegator = Delegator::new(n);
     let challenge = (0..n)
@@ -114,28 +123,26 @@ async fn sumcheck_bench(n: usize, l: usize) {
     end_timer!(sc);
     black_box(proof);
     
-    let net = LocalTestNet::new_local_testnet(l * 8).await.unwrap();
+    let net = LeaderNet::new(l * 8);
     let x = delegator.delegate(l);
     let dsc = start_timer!("Collaborative Sumcheck Leader");
     let proof = c_sumcheck(
-        &x[net.get_leader().party_id() as usize],
+        &x[net.party_id() as usize],
         &challenge,
         &pp,
-        &net.get_leader(),
+        &net,
         MultiplexedStreamID::Zero,
     )
     .await
     .unwrap();
     end_timer!(dsc);
     black_box(proof);
-    println!("Comm: {:?}", net.get_leader().get_comm());
+    println!("Comm: {:?}", net.get_comm());
 }
 
 /// This benchmark runs the sumcheck protocol in a simulation mode, all parties are involved with actual LOCAL communication.
-/// The benchmark is default to run in a multi-threaded environment.
-/// When #[tokio::main(flavor = "current_thread")] feature is enabled, the benchmark is set to run in a single thread. 
-#[cfg(not(feature = "leader"))]
-async fn sumcheck_bench(n: usize, l: usize) {
+/// With `--mode local` the parties run on a single thread, with `--mode local-multi-thread` on many.
+async fn sumcheck_local_bench(n: usize, l: usize) {
     let delegator = Delegator::new(n);
     let challenge = (0..n)
     .map(|_| Fr::rand(&mut ark_std::test_rng()))
@@ -180,8 +187,7 @@ async fn sumcheck_bench(n: usize, l: usize) {
 }
 
 /// This benchmark just runs the leader's part of the sumcheck product protocol without any networking involved.
-#[cfg(feature = "leader")]
-async fn sumcheck_product_bench(n: usize, l: usize) {
+async fn sumcheck_product_leader_bench(n: usize, l: usize) {
     let pp = PackedSharingParams::<Fr>::new(l);
     let delegator = ProductDelegator::new(n);
     let challenge = (0..n)
@@ -196,29 +202,27 @@ async fn sumcheck_product_bench(n: usize, l: usize) {
     end_timer!(sc);
     black_box(proof);
     
-    let net = LocalTestNet::new_local_testnet(l * 8).await.unwrap();
+    let net = LeaderNet::new(l * 8);
     let (x, y) = delegator.delegate(l);
     let dsc = start_timer!("Collaborative SumcheckProduct Leader");
     let proof = c_sumcheck_product(
-        &x[net.get_leader().party_id() as usize],
-        &y[net.get_leader().party_id() as usize],
+        &x[net.party_id() as usize],
+        &y[net.party_id() as usize],
         &challenge,
         &pp,
-        &net.get_leader(),
+        &net,
         MultiplexedStreamID::Zero,
     )
     .await
     .unwrap();
     end_timer!(dsc);
     black_box(proof);
-    println!("Comm: {:?}", net.get_leader().get_comm());
+    println!("Comm: {:?}", net.get_comm());
 }
 
 /// This benchmark runs the sumcheck product protocol in a simulation mode, all parties are involved with actual LOCAL communication.
-/// The benchmark is default to run in a multi-threaded environment.
-/// When #[tokio::main(flavor = "current_thread")] feature is enabled, the benchmark is set to run in a single thread. 
-#[cfg(not(feature = "leader"))]
-async fn sumcheck_product_bench(n: usize, l: usize) {
+/// With `--mode local` the parties run on a single thread, with `--mode local-multi-thread` on many.
+async fn sumcheck_product_local_bench(n: usize, l: usize) {
     let delegator = ProductDelegator::new(n);
     let challenge = (0..n)
     .map(|_| Fr::rand(&mut ark_std::test_rng()))
@@ -261,5 +265,5 @@ async fn sumcheck_product_bench(n: usize, l: usize) {
         .await;
     end_timer!(dsc);
     black_box(proof);
-    // println!("Comm: {:?}", net.get_leader().get_comm());
+    // println!("Comm: {:?}", net.get_comm());
 }
\ No newline at end of file
@@ -1,5 +1,4 @@
 use std::cmp::min;
-#[cfg(not(feature = "comm"))]
 use std::hint::black_box;
 
 use crate::{
@@ -183,16 +182,12 @@ pub async fn c_acc_product_and_share<F: FftField, Net: MPCSerializeNet>(
             .await
             .unwrap();
 
-        #[cfg(feature = "comm")]
-        {
+        if !net.is_simulated() {
             results0.push(in0);
             results1.push(in1);
             results2.push(in2);
-        }
-
-        // If no actual communication, just use the input as a placeholder.
-        #[cfg(not(feature = "comm"))]
-        {
+        } else {
+            // If no actual communication, just use the input as a placeholder.
             black_box(in0);
             black_box(in1);
             black_box(in2);
@@ -286,7 +281,6 @@ pub async fn c_acc_product_and_share<F: FftField, Net: MPCSerializeNet>(
 
     end_timer!(reduce_timer);
 
-    end_timer!(unmask_timer);
     end_timer!(timer);
     Ok((share0, share1, share2))
 }
@@ -321,10 +315,7 @@ pub async fn c_acc_product<F: FftField, Net: MPCSerializeNet>(
     let num_to_send = min(party_count, subtree.len());
     let ld_receive_timer = start_timer!("Send elements to leader", net.is_leader());
     let leader_receiving = net
-        .worker_send_or_leader_receive_element(
-            &subtree[subtree.len() - num_to_send..].to_vec(),
-            sid,
-        )
+        .worker_send_or_leader_receive_elements(&subtree[subtree.len() - num_to_send..], sid)
         .await
         .unwrap();
     end_timer!(ld_receive_timer);
@@ -26,7 +26,10 @@ pub async fn d_msm<G: CurveGroup, Net: MPCSerializeNet>(
 
     let leader_timer = start_timer!("Send to leader for MSM", net.is_leader());
     // Should be masked by randoms. Omitted for simplicity.
-    let result = net.leader_compute_element(&c_shares, sid, |shares|{
+    // The shares are streamed to the leader, there is one per MSM.
+    let shares = net.worker_send_or_leader_receive_elements(&c_shares, sid).await?;
+    let timer = start_timer!("Leader: Compute element (MSM Leader)", net.is_leader());
+    let shares = shares.map(|shares| {
         let shares = transpose(shares);
         let results = shares.iter().map(|s| {
             // This operation is costing for single-threaded execution. In the benchmark statistic, we assume this `iter` opertion can be replaced by a `par_iter` for parallelism. This is reasonable as in practice leader can use rayon to parallelize the computation.
@@ -37,7 +40,9 @@ pub async fn d_msm<G: CurveGroup, Net: MPCSerializeNet>(
             res
         }).collect();
         transpose(results)
-    }, "MSM Leader").await;
+    });
+    end_timer!(timer);
+    let result = net.worker_receive_or_leader_send_element(shares, sid).await;
     end_timer!(leader_timer);
     return result;
 }
@@ -1,5 +1,10 @@
+use crate::mask_poly::MaskPolynomial;
+use crate::sumcheck_proof::{SumcheckProof, ZkSumcheckProof};
+use crate::transcript::Transcript;
+use crate::utils::operator::transpose;
+use crate::virtual_poly::VirtualPolynomial;
 use crate::{unpack::pss2ss, utils::serializing_net::MPCSerializeNet};
-use ark_ff::FftField;
+use ark_ff::{FftField, Field, PrimeField};
 use mpc_net::{end_timer, start_timer, MPCNetError, MultiplexedStreamID};
 use secret_sharing::pss::PackedSharingParams;
 
@@ -89,6 +94,8 @@ pub fn sumcheck_product<F: FftField>(
     result
 }
 
+/// Sumcheck of the packed evaluations `shares`. Every round message is opened to all parties,
+/// so every party returns the plain round messages of [`sumcheck`] on the packed evaluations.
 pub async fn c_sumcheck<F: FftField, Net: MPCSerializeNet>(
     shares: &Vec<F>,
     challenge: &Vec<F>,
@@ -97,427 +104,1071 @@ pub async fn c_sumcheck<F: FftField, Net: MPCSerializeNet>(
     sid: MultiplexedStreamID,
 ) -> Result<Vec<(F, F)>, MPCNetError> {
     let d_sumcheck_timer = start_timer!("Collaborative sumcheck", net.is_leader());
+    let mut challenge = challenge.iter();
+    let (result, _) =
+        c_sumcheck_rounds(shares, |_| *challenge.next().unwrap(), pp, net, sid).await?;
+    end_timer!(d_sumcheck_timer);
+    Ok(result)
+}
+
+/// Like [`c_sumcheck`] for `Σ f·g`. The round messages are shares of products, of degree
+/// 2(t+l), which are opened with `unpack2`. Every party returns the plain round messages of
+/// [`sumcheck_product`] on the packed evaluations.
+pub async fn c_sumcheck_product<F: FftField, Net: MPCSerializeNet>(
+    shares_f: &Vec<F>,
+    shares_g: &Vec<F>,
+    challenge: &Vec<F>,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Vec<(F, F, F)>, MPCNetError> {
+    let d_sumcheck_product_timer = start_timer!("Collaborative sumcheck product", net.is_leader());
+    let mut challenge = challenge.iter();
+    let (result, _) = c_sumcheck_product_rounds(
+        shares_f,
+        shares_g,
+        |_| *challenge.next().unwrap(),
+        pp,
+        net,
+        sid,
+    )
+    .await?;
+    end_timer!(d_sumcheck_product_timer);
+    Ok(result)
+}
+
+/// Sumcheck of `f` whose evaluation `c * n_parties + party` is evaluation `c` of the
+/// `partial_poly` of `party`. Only the leader returns the round messages, ending with `(0, f(r))`
+/// like [`sumcheck`], the other parties return nothing.
+pub async fn d_sumcheck<F: FftField, Net: MPCSerializeNet>(
+    partial_poly: &Vec<F>,
+    challenge: &Vec<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Vec<(F, F)>, MPCNetError> {
+    let d_sumcheck_timer = start_timer!("Distributed sumcheck", net.is_leader());
     let mut result = Vec::new();
     // n and l must be powers of 2
-    let n = shares.len().trailing_zeros() as usize;
-    let l: usize = pp.l.trailing_zeros() as usize;
-    let mut last_round = shares.clone();
+    let n = partial_poly.len().trailing_zeros() as usize;
+    let s = net.n_parties().trailing_zeros() as usize;
+    let mut last_round = partial_poly.clone();
     // Phase 1
     let timer = start_timer!("Local: Phase 1", net.is_leader());
-    for i in 0..n {
-        let parts = last_round.split_at(last_round.len() / 2);
-        let one_minus_challenge = F::ONE - challenge[i];
-        let res1 = parts.0.iter().sum();
-        let res2 = parts.1.iter().sum();
-        result.push((res1, res2));
-        // result.push((parts.0.iter().sum(), parts.1.iter().sum()));
-        let this_round = parts
-            .0
-            .iter()
-            .zip(parts.1.iter())
-            .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-            .collect::<Vec<_>>();
-        last_round = this_round;
+    for challenge in &challenge[..n] {
+        result.push(round_message(&last_round));
+        last_round = bind(&last_round, *challenge);
     }
-    end_timer!(timer);
     debug_assert!(last_round.len() == 1);
-    let mut last_round = pss2ss(last_round[0], pp, net, sid).await?;
+    result.push((F::ZERO, last_round[0]));
+    end_timer!(timer);
+    let local_polys = net
+        .worker_send_or_leader_receive_element(&result, sid)
+        .await?;
     // Phase 2
-    let timer = start_timer!("Local: Phase 2", net.is_leader());
-    for i in 0..l {
-        let parts = last_round.split_at(last_round.len() / 2);
-        let one_minus_challenge = F::ONE - challenge[i];
-        let res1 = parts.0.iter().sum();
-        let res2 = parts.1.iter().sum();
-        result.push((res1, res2));
-        // result.push((parts.0.iter().sum(), parts.1.iter().sum()));
-        let this_round = parts
-            .0
-            .iter()
-            .zip(parts.1.iter())
-            .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
+    let result = if let Some(local_polys) = local_polys {
+        let timer = start_timer!("Leader: Phase 2", net.is_leader());
+        let mut result = (0..n)
+            .flat_map(|i| {
+                local_polys
+                    .iter()
+                    .map(|x| x[i])
+                    .reduce(|(a1, b1), (a2, b2)| (a1 + a2, b1 + b2))
+            })
             .collect::<Vec<_>>();
-        last_round = this_round;
-    }
-    end_timer!(timer);
-    result.push((F::ZERO, last_round[0]));
+        let mut last_round = local_polys.iter().map(|x| x.last().unwrap().1).collect::<Vec<_>>();
+        for challenge in &challenge[n..n + s] {
+            result.push(round_message(&last_round));
+            last_round = bind(&last_round, *challenge);
+        }
+        // The last round yields (0, result).
+        result.push((F::ZERO, last_round[0]));
+        end_timer!(timer);
+        result
+    } else {
+        vec![]
+    };
+
     end_timer!(d_sumcheck_timer);
     Ok(result)
 }
 
-pub async fn c_sumcheck_product<F: FftField, Net: MPCSerializeNet>(
-    shares_f: &Vec<F>,
-    shares_g: &Vec<F>,
+/// Like [`d_sumcheck`] for `Σ f·g`. The round messages of the leader end with
+/// `(0, f(r)·g(r), 0)` like [`sumcheck_product`].
+pub async fn d_sumcheck_product<F: FftField, Net: MPCSerializeNet>(
+    partial_f: &Vec<F>,
+    partial_g: &Vec<F>,
     challenge: &Vec<F>,
-    pp: &PackedSharingParams<F>,
     net: &Net,
     sid: MultiplexedStreamID,
 ) -> Result<Vec<(F, F, F)>, MPCNetError> {
-    let d_sumcheck_product_timer = start_timer!("Collaborative sumcheck product", net.is_leader());
+    let d_sumcheck_product_timer = start_timer!("Distributed sumcheck product", net.is_leader());
     let mut result = Vec::new();
-    let n: usize = shares_f.len().trailing_zeros() as usize;
-    let l: usize = pp.l.trailing_zeros() as usize;
-    assert_eq!(shares_f.len(), shares_g.len());
-    let mut last_round_f = shares_f.clone();
-    let mut last_round_g = shares_g.clone();
+    let n = partial_f.len().trailing_zeros() as usize;
+    let s = net.n_parties().trailing_zeros() as usize;
+    assert_eq!(partial_f.len(), partial_g.len());
+    let mut last_round_f = partial_f.clone();
+    let mut last_round_g = partial_g.clone();
     // Phase 1
     // In this part the shares can be viewed as a whole. There's no need to go into them
     // The result of this part is a degree 2d share since we perform multiplication between shares.
     let timer = start_timer!("Local: Phase 1", net.is_leader());
-    for i in 0..n {
-        let parts_f = last_round_f.split_at(last_round_f.len() / 2);
-        let parts_g = last_round_g.split_at(last_round_g.len() / 2);
-        let one_minus_challenge = F::ONE - challenge[i];
-        let res: (F, F, F) = {
-            // t=0
-            let part0_sum = parts_f
-                .0
-                .iter()
-                .zip(parts_g.0.iter())
-                .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-            // t=1
-            let part1_sum = parts_f
-                .1
-                .iter()
-                .zip(parts_g.1.iter())
-                .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-            // t=2,
-            // in which case, the evaluation of f and g is not present in the bookkeeping table,
-            // we need to calculate them from (1-t)*x0+t*x1
-            let part2_f: Vec<_> = parts_f
-                .0
-                .iter()
-                .zip(parts_f.1.iter())
-                .map(|(x, y)| -*x + *y * F::from(2_u64))
-                .collect();
-            let part2_g: Vec<_> = parts_g
-                .0
-                .iter()
-                .zip(parts_g.1.iter())
-                .map(|(x, y)| -*x + *y * F::from(2_u64))
-                .collect();
-            let part2_sum = part2_f
-                .iter()
-                .zip(part2_g.iter())
-                .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-            (part0_sum, part1_sum, part2_sum)
-        };
-        result.push(res);
-        // (1-u)*x0 + u*x1
-        last_round_f = parts_f
-            .0
-            .iter()
-            .zip(parts_f.1.iter())
-            .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-            .collect::<Vec<_>>();
-        last_round_g = parts_g
-            .0
-            .iter()
-            .zip(parts_g.1.iter())
-            .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-            .collect::<Vec<_>>();
+    for challenge in &challenge[..n] {
+        result.push(round_message_product(&last_round_f, &last_round_g));
+        last_round_f = bind(&last_round_f, *challenge);
+        last_round_g = bind(&last_round_g, *challenge);
     }
     end_timer!(timer);
     debug_assert!(last_round_f.len() == 1);
     debug_assert!(last_round_g.len() == 1);
+    result.push((last_round_g[0], last_round_f[0], F::ZERO));
+    let local_polys = net
+        .worker_send_or_leader_receive_element(&result, sid)
+        .await?;
     // Phase 2
+    let result = if let Some(local_polys) = local_polys {
+        let timer = start_timer!("Leader: Phase 2", net.is_leader());
+        let mut result = (0..n)
+            .flat_map(|i| {
+                local_polys
+                    .iter()
+                    .map(|x| x[i])
+                    .reduce(|(a1, b1, c1), (a2, b2, c2)| (a1 + a2, b1 + b2, c1 + c2))
+            })
+            .collect::<Vec<_>>();
+        let mut last_round_f = local_polys.iter().map(|x| x.last().unwrap().1).collect::<Vec<_>>();
+        let mut last_round_g = local_polys.iter().map(|x| x.last().unwrap().0).collect::<Vec<_>>();
+        for challenge in &challenge[n..n + s] {
+            result.push(round_message_product(&last_round_f, &last_round_g));
+            last_round_f = bind(&last_round_f, *challenge);
+            last_round_g = bind(&last_round_g, *challenge);
+        }
+        // The last round yields (0, result).
+        result.push((F::ZERO, last_round_f[0] * last_round_g[0], F::ZERO));
+        end_timer!(timer);
+        result
+    } else {
+        vec![]
+    };
+    end_timer!(d_sumcheck_product_timer);
+    Ok(result)
+}
+
+/// Labels of what the Fiat–Shamir sumchecks absorb and squeeze.
+pub(crate) const ROUND_LABEL: &[u8] = b"sumcheck round";
+pub(crate) const CHALLENGE_LABEL: &[u8] = b"sumcheck challenge";
+pub(crate) const EVALUATION_LABEL: &[u8] = b"sumcheck evaluation";
+pub(crate) const MASK_SUM_LABEL: &[u8] = b"sumcheck mask sum";
+pub(crate) const MASK_CHALLENGE_LABEL: &[u8] = b"sumcheck mask challenge";
+
+/// The round message of `Σ f` for the first variable: the sums of both halves.
+fn round_message<F: Field>(evaluations: &[F]) -> (F, F) {
+    let parts = evaluations.split_at(evaluations.len() / 2);
+    (parts.0.iter().sum(), parts.1.iter().sum())
+}
+
+/// The round message of `Σ f·g` for the first variable: the round polynomial at 0, 1 and 2.
+fn round_message_product<F: Field>(f: &[F], g: &[F]) -> (F, F, F) {
+    let parts_f = f.split_at(f.len() / 2);
+    let parts_g = g.split_at(g.len() / 2);
+    let two = F::from(2_u64);
+    let mut message = (F::ZERO, F::ZERO, F::ZERO);
+    for i in 0..parts_f.0.len() {
+        let (f0, f1, g0, g1) = (parts_f.0[i], parts_f.1[i], parts_g.0[i], parts_g.1[i]);
+        message.0 += f0 * g0;
+        message.1 += f1 * g1;
+        // At t=2 the evaluations are not in the table, they are (1-t)*x0+t*x1.
+        message.2 += (f1 * two - f0) * (g1 * two - g0);
+    }
+    message
+}
+
+/// Bind the first variable to `challenge`: (1-u)*x0 + u*x1.
+fn bind<F: Field>(evaluations: &[F], challenge: F) -> Vec<F> {
+    let parts = evaluations.split_at(evaluations.len() / 2);
+    parts
+        .0
+        .iter()
+        .zip(parts.1.iter())
+        .map(|(a, b)| *a + (*b - *a) * challenge)
+        .collect()
+}
+
+/// Absorb a round message and squeeze the challenge of the next round.
+fn next_challenge<F: PrimeField, T: ark_serialize::CanonicalSerialize>(
+    message: &T,
+    transcript: &mut Transcript,
+) -> F {
+    transcript.append_serializable(ROUND_LABEL, message);
+    transcript.challenge_scalar(CHALLENGE_LABEL)
+}
+
+/// Like [`next_challenge`] for a round message given by its evaluations. The tuples of the other
+/// provers serialize like their elements back to back, so it is absorbed the same way.
+pub(crate) fn next_round_challenge<F: PrimeField>(message: &[F], transcript: &mut Transcript) -> F {
+    let mut bytes = Vec::new();
+    for evaluation in message {
+        evaluation.serialize_compressed(&mut bytes).unwrap();
+    }
+    transcript.append_message(ROUND_LABEL, &bytes);
+    transcript.challenge_scalar(CHALLENGE_LABEL)
+}
+
+/// Like [`sumcheck`], with the challenge of every round squeezed from `transcript` after
+/// absorbing the round message. Returns the round messages, ending with `(0, f(r))`, and the
+/// challenges `r`.
+pub fn sumcheck_fs<F: PrimeField>(
+    evaluation: &[F],
+    transcript: &mut Transcript,
+) -> (Vec<(F, F)>, Vec<F>) {
+    let n = evaluation.len().trailing_zeros() as usize;
+    let mut result = Vec::new();
+    let mut challenges = Vec::new();
+    let mut last_round = evaluation.to_vec();
+    for _ in 0..n {
+        let message = round_message(&last_round);
+        let challenge = next_challenge(&message, transcript);
+        result.push(message);
+        challenges.push(challenge);
+        last_round = bind(&last_round, challenge);
+    }
+    debug_assert!(last_round.len() == 1);
+    transcript.append_serializable(EVALUATION_LABEL, &last_round[0]);
+    result.push((F::ZERO, last_round[0]));
+    (result, challenges)
+}
+
+/// Like [`sumcheck_product`], with the challenges squeezed from `transcript`, see
+/// [`sumcheck_fs`]. The round messages end with `(0, f(r)·g(r), 0)`.
+pub fn sumcheck_product_fs<F: PrimeField>(
+    evaluation_f: &[F],
+    evaluation_g: &[F],
+    transcript: &mut Transcript,
+) -> (Vec<(F, F, F)>, Vec<F>) {
+    assert_eq!(evaluation_f.len(), evaluation_g.len());
+    let n = evaluation_f.len().trailing_zeros() as usize;
+    let mut result = Vec::new();
+    let mut challenges = Vec::new();
+    let mut last_round_f = evaluation_f.to_vec();
+    let mut last_round_g = evaluation_g.to_vec();
+    for _ in 0..n {
+        let message = round_message_product(&last_round_f, &last_round_g);
+        let challenge = next_challenge(&message, transcript);
+        result.push(message);
+        challenges.push(challenge);
+        last_round_f = bind(&last_round_f, challenge);
+        last_round_g = bind(&last_round_g, challenge);
+    }
+    debug_assert!(last_round_f.len() == 1);
+    let evaluation = last_round_f[0] * last_round_g[0];
+    transcript.append_serializable(EVALUATION_LABEL, &evaluation);
+    result.push((F::ZERO, evaluation, F::ZERO));
+    (result, challenges)
+}
+
+/// Absorb round messages some other party computed, ending with the evaluation, and squeeze the
+/// challenges like the party did.
+fn replay<F: PrimeField, T: ark_serialize::CanonicalSerialize>(
+    messages: &[T],
+    evaluation: F,
+    transcript: &mut Transcript,
+) -> Vec<F> {
+    let challenges = messages
+        .iter()
+        .map(|message| next_challenge(message, transcript))
+        .collect();
+    transcript.append_serializable(EVALUATION_LABEL, &evaluation);
+    challenges
+}
+
+/// Open packed shares of the coefficients of a round message to every party. The shares are of
+/// products of `factors` sharings, of degree factors*(t+l). With `sum_slots` the message is the
+/// sum of the `l` slots, otherwise every slot holds the message, as after [`pss2ss`].
+async fn open_round_message<F: FftField, Net: MPCSerializeNet>(
+    shares: Vec<F>,
+    factors: usize,
+    sum_slots: bool,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<Vec<F>, MPCNetError> {
+    net.leader_compute_element(
+        &shares,
+        sid,
+        |shares| {
+            let message: Vec<F> = transpose(shares)
+                .into_iter()
+                .map(|coefficient| {
+                    let slots = pp.unpack_product(coefficient, factors);
+                    if sum_slots {
+                        slots.into_iter().sum()
+                    } else {
+                        slots[0]
+                    }
+                })
+                .collect();
+            vec![message; net.n_parties()]
+        },
+        "Open round message",
+    )
+    .await
+}
+
+/// The rounds of [`c_sumcheck`], with the challenge of every round given by `round_challenge`
+/// from the opened round message. Returns the round messages, ending with `(0, f(r))`, and the
+/// challenges `r`.
+async fn c_sumcheck_rounds<F: FftField, Net: MPCSerializeNet>(
+    shares: &[F],
+    mut round_challenge: impl FnMut(&(F, F)) -> F,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<(Vec<(F, F)>, Vec<F>), MPCNetError> {
+    let mut result = Vec::new();
+    let mut challenges = Vec::new();
+    // n and l must be powers of 2
+    let n = shares.len().trailing_zeros() as usize;
+    let l: usize = pp.l.trailing_zeros() as usize;
+    let mut last_round = shares.to_vec();
+    // Phase 1, the messages are shares of the sums of every slot.
+    let timer = start_timer!("Local: Phase 1", net.is_leader());
+    for _ in 0..n {
+        let (res1, res2) = round_message(&last_round);
+        let message = open_round_message(vec![res1, res2], 1, true, pp, net, sid).await?;
+        let message = (message[0], message[1]);
+        let challenge = round_challenge(&message);
+        result.push(message);
+        challenges.push(challenge);
+        last_round = bind(&last_round, challenge);
+    }
+    end_timer!(timer);
+    debug_assert!(last_round.len() == 1);
+    let mut last_round = pss2ss(last_round[0], pp, net, sid).await?;
+    // Phase 2, the messages are shares of single values.
+    let timer = start_timer!("Local: Phase 2", net.is_leader());
+    for _ in 0..l {
+        let (res1, res2) = round_message(&last_round);
+        let message = open_round_message(vec![res1, res2], 1, false, pp, net, sid).await?;
+        let message = (message[0], message[1]);
+        let challenge = round_challenge(&message);
+        result.push(message);
+        challenges.push(challenge);
+        last_round = bind(&last_round, challenge);
+    }
+    end_timer!(timer);
+    let evaluation = open_round_message(last_round, 1, false, pp, net, sid).await?[0];
+    result.push((F::ZERO, evaluation));
+    Ok((result, challenges))
+}
+
+/// The rounds of [`c_sumcheck_product`], see [`c_sumcheck_rounds`]. The round messages end with
+/// `(0, f(r)·g(r), 0)`.
+async fn c_sumcheck_product_rounds<F: FftField, Net: MPCSerializeNet>(
+    shares_f: &[F],
+    shares_g: &[F],
+    mut round_challenge: impl FnMut(&(F, F, F)) -> F,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<(Vec<(F, F, F)>, Vec<F>), MPCNetError> {
+    let mut result = Vec::new();
+    let mut challenges = Vec::new();
+    let n: usize = shares_f.len().trailing_zeros() as usize;
+    let l: usize = pp.l.trailing_zeros() as usize;
+    assert_eq!(shares_f.len(), shares_g.len());
+    let mut last_round_f = shares_f.to_vec();
+    let mut last_round_g = shares_g.to_vec();
+    // Phase 1, the messages are degree 2(t+l) shares of the sums of every slot.
+    let timer = start_timer!("Local: Phase 1", net.is_leader());
+    for _ in 0..n {
+        let (res0, res1, res2) = round_message_product(&last_round_f, &last_round_g);
+        let message =
+            open_round_message(vec![res0, res1, res2], 2, true, pp, net, sid).await?;
+        let message = (message[0], message[1], message[2]);
+        let challenge = round_challenge(&message);
+        result.push(message);
+        challenges.push(challenge);
+        last_round_f = bind(&last_round_f, challenge);
+        last_round_g = bind(&last_round_g, challenge);
+    }
+    end_timer!(timer);
+    debug_assert!(last_round_f.len() == 1);
+    debug_assert!(last_round_g.len() == 1);
     let mut last_round_f = pss2ss(last_round_f[0], pp, net, sid).await?;
     let mut last_round_g = pss2ss(last_round_g[0], pp, net, sid).await?;
+    // Phase 2, the messages are degree 2(t+l) shares of single values.
     let timer = start_timer!("Local: Phase 2", net.is_leader());
-    for i in 0..l {
-        let parts_f = last_round_f.split_at(last_round_f.len() / 2);
-        let parts_g = last_round_g.split_at(last_round_g.len() / 2);
-        let one_minus_challenge = F::ONE - challenge[i];
-        let res = {
-            // t=0
-            let part0_sum = parts_f
-                .0
-                .iter()
-                .zip(parts_g.0.iter())
-                .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-            // t=1
-            let part1_sum = parts_f
-                .1
-                .iter()
-                .zip(parts_g.1.iter())
-                .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-            // t=2,
-            // in which case, the evaluation of f and g is not present in the bookkeeping table,
-            // we need to calculate them from (1-t)*x0+t*x1
-            let part2_f: Vec<_> = parts_f
-                .0
-                .iter()
-                .zip(parts_f.1.iter())
-                .map(|(x, y)| -*x + *y * F::from(2_u64))
-                .collect();
-            let part2_g: Vec<_> = parts_g
-                .0
-                .iter()
-                .zip(parts_g.1.iter())
-                .map(|(x, y)| -*x + *y * F::from(2_u64))
-                .collect();
-            let part2_sum = part2_f
-                .iter()
-                .zip(part2_g.iter())
-                .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-            (part0_sum, part1_sum, part2_sum)
+    for _ in 0..l {
+        let (res0, res1, res2) = round_message_product(&last_round_f, &last_round_g);
+        let message =
+            open_round_message(vec![res0, res1, res2], 2, false, pp, net, sid).await?;
+        let message = (message[0], message[1], message[2]);
+        let challenge = round_challenge(&message);
+        result.push(message);
+        challenges.push(challenge);
+        last_round_f = bind(&last_round_f, challenge);
+        last_round_g = bind(&last_round_g, challenge);
+    }
+    end_timer!(timer);
+    let evaluation = open_round_message(
+        vec![last_round_f[0] * last_round_g[0]],
+        2,
+        false,
+        pp,
+        net,
+        sid,
+    )
+    .await?[0];
+    // Put it in the second slot to keep consistency with vec.split_at(vec.len()/2). In which case the first part will be empty.
+    result.push((F::ZERO, evaluation, F::ZERO));
+    Ok((result, challenges))
+}
+
+/// Like [`c_sumcheck`], but every round message is opened to all parties and absorbed into
+/// their `transcript`, from which they squeeze the challenge of the next round. Every party
+/// returns the plain round messages, ending with `(0, f(r))`, and the challenges `r`, the same as
+/// [`sumcheck_fs`] on the packed evaluations.
+pub async fn c_sumcheck_fs<F: PrimeField, Net: MPCSerializeNet>(
+    shares: &[F],
+    pp: &PackedSharingParams<F>,
+    transcript: &mut Transcript,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<(Vec<(F, F)>, Vec<F>), MPCNetError> {
+    let c_sumcheck_timer = start_timer!("Collaborative sumcheck with transcript", net.is_leader());
+    let round_challenge = |message: &(F, F)| next_challenge(message, transcript);
+    let (result, challenges) = c_sumcheck_rounds(shares, round_challenge, pp, net, sid).await?;
+    transcript.append_serializable(EVALUATION_LABEL, &result.last().unwrap().1);
+    end_timer!(c_sumcheck_timer);
+    Ok((result, challenges))
+}
+
+/// Like [`c_sumcheck_product`] with the challenges squeezed from `transcript`, see
+/// [`c_sumcheck_fs`]. Returns the same as [`sumcheck_product_fs`] on the packed evaluations.
+pub async fn c_sumcheck_product_fs<F: PrimeField, Net: MPCSerializeNet>(
+    shares_f: &[F],
+    shares_g: &[F],
+    pp: &PackedSharingParams<F>,
+    transcript: &mut Transcript,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<(Vec<(F, F, F)>, Vec<F>), MPCNetError> {
+    let c_sumcheck_timer =
+        start_timer!("Collaborative sumcheck product with transcript", net.is_leader());
+    let round_challenge = |message: &(F, F, F)| next_challenge(message, transcript);
+    let (result, challenges) =
+        c_sumcheck_product_rounds(shares_f, shares_g, round_challenge, pp, net, sid).await?;
+    transcript.append_serializable(EVALUATION_LABEL, &result.last().unwrap().1);
+    end_timer!(c_sumcheck_timer);
+    Ok((result, challenges))
+}
+
+/// Like [`d_sumcheck`], but the round messages of phase 1 are summed over all parties and opened
+/// to them, and the challenges are squeezed from `transcript`. The leader runs phase 2 over the
+/// evaluations of the parties with [`sumcheck_fs`] and sends the messages to all parties, so
+/// every party returns the round messages and challenges of [`sumcheck_fs`] on the whole
+/// polynomial, whose evaluation `c * n_parties + party` is evaluation `c` of `party`.
+pub async fn d_sumcheck_fs<F: PrimeField, Net: MPCSerializeNet>(
+    partial_poly: &[F],
+    transcript: &mut Transcript,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<(Vec<(F, F)>, Vec<F>), MPCNetError> {
+    let d_sumcheck_timer = start_timer!("Distributed sumcheck with transcript", net.is_leader());
+    let mut result = Vec::new();
+    let mut challenges = Vec::new();
+    let n = partial_poly.len().trailing_zeros() as usize;
+    let mut last_round = partial_poly.to_vec();
+    // Phase 1
+    let timer = start_timer!("Local: Phase 1", net.is_leader());
+    for _ in 0..n {
+        let message = net
+            .leader_compute_element(
+                &round_message(&last_round),
+                sid,
+                |messages| {
+                    let sum = messages
+                        .into_iter()
+                        .fold((F::ZERO, F::ZERO), |(a1, b1), (a2, b2)| (a1 + a2, b1 + b2));
+                    vec![sum; net.n_parties()]
+                },
+                "Sum round messages",
+            )
+            .await?;
+        let challenge = next_challenge(&message, transcript);
+        result.push(message);
+        challenges.push(challenge);
+        last_round = bind(&last_round, challenge);
+    }
+    end_timer!(timer);
+    debug_assert!(last_round.len() == 1);
+    // Phase 2
+    let phase2 = net
+        .leader_compute_element(
+            &vec![(F::ZERO, last_round[0])],
+            sid,
+            |evaluations| {
+                let evaluations: Vec<F> = evaluations.iter().map(|x| x[0].1).collect();
+                let (messages, _) = sumcheck_fs(&evaluations, &mut transcript.clone());
+                vec![messages; net.n_parties()]
+            },
+            "Leader: Phase 2",
+        )
+        .await?;
+    let (messages, evaluation) = phase2.split_at(phase2.len() - 1);
+    challenges.extend(replay(messages, evaluation[0].1, transcript));
+    result.extend(phase2);
+    end_timer!(d_sumcheck_timer);
+    Ok((result, challenges))
+}
+
+/// Like [`d_sumcheck_product`] with the challenges squeezed from `transcript`, see
+/// [`d_sumcheck_fs`]. Returns the same as [`sumcheck_product_fs`] on the whole polynomials.
+pub async fn d_sumcheck_product_fs<F: PrimeField, Net: MPCSerializeNet>(
+    partial_f: &[F],
+    partial_g: &[F],
+    transcript: &mut Transcript,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<(Vec<(F, F, F)>, Vec<F>), MPCNetError> {
+    let d_sumcheck_timer =
+        start_timer!("Distributed sumcheck product with transcript", net.is_leader());
+    let mut result = Vec::new();
+    let mut challenges = Vec::new();
+    let n = partial_f.len().trailing_zeros() as usize;
+    assert_eq!(partial_f.len(), partial_g.len());
+    let mut last_round_f = partial_f.to_vec();
+    let mut last_round_g = partial_g.to_vec();
+    // Phase 1
+    let timer = start_timer!("Local: Phase 1", net.is_leader());
+    for _ in 0..n {
+        let message = net
+            .leader_compute_element(
+                &round_message_product(&last_round_f, &last_round_g),
+                sid,
+                |messages| {
+                    let sum = messages.into_iter().fold(
+                        (F::ZERO, F::ZERO, F::ZERO),
+                        |(a1, b1, c1), (a2, b2, c2)| (a1 + a2, b1 + b2, c1 + c2),
+                    );
+                    vec![sum; net.n_parties()]
+                },
+                "Sum round messages",
+            )
+            .await?;
+        let challenge = next_challenge(&message, transcript);
+        result.push(message);
+        challenges.push(challenge);
+        last_round_f = bind(&last_round_f, challenge);
+        last_round_g = bind(&last_round_g, challenge);
+    }
+    end_timer!(timer);
+    debug_assert!(last_round_f.len() == 1);
+    debug_assert!(last_round_g.len() == 1);
+    // Phase 2
+    let phase2 = net
+        .leader_compute_element(
+            &vec![(last_round_g[0], last_round_f[0], F::ZERO)],
+            sid,
+            |evaluations| {
+                let f: Vec<F> = evaluations.iter().map(|x| x[0].1).collect();
+                let g: Vec<F> = evaluations.iter().map(|x| x[0].0).collect();
+                let (messages, _) = sumcheck_product_fs(&f, &g, &mut transcript.clone());
+                vec![messages; net.n_parties()]
+            },
+            "Leader: Phase 2",
+        )
+        .await?;
+    let (messages, evaluation) = phase2.split_at(phase2.len() - 1);
+    challenges.extend(replay(messages, evaluation[0].1, transcript));
+    result.extend(phase2);
+    end_timer!(d_sumcheck_timer);
+    Ok((result, challenges))
+}
+
+/// Sumcheck of the virtual polynomial `poly`, whose round polynomials have degree
+/// `poly.degree()` and are sent as their evaluations at 0, 1, ..., the degree.
+pub fn sumcheck_virtual<F: Field>(poly: &VirtualPolynomial<F>, challenge: &[F]) -> SumcheckProof<F> {
+    let mut poly = poly.clone();
+    let mut rounds = Vec::new();
+    for challenge in &challenge[..poly.num_vars()] {
+        rounds.push(poly.round_message());
+        poly.bind(*challenge);
+    }
+    SumcheckProof {
+        rounds,
+        evaluation: poly.evaluation(),
+    }
+}
+
+/// Like [`sumcheck_virtual`] on the packed shares of the extensions. Every round message is
+/// opened to all parties, so every party returns the plain proof of [`sumcheck_virtual`] on the
+/// packed evaluations. The shares of products of `poly.degree()` extensions must still be
+/// unpackable, see [`PackedSharingParams::unpack_product`].
+pub async fn c_sumcheck_virtual<F: FftField, Net: MPCSerializeNet>(
+    poly: &VirtualPolynomial<F>,
+    challenge: &[F],
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<SumcheckProof<F>, MPCNetError> {
+    let c_sumcheck_timer = start_timer!("Collaborative sumcheck virtual", net.is_leader());
+    let degree = poly.degree();
+    let mut rounds = Vec::new();
+    // n and l must be powers of 2
+    let n = poly.num_vars();
+    let l: usize = pp.l.trailing_zeros() as usize;
+    let mut poly = poly.clone();
+    // Phase 1, the messages are shares of the sums of every slot.
+    let timer = start_timer!("Local: Phase 1", net.is_leader());
+    for challenge in &challenge[..n] {
+        let message = poly.round_message();
+        rounds.push(open_round_message(message, degree, true, pp, net, sid).await?);
+        poly.bind(*challenge);
+    }
+    end_timer!(timer);
+    for mle in poly.mles.iter_mut() {
+        debug_assert!(mle.len() == 1);
+        *mle = pss2ss(mle[0], pp, net, sid).await?;
+    }
+    // Phase 2, the messages are shares of single values.
+    let timer = start_timer!("Local: Phase 2", net.is_leader());
+    for challenge in &challenge[n..n + l] {
+        let message = poly.round_message();
+        rounds.push(open_round_message(message, degree, false, pp, net, sid).await?);
+        poly.bind(*challenge);
+    }
+    end_timer!(timer);
+    let evaluation =
+        open_round_message(vec![poly.evaluation()], degree, false, pp, net, sid).await?[0];
+    end_timer!(c_sumcheck_timer);
+    Ok(SumcheckProof { rounds, evaluation })
+}
+
+/// Like [`sumcheck_virtual`] on the polynomial whose extensions have their evaluation
+/// `c * n_parties + party` at evaluation `c` of the `poly` of `party`. The round messages of
+/// phase 1 are summed over all parties, the leader runs phase 2 over the evaluations of the
+/// parties, and every party returns the proof of [`sumcheck_virtual`] on the whole polynomial.
+pub async fn d_sumcheck_virtual<F: FftField, Net: MPCSerializeNet>(
+    poly: &VirtualPolynomial<F>,
+    challenge: &[F],
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<SumcheckProof<F>, MPCNetError> {
+    let d_sumcheck_timer = start_timer!("Distributed sumcheck virtual", net.is_leader());
+    let mut rounds = Vec::new();
+    let n = poly.num_vars();
+    let mut poly = poly.clone();
+    // Phase 1
+    let timer = start_timer!("Local: Phase 1", net.is_leader());
+    for challenge in &challenge[..n] {
+        let message = net
+            .leader_compute_element(
+                &poly.round_message(),
+                sid,
+                |messages| {
+                    let sum = messages
+                        .into_iter()
+                        .reduce(|a, b| a.iter().zip(&b).map(|(a, b)| *a + b).collect())
+                        .unwrap();
+                    vec![sum; net.n_parties()]
+                },
+                "Sum round messages",
+            )
+            .await?;
+        rounds.push(message);
+        poly.bind(*challenge);
+    }
+    end_timer!(timer);
+    // Phase 2
+    let values: Vec<F> = poly.mles.iter().map(|mle| mle[0]).collect();
+    let values = net.worker_send_or_leader_receive_element(&values, sid).await?;
+    let phase2 = values.map(|values| {
+        let timer = start_timer!("Leader: Phase 2", net.is_leader());
+        let leader_poly = VirtualPolynomial {
+            mles: transpose(values),
+            products: poly.products.clone(),
         };
-        result.push(res);
-        // (1-u)*x0 + u*x1
-        last_round_f = parts_f
-            .0
-            .iter()
-            .zip(parts_f.1.iter())
-            .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-            .collect::<Vec<_>>();
-        last_round_g = parts_g
-            .0
-            .iter()
-            .zip(parts_g.1.iter())
-            .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-            .collect::<Vec<_>>();
+        let proof = sumcheck_virtual(&leader_poly, &challenge[n..]);
+        end_timer!(timer);
+        vec![proof; net.n_parties()]
+    });
+    let phase2 = net.worker_receive_or_leader_send_element(phase2, sid).await?;
+    rounds.extend(phase2.rounds);
+    end_timer!(d_sumcheck_timer);
+    Ok(SumcheckProof {
+        rounds,
+        evaluation: phase2.evaluation,
+    })
+}
+
+/// The round message of `poly + scale·mask` for the variable after the `bound` ones.
+fn masked_round_message<F: Field>(
+    poly: &VirtualPolynomial<F>,
+    mask: &MaskPolynomial<F>,
+    scale: F,
+    bound: &[F],
+) -> Vec<F> {
+    let mask_message = mask.round_message(bound, poly.degree());
+    poly.round_message()
+        .into_iter()
+        .zip(mask_message)
+        .map(|(a, b)| a + scale * b)
+        .collect()
+}
+
+/// Zero-knowledge [`sumcheck_virtual`]: the sumcheck of `poly + rho·mask`, see
+/// [`crate::mask_poly`]. The mask must have the degree of `poly`.
+pub fn zk_sumcheck_virtual<F: Field>(
+    poly: &VirtualPolynomial<F>,
+    mask: &MaskPolynomial<F>,
+    rho: F,
+    challenge: &[F],
+) -> ZkSumcheckProof<F> {
+    let n = poly.num_vars();
+    assert_eq!(mask.num_vars(), n);
+    assert_eq!(mask.degree(), poly.degree());
+    let mut poly = poly.clone();
+    let mut rounds = Vec::new();
+    for i in 0..n {
+        rounds.push(masked_round_message(&poly, mask, rho, &challenge[..i]));
+        poly.bind(challenge[i]);
+    }
+    ZkSumcheckProof {
+        mask_sum: mask.sum(),
+        proof: SumcheckProof {
+            rounds,
+            evaluation: poly.evaluation() + rho * mask.evaluate(&challenge[..n]),
+        },
+    }
+}
+
+/// Absorb the sum of the mask and squeeze `ρ`.
+fn mask_challenge<F: PrimeField>(mask_sum: F, transcript: &mut Transcript) -> F {
+    transcript.append_serializable(MASK_SUM_LABEL, &mask_sum);
+    transcript.challenge_scalar(MASK_CHALLENGE_LABEL)
+}
+
+/// Like [`zk_sumcheck_virtual`], with `ρ` squeezed from `transcript` after absorbing the sum of
+/// the mask, and the challenge of every round after absorbing the masked round message. The
+/// caller absorbs the commitments of `poly` and the mask before. Returns the proof, `ρ` and the
+/// challenges.
+pub fn zk_sumcheck_virtual_fs<F: PrimeField>(
+    poly: &VirtualPolynomial<F>,
+    mask: &MaskPolynomial<F>,
+    transcript: &mut Transcript,
+) -> (ZkSumcheckProof<F>, F, Vec<F>) {
+    let n = poly.num_vars();
+    assert_eq!(mask.num_vars(), n);
+    assert_eq!(mask.degree(), poly.degree());
+    let mask_sum = mask.sum();
+    let rho = mask_challenge(mask_sum, transcript);
+    let mut poly = poly.clone();
+    let mut rounds = Vec::new();
+    let mut challenges = Vec::new();
+    for _ in 0..n {
+        let message = masked_round_message(&poly, mask, rho, &challenges);
+        let challenge = next_round_challenge(&message, transcript);
+        rounds.push(message);
+        challenges.push(challenge);
+        poly.bind(challenge);
+    }
+    let evaluation = poly.evaluation() + rho * mask.evaluate(&challenges);
+    transcript.append_serializable(EVALUATION_LABEL, &evaluation);
+    let proof = ZkSumcheckProof {
+        mask_sum,
+        proof: SumcheckProof { rounds, evaluation },
+    };
+    (proof, rho, challenges)
+}
+
+/// Zero-knowledge [`c_sumcheck_virtual`] with the shares of a mask from
+/// [`crate::mask_poly::c_mask_polynomial`] in all variables, the `l` of the slots last. No `t`
+/// parties learn the mask, only its sum and the evaluation of `poly + rho·mask` at the challenges
+/// are opened. Every party returns the proof of [`zk_sumcheck_virtual`] on the packed
+/// evaluations.
+pub async fn c_zk_sumcheck_virtual<F: FftField, Net: MPCSerializeNet>(
+    poly: &VirtualPolynomial<F>,
+    mask: &MaskPolynomial<F>,
+    rho: F,
+    challenge: &[F],
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<ZkSumcheckProof<F>, MPCNetError> {
+    let c_sumcheck_timer = start_timer!("Collaborative zero-knowledge sumcheck", net.is_leader());
+    let degree = poly.degree();
+    // n and l must be powers of 2
+    let n = poly.num_vars();
+    let l: usize = pp.l.trailing_zeros() as usize;
+    assert_eq!(mask.num_vars(), n + l);
+    assert_eq!(mask.degree(), degree);
+    let mut rounds = Vec::new();
+    let mut poly = poly.clone();
+    // Every slot of the mask shares holds the value.
+    let mask_sum = open_round_message(vec![mask.sum()], 1, false, pp, net, sid).await?[0];
+    // Phase 1, the slots are summed, so the mask is split across them.
+    let timer = start_timer!("Local: Phase 1", net.is_leader());
+    let scale = rho * F::from(pp.l as u64).inverse().unwrap();
+    for i in 0..n {
+        let message = masked_round_message(&poly, mask, scale, &challenge[..i]);
+        rounds.push(open_round_message(message, degree, true, pp, net, sid).await?);
+        poly.bind(challenge[i]);
+    }
+    end_timer!(timer);
+    for mle in poly.mles.iter_mut() {
+        debug_assert!(mle.len() == 1);
+        *mle = pss2ss(mle[0], pp, net, sid).await?;
+    }
+    // Phase 2
+    let timer = start_timer!("Local: Phase 2", net.is_leader());
+    for i in n..n + l {
+        let message = masked_round_message(&poly, mask, rho, &challenge[..i]);
+        rounds.push(open_round_message(message, degree, false, pp, net, sid).await?);
+        poly.bind(challenge[i]);
+    }
+    end_timer!(timer);
+    let evaluation = poly.evaluation() + rho * mask.evaluate(&challenge[..n + l]);
+    let evaluation = open_round_message(vec![evaluation], degree, false, pp, net, sid).await?[0];
+    end_timer!(c_sumcheck_timer);
+    Ok(ZkSumcheckProof {
+        mask_sum,
+        proof: SumcheckProof { rounds, evaluation },
+    })
+}
+
+/// Like [`c_zk_sumcheck_virtual`] with `ρ` and the challenges squeezed from `transcript`, see
+/// [`zk_sumcheck_virtual_fs`]. Every party returns the same as [`zk_sumcheck_virtual_fs`] on the
+/// packed evaluations.
+pub async fn c_zk_sumcheck_virtual_fs<F: PrimeField, Net: MPCSerializeNet>(
+    poly: &VirtualPolynomial<F>,
+    mask: &MaskPolynomial<F>,
+    pp: &PackedSharingParams<F>,
+    transcript: &mut Transcript,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<(ZkSumcheckProof<F>, F, Vec<F>), MPCNetError> {
+    let c_sumcheck_timer = start_timer!(
+        "Collaborative zero-knowledge sumcheck with transcript",
+        net.is_leader()
+    );
+    let degree = poly.degree();
+    // n and l must be powers of 2
+    let n = poly.num_vars();
+    let l: usize = pp.l.trailing_zeros() as usize;
+    assert_eq!(mask.num_vars(), n + l);
+    assert_eq!(mask.degree(), degree);
+    let mut rounds = Vec::new();
+    let mut challenges = Vec::new();
+    let mut poly = poly.clone();
+    // Every slot of the mask shares holds the value.
+    let mask_sum = open_round_message(vec![mask.sum()], 1, false, pp, net, sid).await?[0];
+    let rho = mask_challenge(mask_sum, transcript);
+    // Phase 1, the slots are summed, so the mask is split across them.
+    let timer = start_timer!("Local: Phase 1", net.is_leader());
+    let scale = rho * F::from(pp.l as u64).inverse().unwrap();
+    for _ in 0..n {
+        let message = masked_round_message(&poly, mask, scale, &challenges);
+        let message = open_round_message(message, degree, true, pp, net, sid).await?;
+        let challenge = next_round_challenge(&message, transcript);
+        rounds.push(message);
+        challenges.push(challenge);
+        poly.bind(challenge);
     }
     end_timer!(timer);
-    // Put it in the second slot to keep consistency with vec.split_at(vec.len()/2). In which case the first part will be empty.
-    result.push((F::ZERO, last_round_f[0] * last_round_g[0], F::ZERO));
-    end_timer!(d_sumcheck_product_timer);
-    Ok(result)
+    for mle in poly.mles.iter_mut() {
+        debug_assert!(mle.len() == 1);
+        *mle = pss2ss(mle[0], pp, net, sid).await?;
+    }
+    // Phase 2
+    let timer = start_timer!("Local: Phase 2", net.is_leader());
+    for _ in 0..l {
+        let message = masked_round_message(&poly, mask, rho, &challenges);
+        let message = open_round_message(message, degree, false, pp, net, sid).await?;
+        let challenge = next_round_challenge(&message, transcript);
+        rounds.push(message);
+        challenges.push(challenge);
+        poly.bind(challenge);
+    }
+    end_timer!(timer);
+    let evaluation = poly.evaluation() + rho * mask.evaluate(&challenges);
+    let evaluation = open_round_message(vec![evaluation], degree, false, pp, net, sid).await?[0];
+    transcript.append_serializable(EVALUATION_LABEL, &evaluation);
+    end_timer!(c_sumcheck_timer);
+    let proof = ZkSumcheckProof {
+        mask_sum,
+        proof: SumcheckProof { rounds, evaluation },
+    };
+    Ok((proof, rho, challenges))
 }
 
-pub async fn d_sumcheck<F: FftField, Net: MPCSerializeNet>(
-    partial_poly: &Vec<F>,
-    challenge: &Vec<F>,
+/// The round message of the sum of all parties' `poly` plus `rho·mask`, for every party.
+async fn d_masked_round_message<F: FftField, Net: MPCSerializeNet>(
+    poly: &VirtualPolynomial<F>,
+    mask: &MaskPolynomial<F>,
+    rho: F,
+    bound: &[F],
     net: &Net,
     sid: MultiplexedStreamID,
-) -> Result<Vec<(F, F)>, MPCNetError> {
-    let d_sumcheck_timer = start_timer!("Distributed sumcheck", net.is_leader());
-    let mut result = Vec::new();
-    // n and l must be powers of 2
-    let n = partial_poly.len().trailing_zeros() as usize;
+) -> Result<Vec<F>, MPCNetError> {
+    // Every party knows the mask and adds it to the sum of the round messages.
+    let message = net
+        .leader_compute_element(
+            &poly.round_message(),
+            sid,
+            |messages| {
+                let sum = messages
+                    .into_iter()
+                    .reduce(|a, b| a.iter().zip(&b).map(|(a, b)| *a + b).collect())
+                    .unwrap();
+                vec![sum; net.n_parties()]
+            },
+            "Sum round messages",
+        )
+        .await?;
+    let mask_message = mask.round_message(bound, poly.degree());
+    Ok(message
+        .into_iter()
+        .zip(mask_message)
+        .map(|(a, b)| a + rho * b)
+        .collect())
+}
+
+/// Zero-knowledge [`d_sumcheck_virtual`] with a mask from
+/// [`crate::mask_poly::d_mask_polynomial`] in all variables, the ones of the parties last.
+/// Every party returns the proof of [`zk_sumcheck_virtual`] on the whole polynomial.
+pub async fn d_zk_sumcheck_virtual<F: FftField, Net: MPCSerializeNet>(
+    poly: &VirtualPolynomial<F>,
+    mask: &MaskPolynomial<F>,
+    rho: F,
+    challenge: &[F],
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<ZkSumcheckProof<F>, MPCNetError> {
+    let d_sumcheck_timer = start_timer!("Distributed zero-knowledge sumcheck", net.is_leader());
+    let n = poly.num_vars();
     let s = net.n_parties().trailing_zeros() as usize;
-    let mut last_round = partial_poly.clone();
+    assert_eq!(mask.num_vars(), n + s);
+    assert_eq!(mask.degree(), poly.degree());
+    let mut rounds = Vec::new();
+    let mut poly = poly.clone();
     // Phase 1
     let timer = start_timer!("Local: Phase 1", net.is_leader());
     for i in 0..n {
-        let parts = last_round.split_at(last_round.len() / 2);
-        let one_minus_challenge = F::ONE - challenge[i];
-        let res1 = parts.0.iter().sum();
-        let res2 = parts.1.iter().sum();
-        result.push((res1, res2));
-        // result.push((parts.0.iter().sum(), parts.1.iter().sum()));
-        let this_round = parts
-            .0
-            .iter()
-            .zip(parts.1.iter())
-            .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-            .collect::<Vec<_>>();
-        last_round = this_round;
+        rounds.push(d_masked_round_message(&poly, mask, rho, &challenge[..i], net, sid).await?);
+        poly.bind(challenge[i]);
     }
-    debug_assert!(last_round.len() == 1);
-    result.push((F::ZERO, last_round[0]));
     end_timer!(timer);
-    let local_polys = net
-        .worker_send_or_leader_receive_element(&result, sid)
-        .await?;
     // Phase 2
-    let result = if let Some(local_polys) = local_polys {
+    let values: Vec<F> = poly.mles.iter().map(|mle| mle[0]).collect();
+    let values = net.worker_send_or_leader_receive_element(&values, sid).await?;
+    let phase2 = values.map(|values| {
         let timer = start_timer!("Leader: Phase 2", net.is_leader());
-        let mut result = (0..n)
-            .flat_map(|i| {
-                local_polys
-                    .iter()
-                    .map(|x| x[i])
-                    .reduce(|(a1, b1), (a2, b2)| (a1 + a2, b1 + b2))
-            })
-            .collect::<Vec<_>>();
-        let leader_f = local_polys.iter().map(|x| x.last().unwrap().1).collect::<Vec<_>>();
-        let mut last_round = leader_f.clone();
-        for i in n..n+s {
-            let parts = last_round.split_at(last_round.len() / 2);
-            let one_minus_challenge = F::ONE - challenge[i];
-            let res1 = parts.0.iter().sum();
-            let res2 = parts.1.iter().sum();
-            result.push((res1, res2));
-            let this_round = parts
-                .0
-                .iter()
-                .zip(parts.1.iter())
-                .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-                .collect::<Vec<_>>();
-            last_round = this_round;
+        let mut leader_poly = VirtualPolynomial {
+            mles: transpose(values),
+            products: poly.products.clone(),
+        };
+        let mut rounds = Vec::new();
+        for i in n..n + s {
+            rounds.push(masked_round_message(&leader_poly, mask, rho, &challenge[..i]));
+            leader_poly.bind(challenge[i]);
         }
+        let evaluation = leader_poly.evaluation() + rho * mask.evaluate(&challenge[..n + s]);
         end_timer!(timer);
-        result
-    } else {
-        vec![]
-    };
-
+        vec![SumcheckProof { rounds, evaluation }; net.n_parties()]
+    });
+    let phase2 = net.worker_receive_or_leader_send_element(phase2, sid).await?;
+    rounds.extend(phase2.rounds);
     end_timer!(d_sumcheck_timer);
-    Ok(result)
+    Ok(ZkSumcheckProof {
+        mask_sum: mask.sum(),
+        proof: SumcheckProof {
+            rounds,
+            evaluation: phase2.evaluation,
+        },
+    })
 }
 
-pub async fn d_sumcheck_product<F: FftField, Net: MPCSerializeNet>(
-    partial_f: &Vec<F>,
-    partial_g: &Vec<F>,
-    challenge: &Vec<F>,
+/// Like [`d_zk_sumcheck_virtual`] with `ρ` and the challenges squeezed from `transcript`, see
+/// [`zk_sumcheck_virtual_fs`]. The leader runs phase 2 on a copy of the transcript and the
+/// parties replay its messages. Every party returns the same as [`zk_sumcheck_virtual_fs`] on the
+/// whole polynomial.
+pub async fn d_zk_sumcheck_virtual_fs<F: PrimeField, Net: MPCSerializeNet>(
+    poly: &VirtualPolynomial<F>,
+    mask: &MaskPolynomial<F>,
+    transcript: &mut Transcript,
     net: &Net,
     sid: MultiplexedStreamID,
-) -> Result<Vec<(F, F, F)>, MPCNetError> {
-    let d_sumcheck_product_timer = start_timer!("Distributed sumcheck product", net.is_leader());
-    let mut result = Vec::new();
-    let n = partial_f.len().trailing_zeros() as usize;
+) -> Result<(ZkSumcheckProof<F>, F, Vec<F>), MPCNetError> {
+    let d_sumcheck_timer = start_timer!(
+        "Distributed zero-knowledge sumcheck with transcript",
+        net.is_leader()
+    );
+    let n = poly.num_vars();
     let s = net.n_parties().trailing_zeros() as usize;
-    assert_eq!(partial_f.len(), partial_g.len());
-    let mut last_round_f = partial_f.clone();
-    let mut last_round_g = partial_g.clone();
+    assert_eq!(mask.num_vars(), n + s);
+    assert_eq!(mask.degree(), poly.degree());
+    let mut rounds = Vec::new();
+    let mut challenges = Vec::new();
+    let mut poly = poly.clone();
+    let mask_sum = mask.sum();
+    let rho = mask_challenge(mask_sum, transcript);
     // Phase 1
-    // In this part the shares can be viewed as a whole. There's no need to go into them
-    // The result of this part is a degree 2d share since we perform multiplication between shares.
     let timer = start_timer!("Local: Phase 1", net.is_leader());
-    for i in 0..n {
-        let parts_f = last_round_f.split_at(last_round_f.len() / 2);
-        let parts_g = last_round_g.split_at(last_round_g.len() / 2);
-        let one_minus_challenge = F::ONE - challenge[i];
-        let res: (F, F, F) = {
-            // t=0
-            let part0_sum = parts_f
-                .0
-                .iter()
-                .zip(parts_g.0.iter())
-                .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-            // t=1
-            let part1_sum = parts_f
-                .1
-                .iter()
-                .zip(parts_g.1.iter())
-                .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-            // t=2,
-            // in which case, the evaluation of f and g is not present in the bookkeeping table,
-            // we need to calculate them from (1-t)*x0+t*x1
-            let part2_f: Vec<_> = parts_f
-                .0
-                .iter()
-                .zip(parts_f.1.iter())
-                .map(|(x, y)| -*x + *y * F::from(2_u64))
-                .collect();
-            let part2_g: Vec<_> = parts_g
-                .0
-                .iter()
-                .zip(parts_g.1.iter())
-                .map(|(x, y)| -*x + *y * F::from(2_u64))
-                .collect();
-            let part2_sum = part2_f
-                .iter()
-                .zip(part2_g.iter())
-                .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-            (part0_sum, part1_sum, part2_sum)
-        };
-        result.push(res);
-        // (1-u)*x0 + u*x1
-        last_round_f = parts_f
-            .0
-            .iter()
-            .zip(parts_f.1.iter())
-            .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-            .collect::<Vec<_>>();
-        last_round_g = parts_g
-            .0
-            .iter()
-            .zip(parts_g.1.iter())
-            .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-            .collect::<Vec<_>>();
+    for _ in 0..n {
+        let message = d_masked_round_message(&poly, mask, rho, &challenges, net, sid).await?;
+        let challenge = next_round_challenge(&message, transcript);
+        rounds.push(message);
+        challenges.push(challenge);
+        poly.bind(challenge);
     }
     end_timer!(timer);
-    debug_assert!(last_round_f.len() == 1);
-    debug_assert!(last_round_g.len() == 1);
-    result.push((last_round_g[0], last_round_f[0], F::ZERO));
-    let local_polys = net
-        .worker_send_or_leader_receive_element(&result, sid)
-        .await?;
     // Phase 2
-    let result = if let Some(local_polys) = local_polys {
+    let values: Vec<F> = poly.mles.iter().map(|mle| mle[0]).collect();
+    let values = net.worker_send_or_leader_receive_element(&values, sid).await?;
+    let phase2 = values.map(|values| {
         let timer = start_timer!("Leader: Phase 2", net.is_leader());
-        let mut result = (0..n)
-            .flat_map(|i| {
-                local_polys
-                    .iter()
-                    .map(|x| x[i])
-                    .reduce(|(a1, b1, c1), (a2, b2, c2)| (a1 + a2, b1 + b2, c1 + c2))
-            })
-            .collect::<Vec<_>>();
-        let leader_f = local_polys.iter().map(|x| x.last().unwrap().1).collect::<Vec<_>>();
-        let leader_g: Vec<_> = local_polys.iter().map(|x| x.last().unwrap().0).collect::<Vec<_>>();
-        let mut last_round_f = leader_f.clone();
-        let mut last_round_g = leader_g.clone();
-        for i in n..n+s {
-            let parts_f = last_round_f.split_at(last_round_f.len() / 2);
-            let parts_g = last_round_g.split_at(last_round_g.len() / 2);
-            let one_minus_challenge = F::ONE - challenge[i];
-            let res: (F, F, F) = {
-                // t=0
-                let part0_sum = parts_f
-                    .0
-                    .iter()
-                    .zip(parts_g.0.iter())
-                    .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-                // t=1
-                let part1_sum = parts_f
-                    .1
-                    .iter()
-                    .zip(parts_g.1.iter())
-                    .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-                // t=2,
-                // in which case, the evaluation of f and g is not present in the bookkeeping table,
-                // we need to calculate them from (1-t)*x0+t*x1
-                let part2_f: Vec<_> = parts_f
-                    .0
-                    .iter()
-                    .zip(parts_f.1.iter())
-                    .map(|(x, y)| -*x + *y * F::from(2_u64))
-                    .collect();
-                let part2_g: Vec<_> = parts_g
-                    .0
-                    .iter()
-                    .zip(parts_g.1.iter())
-                    .map(|(x, y)| -*x + *y * F::from(2_u64))
-                    .collect();
-                let part2_sum = part2_f
-                    .iter()
-                    .zip(part2_g.iter())
-                    .fold(F::zero(), |acc, (x, y)| acc + *x * *y);
-                (part0_sum, part1_sum, part2_sum)
-            };
-            result.push(res);
-            // (1-u)*x0 + u*x1
-            last_round_f = parts_f
-                .0
-                .iter()
-                .zip(parts_f.1.iter())
-                .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-                .collect::<Vec<_>>();
-            last_round_g = parts_g
-                .0
-                .iter()
-                .zip(parts_g.1.iter())
-                .map(|(a, b)| *a * one_minus_challenge + *b * challenge[i])
-                .collect::<Vec<_>>();
+        let mut leader_poly = VirtualPolynomial {
+            mles: transpose(values),
+            products: poly.products.clone(),
+        };
+        let mut transcript = transcript.clone();
+        let mut challenges = challenges.clone();
+        let mut rounds = Vec::new();
+        for _ in 0..s {
+            let message = masked_round_message(&leader_poly, mask, rho, &challenges);
+            let challenge = next_round_challenge(&message, &mut transcript);
+            rounds.push(message);
+            challenges.push(challenge);
+            leader_poly.bind(challenge);
         }
+        let evaluation = leader_poly.evaluation() + rho * mask.evaluate(&challenges);
         end_timer!(timer);
-        result
-    } else {
-        vec![]
+        vec![SumcheckProof { rounds, evaluation }; net.n_parties()]
+    });
+    let phase2 = net.worker_receive_or_leader_send_element(phase2, sid).await?;
+    for message in &phase2.rounds {
+        challenges.push(next_round_challenge(message, transcript));
+    }
+    transcript.append_serializable(EVALUATION_LABEL, &phase2.evaluation);
+    rounds.extend(phase2.rounds);
+    end_timer!(d_sumcheck_timer);
+    let proof = ZkSumcheckProof {
+        mask_sum,
+        proof: SumcheckProof {
+            rounds,
+            evaluation: phase2.evaluation,
+        },
     };
-    end_timer!(d_sumcheck_product_timer);
-    Ok(result)
+    Ok((proof, rho, challenges))
 }
 
 #[cfg(test)]
 mod tests {
     use ark_ec::bls12::Bls12Config;
     use ark_ec::Group;
+    use ark_ff::Field;
     use ark_std::One;
     use ark_std::UniformRand;
-    use itertools::Itertools;
 
     use mpc_net::MPCNet;
     use mpc_net::MultiplexedStreamID;
@@ -531,6 +1182,19 @@ mod tests {
 
     use crate::dsumcheck::c_sumcheck;
     use crate::dsumcheck::c_sumcheck_product;
+    use crate::dsumcheck::{c_sumcheck_fs, c_sumcheck_product_fs, sumcheck_fs, sumcheck_product_fs};
+    use crate::dsumcheck::{d_sumcheck_fs, d_sumcheck_product_fs};
+    use crate::dsumcheck::{c_sumcheck_virtual, d_sumcheck_virtual, sumcheck_virtual};
+    use crate::dsumcheck::{c_zk_sumcheck_virtual, d_zk_sumcheck_virtual, zk_sumcheck_virtual};
+    use crate::dsumcheck::{
+        c_zk_sumcheck_virtual_fs, d_zk_sumcheck_virtual_fs, zk_sumcheck_virtual_fs,
+    };
+    use crate::mask_poly::{c_mask_polynomial, d_mask_polynomial, MaskPolynomial};
+    use crate::dsumcheck::{sumcheck, sumcheck_product};
+    use crate::mle::fix_variable;
+    use crate::sumcheck_proof::{SumcheckError, SumcheckProof};
+    use crate::transcript::Transcript;
+    use crate::virtual_poly::VirtualPolynomial;
     // use crate::dmsm::packexp_from_public;
     // use crate::dmsm::unpackexp;
     use crate::utils::operator::transpose;
@@ -622,11 +1286,11 @@ mod tests {
 
     #[tokio::test]
     async fn dsumcheck_test() {
-        let net = LocalTestNet::new_local_testnet(L * 4).await.unwrap();
+        let net = LocalTestNet::new_local_testnet(L * 8).await.unwrap();
         let pp = PackedSharingParams::<Fr>::new(L);
         let rng = &mut ark_std::test_rng();
         let x: Vec<Fr> = (0..2usize.pow(N as u32)).map(|_| Fr::rand(rng)).collect();
-        let mut workers = vec![Vec::new(); L * 4];
+        let mut workers = vec![Vec::new(); L * 8];
         x.chunks(L).enumerate().for_each(|(_, chunk)| {
             let shares = pp.pack_from_public(chunk.to_vec());
             shares.into_iter().enumerate().for_each(|(j, share)| {
@@ -636,7 +1300,7 @@ mod tests {
         // Verification part
         let mut sum0 = Vec::new();
         let mut sum1 = Vec::new();
-        for j in 0..L * 4 {
+        for j in 0..L * 8 {
             let (part0, part1) = workers[j].split_at(workers[j].len() / 2);
             let res0 = part0.iter().sum::<Fr>();
             let res1 = part1.iter().sum::<Fr>();
@@ -667,18 +1331,12 @@ mod tests {
                 },
             )
             .await;
-        let result = transpose(result);
-        let result: Vec<(Fr, Fr)> = result
-            .into_iter()
-            .map(|x| {
-                let (vec0, vec1): (Vec<Fr>, Vec<Fr>) = x.into_iter().unzip();
-                let res0 = pp.unpack(vec0);
-                let res1 = pp.unpack(vec1);
-                assert!(res0.windows(2).all(|w| w[0] == w[1]));
-                assert!(res1.windows(2).all(|w| w[0] == w[1]));
-                (res0[0], res1[0])
-            })
-            .collect();
+        // Every party learns the plain round messages.
+        let expected = sumcheck(&x, &challenge);
+        for result in &result {
+            assert_eq!(result, &expected);
+        }
+        let result = result[0].clone();
         assert_eq!(result[0].0, proof0);
         assert_eq!(result[0].1, proof1);
         assert!(check_sumcheck(h, result, challenge));
@@ -808,12 +1466,12 @@ mod tests {
     // }
     #[tokio::test]
     async fn dsumcheck_product_test() {
-        let net = LocalTestNet::new_local_testnet(L * 4).await.unwrap();
+        let net = LocalTestNet::new_local_testnet(L * 8).await.unwrap();
         let pp = PackedSharingParams::<Fr>::new(L);
         let rng = &mut ark_std::test_rng();
         let x: Vec<Fr> = (0..2usize.pow(N as u32)).map(|_| Fr::rand(rng)).collect();
-        let mut workers_f = vec![Vec::new(); L * 4];
-        let mut workers_g = vec![Vec::new(); L * 4];
+        let mut workers_f = vec![Vec::new(); L * 8];
+        let mut workers_g = vec![Vec::new(); L * 8];
         x.chunks(L).enumerate().for_each(|(_, chunk)| {
             let shares = pp.pack_from_public(chunk.to_vec());
             shares.into_iter().enumerate().for_each(|(j, share)| {
@@ -827,7 +1485,7 @@ mod tests {
         let result = net
             .simulate_network_round(
                 (workers_f, workers_g, challenge.clone()),
-                |net: mpc_net::multi::MPCNetConnection<tokio::net::TcpStream>,
+                |net: mpc_net::multi::MPCNetConnection,
                  (shares_f, shares_g, challenge)| async move {
                     let pp = PackedSharingParams::<Fr>::new(L);
                     c_sumcheck_product(
@@ -843,21 +1501,410 @@ mod tests {
                 },
             )
             .await;
-        let result = transpose(result);
-        let result: Vec<(Fr, Fr, Fr)> = result
+        // Every party learns the plain round messages, the degree 2(t+l) shares are unpacked.
+        let expected = sumcheck_product(&x, &x, &challenge);
+        for result in &result {
+            assert_eq!(result, &expected);
+        }
+        let h: Fr = x.iter().map(|x| x * x).sum();
+        assert!(check_sumcheck_product(h, result[0].clone(), challenge));
+    }
+
+    #[tokio::test]
+    async fn c_sumcheck_fs_test() {
+        let net = LocalTestNet::new_local_testnet(L * 8).await.unwrap();
+        let pp = PackedSharingParams::<Fr>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let f: Vec<Fr> = (0..2usize.pow(N as u32)).map(|_| Fr::rand(rng)).collect();
+        let g: Vec<Fr> = (0..2usize.pow(N as u32)).map(|_| Fr::rand(rng)).collect();
+        let share = |x: &Vec<Fr>| {
+            transpose(x.chunks(L).map(|chunk| pp.pack_from_public(chunk.to_vec())).collect())
+        };
+        let (shares_f, shares_g) = (share(&f), share(&g));
+
+        let mut transcript = Transcript::new(b"test");
+        let expected = sumcheck_fs(&f, &mut transcript);
+        let expected_product = sumcheck_product_fs(&f, &g, &mut transcript);
+        let results = net
+            .simulate_network_round((shares_f, shares_g), |net, (shares_f, shares_g)| async move {
+                let pp = PackedSharingParams::<Fr>::new(L);
+                let id = net.party_id() as usize;
+                let sid = MultiplexedStreamID::Zero;
+                let mut transcript = Transcript::new(b"test");
+                let proof = c_sumcheck_fs(&shares_f[id], &pp, &mut transcript, &net, sid)
+                    .await
+                    .unwrap();
+                let product_proof = c_sumcheck_product_fs(
+                    &shares_f[id],
+                    &shares_g[id],
+                    &pp,
+                    &mut transcript,
+                    &net,
+                    sid,
+                )
+                .await
+                .unwrap();
+                (proof, product_proof, transcript)
+            })
+            .await;
+        for (proof, product_proof, party_transcript) in results {
+            assert_eq!(proof, expected);
+            assert_eq!(product_proof, expected_product);
+            assert_eq!(party_transcript, transcript);
+        }
+    }
+
+    #[tokio::test]
+    async fn d_sumcheck_fs_test() {
+        const PARTIES: usize = 4;
+        let net = LocalTestNet::new_local_testnet(PARTIES).await.unwrap();
+        let rng = &mut ark_std::test_rng();
+        let f: Vec<Fr> = (0..2usize.pow(N as u32)).map(|_| Fr::rand(rng)).collect();
+        let g: Vec<Fr> = (0..2usize.pow(N as u32)).map(|_| Fr::rand(rng)).collect();
+        // Evaluation `c * PARTIES + party` is evaluation `c` of `party`.
+        let partial = |x: &Vec<Fr>| {
+            (0..PARTIES)
+                .map(|party| x.iter().skip(party).step_by(PARTIES).copied().collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        let (partial_f, partial_g) = (partial(&f), partial(&g));
+
+        let mut transcript = Transcript::new(b"test");
+        let expected = sumcheck_fs(&f, &mut transcript);
+        let expected_product = sumcheck_product_fs(&f, &g, &mut transcript);
+        let results = net
+            .simulate_network_round(
+                (partial_f, partial_g),
+                |net, (partial_f, partial_g)| async move {
+                    let id = net.party_id() as usize;
+                    let sid = MultiplexedStreamID::Zero;
+                    let mut transcript = Transcript::new(b"test");
+                    let proof = d_sumcheck_fs(&partial_f[id], &mut transcript, &net, sid)
+                        .await
+                        .unwrap();
+                    let product_proof = d_sumcheck_product_fs(
+                        &partial_f[id],
+                        &partial_g[id],
+                        &mut transcript,
+                        &net,
+                        sid,
+                    )
+                    .await
+                    .unwrap();
+                    (proof, product_proof, transcript)
+                },
+            )
+            .await;
+        for (proof, product_proof, party_transcript) in results {
+            assert_eq!(proof, expected);
+            assert_eq!(product_proof, expected_product);
+            assert_eq!(party_transcript, transcript);
+        }
+    }
+
+    /// eq·(q1·(a+b) + q2·a·b − c + I), the gate identity of HyperPlonk, of the extensions
+    /// eq, q1, q2, a, b, c and I.
+    fn gate_identity(mles: Vec<Vec<Fr>>) -> VirtualPolynomial<Fr> {
+        let mut poly = VirtualPolynomial::new();
+        let [eq, q1, q2, a, b, c, i] = <[_; 7]>::try_from(mles)
+            .unwrap()
+            .map(|mle| poly.add_mle(mle));
+        poly.add_product(Fr::one(), [eq, q1, a]);
+        poly.add_product(Fr::one(), [eq, q1, b]);
+        poly.add_product(Fr::one(), [eq, q2, a, b]);
+        poly.add_product(-Fr::one(), [eq, c]);
+        poly.add_product(Fr::one(), [eq, i]);
+        poly
+    }
+
+    #[test]
+    fn sumcheck_virtual_test() {
+        let rng = &mut ark_std::test_rng();
+        let mles: Vec<Vec<Fr>> =
+            (0..7).map(|_| (0..1 << N).map(|_| Fr::rand(rng)).collect()).collect();
+        let challenge: Vec<Fr> = (0..N).map(|_| Fr::rand(rng)).collect();
+        let poly = gate_identity(mles.clone());
+        assert_eq!(poly.degree(), 4);
+
+        let proof = sumcheck_virtual(&poly, &challenge);
+        assert!(proof.rounds.iter().all(|round| round.len() == 5));
+        assert_eq!(proof.verify(poly.sum(), poly.degree(), &challenge), Ok(proof.evaluation));
+        let at_challenge = gate_identity(
+            mles.iter().map(|mle| fix_variable(mle, &challenge)).collect(),
+        );
+        assert_eq!(proof.evaluation, at_challenge.evaluation());
+
+        // Single extensions and products of two are the sumchecks of before.
+        let (f, g) = (mles[0].clone(), mles[1].clone());
+        let mut poly = VirtualPolynomial::new();
+        let f_index = poly.add_mle(f.clone());
+        poly.add_product(Fr::one(), [f_index]);
+        assert_eq!(
+            sumcheck_virtual(&poly, &challenge),
+            SumcheckProof::from(sumcheck(&f, &challenge))
+        );
+        let g_index = poly.add_mle(g.clone());
+        poly.products = vec![(Fr::one(), vec![f_index, g_index])];
+        assert_eq!(
+            sumcheck_virtual(&poly, &challenge),
+            SumcheckProof::from(sumcheck_product(&f, &g, &challenge))
+        );
+    }
+
+    #[tokio::test]
+    async fn c_sumcheck_virtual_test() {
+        let net = LocalTestNet::new_local_testnet(L * 8).await.unwrap();
+        let pp = PackedSharingParams::<Fr>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let mles: Vec<Vec<Fr>> =
+            (0..7).map(|_| (0..1 << N).map(|_| Fr::rand(rng)).collect()).collect();
+        let challenge: Vec<Fr> = (0..N).map(|_| Fr::rand(rng)).collect();
+        let expected = sumcheck_virtual(&gate_identity(mles.clone()), &challenge);
+        // Party `j` holds share `j` of every extension.
+        let shares: Vec<Vec<Vec<Fr>>> = transpose(
+            mles.iter()
+                .map(|mle| {
+                    transpose(
+                        mle.chunks(L).map(|chunk| pp.pack_from_public(chunk.to_vec())).collect(),
+                    )
+                })
+                .collect(),
+        );
+        let results = net
+            .simulate_network_round((shares, challenge), |net, (shares, challenge)| async move {
+                let pp = PackedSharingParams::<Fr>::new(L);
+                let poly = gate_identity(shares[net.party_id() as usize].clone());
+                c_sumcheck_virtual(&poly, &challenge, &pp, &net, MultiplexedStreamID::Zero)
+                    .await
+                    .unwrap()
+            })
+            .await;
+        for proof in results {
+            assert_eq!(proof, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn d_sumcheck_virtual_test() {
+        const PARTIES: usize = 4;
+        let net = LocalTestNet::new_local_testnet(PARTIES).await.unwrap();
+        let rng = &mut ark_std::test_rng();
+        let mles: Vec<Vec<Fr>> =
+            (0..7).map(|_| (0..1 << N).map(|_| Fr::rand(rng)).collect()).collect();
+        let challenge: Vec<Fr> = (0..N).map(|_| Fr::rand(rng)).collect();
+        let expected = sumcheck_virtual(&gate_identity(mles.clone()), &challenge);
+        // Evaluation `c * PARTIES + party` is evaluation `c` of `party`.
+        let partial: Vec<Vec<Vec<Fr>>> = (0..PARTIES)
+            .map(|party| {
+                mles.iter()
+                    .map(|mle| mle.iter().skip(party).step_by(PARTIES).copied().collect())
+                    .collect()
+            })
+            .collect();
+        let results = net
+            .simulate_network_round((partial, challenge), |net, (partial, challenge)| async move {
+                let poly = gate_identity(partial[net.party_id() as usize].clone());
+                d_sumcheck_virtual(&poly, &challenge, &net, MultiplexedStreamID::Zero)
+                    .await
+                    .unwrap()
+            })
+            .await;
+        for proof in results {
+            assert_eq!(proof, expected);
+        }
+    }
+
+    #[test]
+    fn zk_sumcheck_virtual_test() {
+        let rng = &mut ark_std::test_rng();
+        let mles: Vec<Vec<Fr>> =
+            (0..7).map(|_| (0..1 << N).map(|_| Fr::rand(rng)).collect()).collect();
+        let challenge: Vec<Fr> = (0..N).map(|_| Fr::rand(rng)).collect();
+        let rho = Fr::rand(rng);
+        let poly = gate_identity(mles);
+        let degree = poly.degree();
+        let mask = MaskPolynomial::rand(N, degree);
+
+        let proof = zk_sumcheck_virtual(&poly, &mask, rho, &challenge);
+        let expected = sumcheck_virtual(&poly, &challenge);
+        assert_eq!(
+            proof.verify(poly.sum(), degree, rho, &challenge),
+            Ok(expected.evaluation + rho * mask.evaluate(&challenge))
+        );
+        // The round messages are masked.
+        assert!(proof.proof.rounds.iter().zip(&expected.rounds).all(|(a, b)| a != b));
+        assert!(proof.verify(poly.sum() + Fr::one(), degree, rho, &challenge).is_err());
+
+        let mut transcript = Transcript::new(b"test");
+        let (proof, rho, challenge) = zk_sumcheck_virtual_fs(&poly, &mask, &mut transcript);
+        assert_eq!(proof, zk_sumcheck_virtual(&poly, &mask, rho, &challenge));
+        let mut verifier = Transcript::new(b"test");
+        assert_eq!(
+            proof.verify_fs(poly.sum(), N, degree, &mut verifier),
+            Ok((proof.proof.evaluation, rho, challenge))
+        );
+        assert_eq!(verifier, transcript);
+        let mut truncated = proof.clone();
+        truncated.proof.rounds.pop();
+        assert_eq!(
+            truncated.verify_fs(poly.sum(), N, degree, &mut Transcript::new(b"test")),
+            Err(SumcheckError::RoundCount { expected: N, actual: N - 1 })
+        );
+    }
+
+    #[test]
+    fn zk_sumcheck_hides_evaluation_test() {
+        let rng = &mut ark_std::test_rng();
+        let mles: Vec<Vec<Fr>> =
+            (0..7).map(|_| (0..1 << N).map(|_| Fr::rand(rng)).collect()).collect();
+        let poly = gate_identity(mles);
+        let mask = MaskPolynomial::rand(N, poly.degree());
+        let (proof, rho, challenge) =
+            zk_sumcheck_virtual_fs(&poly, &mask, &mut Transcript::new(b"test"));
+
+        // f + δ·(x_0 - 1/2) and g - δ/ρ·(x_0 - 1/2) have the sums of f and g and the same
+        // f + ρ·g, so they have the same proof, but the evaluation of f differs.
+        let delta = Fr::rand(rng);
+        let half = Fr::from(2u64).inverse().unwrap();
+        let mut other = poly.clone();
+        let x0 = (0..1 << N).map(|i| Fr::from((i >> (N - 1)) as u64) - half).collect();
+        let x0 = other.add_mle(x0);
+        other.add_product(delta, [x0]);
+        let mut other_mask = mask.clone();
+        other_mask.constant += delta / rho * half;
+        other_mask.coefficients[0][0] -= delta / rho;
+        assert_eq!(other.sum(), poly.sum());
+        let (other_proof, _, _) =
+            zk_sumcheck_virtual_fs(&other, &other_mask, &mut Transcript::new(b"test"));
+        assert_eq!(other_proof, proof);
+        let at_challenge = |poly: &VirtualPolynomial<Fr>| {
+            let mut poly = poly.clone();
+            for challenge in &challenge {
+                poly.bind(*challenge);
+            }
+            poly.evaluation()
+        };
+        assert_ne!(at_challenge(&other), at_challenge(&poly));
+    }
+
+    #[tokio::test]
+    async fn c_zk_sumcheck_virtual_test() {
+        let net = LocalTestNet::new_local_testnet(L * 8).await.unwrap();
+        let pp = PackedSharingParams::<Fr>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let mles: Vec<Vec<Fr>> =
+            (0..7).map(|_| (0..1 << N).map(|_| Fr::rand(rng)).collect()).collect();
+        let challenge: Vec<Fr> = (0..N).map(|_| Fr::rand(rng)).collect();
+        let rho = Fr::rand(rng);
+        let poly = gate_identity(mles.clone());
+        let degree = poly.degree();
+        let expected = sumcheck_virtual(&poly, &challenge);
+        let shares: Vec<Vec<Vec<Fr>>> = transpose(
+            mles.iter()
+                .map(|mle| {
+                    transpose(
+                        mle.chunks(L).map(|chunk| pp.pack_from_public(chunk.to_vec())).collect(),
+                    )
+                })
+                .collect(),
+        );
+        let results = net
+            .simulate_network_round(
+                (shares, challenge.clone()),
+                move |net, (shares, challenge)| async move {
+                    let pp = PackedSharingParams::<Fr>::new(L);
+                    let sid = MultiplexedStreamID::Zero;
+                    let poly = gate_identity(shares[net.party_id() as usize].clone());
+                    let mask = c_mask_polynomial(N, poly.degree(), &pp, &net, sid).await.unwrap();
+                    let proof =
+                        c_zk_sumcheck_virtual(&poly, &mask, rho, &challenge, &pp, &net, sid)
+                            .await
+                            .unwrap();
+                    let mut transcript = Transcript::new(b"test");
+                    let fs_proof =
+                        c_zk_sumcheck_virtual_fs(&poly, &mask, &pp, &mut transcript, &net, sid)
+                            .await
+                            .unwrap();
+                    (proof, fs_proof, mask)
+                },
+            )
+            .await;
+        for (proof, fs_proof, _) in &results {
+            assert_eq!(proof, &results[0].0);
+            assert_eq!(fs_proof, &results[0].1);
+        }
+        // Every slot of the mask shares holds the coefficient.
+        let coefficients = transpose(results.iter().map(|(_, _, mask)| mask.flatten()).collect())
             .into_iter()
-            .map(|x| {
-                let (vec0, vec1, vec2): (Vec<Fr>, Vec<Fr>, Vec<Fr>) = x.into_iter().multiunzip();
-                let res0 = pp.unpack(vec0);
-                let res1 = pp.unpack(vec1);
-                let res2 = pp.unpack(vec2);
-                assert!(res0.windows(2).all(|w| w[0] == w[1]));
-                assert!(res1.windows(2).all(|w| w[0] == w[1]));
-                assert!(res2.windows(2).all(|w| w[0] == w[1]));
-                (res0[0], res1[0], res2[0])
+            .map(|shares| pp.unpack(shares)[0])
+            .collect();
+        let mask = MaskPolynomial::from_flat(N, degree, coefficients);
+        let (proof, (fs_proof, fs_rho, fs_challenge), _) = &results[0];
+        assert_eq!(proof, &zk_sumcheck_virtual(&poly, &mask, rho, &challenge));
+        assert_eq!(
+            proof.verify(poly.sum(), degree, rho, &challenge),
+            Ok(expected.evaluation + rho * mask.evaluate(&challenge))
+        );
+        assert!(proof.proof.rounds.iter().zip(&expected.rounds).all(|(a, b)| a != b));
+
+        let mut transcript = Transcript::new(b"test");
+        let expected = zk_sumcheck_virtual_fs(&poly, &mask, &mut transcript);
+        assert_eq!((fs_proof, fs_rho, fs_challenge), (&expected.0, &expected.1, &expected.2));
+        let mut verifier = Transcript::new(b"test");
+        assert!(fs_proof.verify_fs(poly.sum(), N, degree, &mut verifier).is_ok());
+        assert_eq!(verifier, transcript);
+    }
+
+    #[tokio::test]
+    async fn d_zk_sumcheck_virtual_test() {
+        const PARTIES: usize = 4;
+        let net = LocalTestNet::new_local_testnet(PARTIES).await.unwrap();
+        let rng = &mut ark_std::test_rng();
+        let mles: Vec<Vec<Fr>> =
+            (0..7).map(|_| (0..1 << N).map(|_| Fr::rand(rng)).collect()).collect();
+        let challenge: Vec<Fr> = (0..N).map(|_| Fr::rand(rng)).collect();
+        let rho = Fr::rand(rng);
+        let poly = gate_identity(mles.clone());
+        let degree = poly.degree();
+        let expected = sumcheck_virtual(&poly, &challenge);
+        let partial: Vec<Vec<Vec<Fr>>> = (0..PARTIES)
+            .map(|party| {
+                mles.iter()
+                    .map(|mle| mle.iter().skip(party).step_by(PARTIES).copied().collect())
+                    .collect()
             })
             .collect();
-        let h: Fr = x.iter().map(|x| x * x).sum();
-        assert!(check_sumcheck_product(h, result, challenge));
+        let results = net
+            .simulate_network_round(
+                (partial, challenge.clone()),
+                move |net, (partial, challenge)| async move {
+                    let sid = MultiplexedStreamID::Zero;
+                    let poly = gate_identity(partial[net.party_id() as usize].clone());
+                    let mask = d_mask_polynomial(N, poly.degree(), &net, sid).await.unwrap();
+                    let proof = d_zk_sumcheck_virtual(&poly, &mask, rho, &challenge, &net, sid)
+                        .await
+                        .unwrap();
+                    let mut transcript = Transcript::new(b"test");
+                    let fs_proof =
+                        d_zk_sumcheck_virtual_fs(&poly, &mask, &mut transcript, &net, sid)
+                            .await
+                            .unwrap();
+                    (proof, fs_proof, mask, transcript)
+                },
+            )
+            .await;
+        let (proof, fs_proof, mask, _) = &results[0];
+        for result in &results {
+            assert_eq!(result, &results[0]);
+        }
+        // The parties prove what the monolithic prover would with their joint mask.
+        assert_eq!(proof, &zk_sumcheck_virtual(&poly, mask, rho, &challenge));
+        assert_eq!(
+            proof.verify(poly.sum(), degree, rho, &challenge),
+            Ok(expected.evaluation + rho * mask.evaluate(&challenge))
+        );
+        let mut transcript = Transcript::new(b"test");
+        assert_eq!(fs_proof, &zk_sumcheck_virtual_fs(&poly, mask, &mut transcript));
+        assert_eq!(results[0].3, transcript);
     }
 }
@@ -6,6 +6,10 @@ pub mod dpoly_comm;
 pub mod mle;
 pub mod unpack;
 pub mod dacc_product;
+pub mod transcript;
+pub mod sumcheck_proof;
+pub mod virtual_poly;
+pub mod mask_poly;
 
 use ark_ff::UniformRand;
 use rand::{rngs::StdRng, SeedableRng};
@@ -0,0 +1,248 @@
+//! Masking polynomials of the zero-knowledge sumchecks.
+//!
+//! The prover of a zero-knowledge sumcheck of `f` picks a random masking polynomial `g`, sends
+//! its sum `G` over the hypercube, receives a challenge `ρ` and runs the sumcheck of `f + ρ·g`
+//! instead, whose round messages are random (Libra, Xie et al. 2019). The verifier checks the
+//! last round against `f(r) + ρ·g(r)`, so `g` has to be committed before `ρ`, and only the
+//! combination `f + ρ·g` is opened at the challenges `r`: `g(r)` would give away `f(r)`. The
+//! commitments are up to the caller.
+//!
+//! The parties generate `g` jointly, every party contributes a random polynomial and `g` is their
+//! sum, so it is random as long as one party is honest.
+use ark_ff::{FftField, Field};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use mpc_net::{MPCNetError, MultiplexedStreamID};
+use secret_sharing::pss::PackedSharingParams;
+
+use crate::random_evaluations;
+use crate::utils::operator::transpose;
+use crate::utils::serializing_net::MPCSerializeNet;
+
+/// The polynomial `g(x) = c + Σ_i g_i(x_i)` of univariates `g_i` without constant term. It has
+/// few coefficients but the degree of the `g_i` in every variable.
+///
+/// Every function of it is linear in the coefficients, so it works on shares of them as well.
+#[derive(Clone, Debug, Default, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct MaskPolynomial<F: Field> {
+    /// The constant term `c`.
+    pub constant: F,
+    /// `coefficients[i][k]` is the coefficient of `x_i^(k+1)`.
+    pub coefficients: Vec<Vec<F>>,
+}
+
+impl<F: Field> MaskPolynomial<F> {
+    /// A random polynomial in `num_vars` variables of degree `degree` in every variable.
+    pub fn rand(num_vars: usize, degree: usize) -> Self {
+        Self::from_flat(num_vars, degree, random_evaluations(1 + num_vars * degree))
+    }
+
+    /// The polynomial of the coefficients laid out by [`MaskPolynomial::flatten`].
+    pub fn from_flat(num_vars: usize, degree: usize, flat: Vec<F>) -> Self {
+        assert_eq!(flat.len(), 1 + num_vars * degree);
+        Self {
+            constant: flat[0],
+            coefficients: flat[1..].chunks(degree).map(|c| c.to_vec()).collect(),
+        }
+    }
+
+    /// The constant term followed by the coefficients of every variable.
+    pub fn flatten(&self) -> Vec<F> {
+        std::iter::once(self.constant)
+            .chain(self.coefficients.iter().flatten().copied())
+            .collect()
+    }
+
+    pub fn num_vars(&self) -> usize {
+        self.coefficients.len()
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coefficients.first().map_or(0, |c| c.len())
+    }
+
+    /// `g_i(x)`.
+    fn univariate(&self, i: usize, x: F) -> F {
+        self.coefficients[i].iter().rev().fold(F::ZERO, |acc, c| (acc + c) * x)
+    }
+
+    /// The sum over the hypercube, `2^m·c + 2^(m-1)·Σ_i g_i(1)`.
+    pub fn sum(&self) -> F {
+        let m = self.num_vars() as u64;
+        if m == 0 {
+            return self.constant;
+        }
+        let ones: F = (0..self.num_vars()).map(|i| self.univariate(i, F::ONE)).sum();
+        F::from(2u64).pow([m]) * self.constant + F::from(2u64).pow([m - 1]) * ones
+    }
+
+    /// The value at `point`.
+    pub fn evaluate(&self, point: &[F]) -> F {
+        debug_assert_eq!(point.len(), self.num_vars());
+        point
+            .iter()
+            .enumerate()
+            .fold(self.constant, |acc, (i, x)| acc + self.univariate(i, *x))
+    }
+
+    /// The round polynomial for the variable after the `bound` ones, summed over the others and
+    /// evaluated at 0, 1, ..., `degree`.
+    pub fn round_message(&self, bound: &[F], degree: usize) -> Vec<F> {
+        let j = bound.len();
+        let rest = (self.num_vars() - j - 1) as u64;
+        let base = bound
+            .iter()
+            .enumerate()
+            .fold(self.constant, |acc, (i, x)| acc + self.univariate(i, *x));
+        // Every free variable x_i after j is 1 in half of the points, where g_i is g_i(1).
+        let tail = if rest == 0 {
+            F::ZERO
+        } else {
+            let ones: F = (j + 1..self.num_vars()).map(|i| self.univariate(i, F::ONE)).sum();
+            F::from(2u64).pow([rest - 1]) * ones
+        };
+        let count = F::from(2u64).pow([rest]);
+        (0..=degree as u64)
+            .map(|t| count * (base + self.univariate(j, F::from(t))) + tail)
+            .collect()
+    }
+}
+
+/// Generate shares of a masking polynomial jointly. Every party shares every coefficient of its
+/// random contribution, packed into all slots, and the mask is the sum of the contributions. No
+/// `t` parties learn anything about it.
+pub async fn c_mask_polynomial<F: FftField, Net: MPCSerializeNet>(
+    num_vars: usize,
+    degree: usize,
+    pp: &PackedSharingParams<F>,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<MaskPolynomial<F>, MPCNetError> {
+    let contribution = MaskPolynomial::<F>::rand(num_vars, degree);
+    // out[j] are the shares of party j.
+    let out = transpose(
+        contribution
+            .flatten()
+            .into_iter()
+            .map(|c| share_single(c, pp))
+            .collect(),
+    );
+    let received = net.all_to_all_element(out, sid).await?;
+    let shares = received
+        .into_iter()
+        .reduce(|a, b| a.iter().zip(&b).map(|(a, b)| *a + b).collect())
+        .unwrap();
+    Ok(MaskPolynomial::from_flat(num_vars, degree, shares))
+}
+
+/// Packs `secret` into every slot of a sharing with fresh randomness, unlike
+/// [`PackedSharingParams::pack_single`].
+fn share_single<F: FftField>(secret: F, pp: &PackedSharingParams<F>) -> Vec<F> {
+    let mut points = vec![secret; pp.l];
+    points.extend(random_evaluations::<F>(pp.t + 1));
+    pp.pack_from_public_in_place(&mut points);
+    points
+}
+
+/// Generate a masking polynomial jointly, every party learns it. The leader sums the random
+/// contributions of the parties.
+pub async fn d_mask_polynomial<F: FftField, Net: MPCSerializeNet>(
+    num_vars: usize,
+    degree: usize,
+    net: &Net,
+    sid: MultiplexedStreamID,
+) -> Result<MaskPolynomial<F>, MPCNetError> {
+    let contribution = MaskPolynomial::<F>::rand(num_vars, degree);
+    let mask = net
+        .leader_compute_element(
+            &contribution.flatten(),
+            sid,
+            |contributions| {
+                let sum = contributions
+                    .into_iter()
+                    .reduce(|a, b| a.iter().zip(&b).map(|(a, b)| *a + b).collect())
+                    .unwrap();
+                vec![sum; net.n_parties()]
+            },
+            "Sum masks",
+        )
+        .await?;
+    Ok(MaskPolynomial::from_flat(num_vars, degree, mask))
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr;
+    use ark_ff::Field;
+    use ark_std::UniformRand;
+    use secret_sharing::pss::PackedSharingParams;
+
+    use super::{share_single, MaskPolynomial};
+
+    #[test]
+    fn mask_polynomial_test() {
+        const M: usize = 4;
+        let rng = &mut ark_std::test_rng();
+        let mask = MaskPolynomial::<Fr>::rand(M, 3);
+        let point = |index: usize| -> Vec<Fr> {
+            (0..M).map(|i| Fr::from(((index >> (M - 1 - i)) & 1) as u64)).collect()
+        };
+        let sum: Fr = (0..1 << M).map(|index| mask.evaluate(&point(index))).sum();
+        assert_eq!(mask.sum(), sum);
+
+        // Every round sums to the claim of the round before.
+        let challenge: Vec<Fr> = (0..M).map(|_| Fr::rand(rng)).collect();
+        let mut claim = sum;
+        for j in 0..M {
+            let message = mask.round_message(&challenge[..j], 3);
+            assert_eq!(message[0] + message[1], claim);
+            // The round polynomial is g at the bound variables, t and any free ones.
+            for (t, evaluation) in message.iter().enumerate() {
+                let expected: Fr = (0..1 << (M - j - 1))
+                    .map(|index| {
+                        let mut x = challenge[..j].to_vec();
+                        x.push(Fr::from(t as u64));
+                        x.extend(&point(index)[j + 1..]);
+                        mask.evaluate(&x)
+                    })
+                    .sum();
+                assert_eq!(*evaluation, expected);
+            }
+            let mut x = challenge[..j].to_vec();
+            x.push(challenge[j]);
+            claim = (0..1 << (M - j - 1))
+                .map(|index| {
+                    let mut x = x.clone();
+                    x.extend(&point(index)[j + 1..]);
+                    mask.evaluate(&x)
+                })
+                .sum();
+        }
+        assert_eq!(claim, mask.evaluate(&challenge));
+        assert_ne!(mask.sum(), Fr::ZERO);
+    }
+
+    #[test]
+    fn share_single_test() {
+        const L: usize = 4;
+        let pp = PackedSharingParams::<Fr>::new(L);
+        let rng = &mut ark_std::test_rng();
+        let (x, y) = (Fr::rand(rng), Fr::rand(rng));
+
+        let shares = share_single(x, &pp);
+        assert_eq!(pp.unpack(shares.clone()), vec![x; L]);
+        assert_ne!(shares, share_single(x, &pp));
+
+        // The share of any party is consistent with any other coefficient: shift the sharing by
+        // one of y - x and cancel the share of the party with one of 0.
+        let diff = share_single(y - x, &pp);
+        let zero = share_single(Fr::ZERO, &pp);
+        for party in 0..pp.n {
+            let scale = diff[party] / zero[party];
+            let other: Vec<Fr> = (0..pp.n)
+                .map(|i| shares[i] + diff[i] - scale * zero[i])
+                .collect();
+            assert_eq!(other[party], shares[party]);
+            assert_eq!(pp.unpack(other), vec![y; L]);
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//! Verification of sumcheck proofs.
+//!
+//! The provers of [`crate::dsumcheck`] output one message per round, ending with the claimed
+//! evaluation of the summed polynomial at the challenges. [`SumcheckProof`] converts from any of
+//! them, as long as the messages are plain: the monolithic and the distributed outputs, the
+//! Fiat–Shamir outputs of every prover, and the collaborative outputs once opened.
+use ark_ff::{Field, PrimeField};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+
+use crate::dsumcheck::{
+    next_round_challenge, EVALUATION_LABEL, MASK_CHALLENGE_LABEL, MASK_SUM_LABEL,
+};
+use crate::transcript::Transcript;
+
+/// Why a [`SumcheckProof`] was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SumcheckError {
+    /// The proof has a different number of rounds than the polynomial has variables.
+    RoundCount { expected: usize, actual: usize },
+    /// The round polynomial is not given by exactly `degree + 1` evaluations.
+    Degree { round: usize },
+    /// `p(0) + p(1)` of the round polynomial is not the claim of the previous round.
+    RoundSum { round: usize },
+    /// The last round polynomial at its challenge is not the claimed evaluation.
+    Evaluation,
+}
+
+impl std::fmt::Display for SumcheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SumcheckError::RoundCount { expected, actual } => {
+                write!(f, "Expected {} rounds, the proof has {}", expected, actual)
+            }
+            SumcheckError::Degree { round } => {
+                write!(f, "Round {} does not have the expected degree", round)
+            }
+            SumcheckError::RoundSum { round } => {
+                write!(f, "Round {} does not sum to the previous claim", round)
+            }
+            SumcheckError::Evaluation => {
+                write!(f, "The last round does not match the claimed evaluation")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SumcheckError {}
+
+/// The round messages of a sumcheck.
+#[derive(Debug, Clone, Default, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct SumcheckProof<F: Field> {
+    /// The round polynomials, each evaluated at 0, 1, ..., its degree.
+    pub rounds: Vec<Vec<F>>,
+    /// The claimed evaluation of the summed polynomial at the challenges.
+    pub evaluation: F,
+}
+
+impl<F: Field> From<Vec<(F, F)>> for SumcheckProof<F> {
+    /// Converts the output of [`crate::dsumcheck::sumcheck`] and its variants, ending with
+    /// `(0, f(r))`.
+    fn from(mut messages: Vec<(F, F)>) -> Self {
+        let (_, evaluation) = messages.pop().expect("the proof ends with the evaluation");
+        Self {
+            rounds: messages.into_iter().map(|(a, b)| vec![a, b]).collect(),
+            evaluation,
+        }
+    }
+}
+
+impl<F: Field> From<Vec<(F, F, F)>> for SumcheckProof<F> {
+    /// Converts the output of [`crate::dsumcheck::sumcheck_product`] and its variants, ending
+    /// with `(0, f(r)·g(r), 0)`.
+    fn from(mut messages: Vec<(F, F, F)>) -> Self {
+        let (_, evaluation, _) = messages.pop().expect("the proof ends with the evaluation");
+        Self {
+            rounds: messages.into_iter().map(|(a, b, c)| vec![a, b, c]).collect(),
+            evaluation,
+        }
+    }
+}
+
+impl<F: Field> SumcheckProof<F> {
+    /// Check that the proof sums to `claimed_sum` over the hypercube with the verifier sending
+    /// `challenges`, every round polynomial of degree `degree` in its variable: 1 for
+    /// [`crate::dsumcheck::sumcheck`], 2 for [`crate::dsumcheck::sumcheck_product`] and the
+    /// degree of the polynomial for the virtual ones. Returns the claimed evaluation at
+    /// `challenges`, which the caller still has to check against the polynomial.
+    pub fn verify(
+        &self,
+        claimed_sum: F,
+        degree: usize,
+        challenges: &[F],
+    ) -> Result<F, SumcheckError> {
+        if self.rounds.len() != challenges.len() {
+            return Err(SumcheckError::RoundCount {
+                expected: challenges.len(),
+                actual: self.rounds.len(),
+            });
+        }
+        let mut claim = claimed_sum;
+        for (round, (evaluations, challenge)) in self.rounds.iter().zip(challenges).enumerate() {
+            claim = check_round(round, evaluations, degree, claim, *challenge)?;
+        }
+        if claim != self.evaluation {
+            return Err(SumcheckError::Evaluation);
+        }
+        Ok(self.evaluation)
+    }
+}
+
+impl<F: PrimeField> SumcheckProof<F> {
+    /// Like [`SumcheckProof::verify`] for a polynomial in `num_vars` variables, with the
+    /// challenges squeezed from `transcript` like the Fiat–Shamir provers of [`crate::dsumcheck`]
+    /// do. Returns the claimed evaluation and the challenges.
+    pub fn verify_fs(
+        &self,
+        claimed_sum: F,
+        num_vars: usize,
+        degree: usize,
+        transcript: &mut Transcript,
+    ) -> Result<(F, Vec<F>), SumcheckError> {
+        if self.rounds.len() != num_vars {
+            return Err(SumcheckError::RoundCount {
+                expected: num_vars,
+                actual: self.rounds.len(),
+            });
+        }
+        let mut claim = claimed_sum;
+        let mut challenges = Vec::new();
+        for (round, evaluations) in self.rounds.iter().enumerate() {
+            let challenge = next_round_challenge(evaluations, transcript);
+            claim = check_round(round, evaluations, degree, claim, challenge)?;
+            challenges.push(challenge);
+        }
+        if claim != self.evaluation {
+            return Err(SumcheckError::Evaluation);
+        }
+        transcript.append_serializable(EVALUATION_LABEL, &self.evaluation);
+        Ok((self.evaluation, challenges))
+    }
+}
+
+/// A zero-knowledge sumcheck of `f`, the sumcheck of `f + ρ·g` for a masking polynomial `g`,
+/// see [`crate::mask_poly`]. It ends with the evaluation of `f + ρ·g` only, neither `f(r)` nor
+/// `g(r)` is part of it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, CanonicalSerialize, CanonicalDeserialize)]
+pub struct ZkSumcheckProof<F: Field> {
+    /// The sum of `g` over the hypercube, sent before `ρ`.
+    pub mask_sum: F,
+    /// The sumcheck of `f + ρ·g`.
+    pub proof: SumcheckProof<F>,
+}
+
+impl<F: Field> ZkSumcheckProof<F> {
+    /// Check that `f` sums to `claimed_sum` over the hypercube with the verifier sending `rho`
+    /// and `challenges`, see [`SumcheckProof::verify`] for `degree`. Returns the claimed
+    /// evaluation of `f + ρ·g` at `challenges`, which the caller still has to check against an
+    /// opening of the committed `f + ρ·g`.
+    pub fn verify(
+        &self,
+        claimed_sum: F,
+        degree: usize,
+        rho: F,
+        challenges: &[F],
+    ) -> Result<F, SumcheckError> {
+        self.proof.verify(claimed_sum + rho * self.mask_sum, degree, challenges)
+    }
+}
+
+impl<F: PrimeField> ZkSumcheckProof<F> {
+    /// Like [`ZkSumcheckProof::verify`] for a polynomial in `num_vars` variables, with `ρ` and
+    /// the challenges squeezed from `transcript` like [`crate::dsumcheck::zk_sumcheck_virtual_fs`]
+    /// does, after the caller absorbed the commitment of the mask. Returns the claimed evaluation
+    /// of `f + ρ·g`, `ρ` and the challenges.
+    pub fn verify_fs(
+        &self,
+        claimed_sum: F,
+        num_vars: usize,
+        degree: usize,
+        transcript: &mut Transcript,
+    ) -> Result<(F, F, Vec<F>), SumcheckError> {
+        transcript.append_serializable(MASK_SUM_LABEL, &self.mask_sum);
+        let rho = transcript.challenge_scalar(MASK_CHALLENGE_LABEL);
+        let (evaluation, challenges) =
+            self.proof.verify_fs(claimed_sum + rho * self.mask_sum, num_vars, degree, transcript)?;
+        Ok((evaluation, rho, challenges))
+    }
+}
+
+/// Check the round polynomial of degree `degree` against `claim` and return its evaluation at
+/// `challenge`, the claim of the next round.
+fn check_round<F: Field>(
+    round: usize,
+    evaluations: &[F],
+    degree: usize,
+    claim: F,
+    challenge: F,
+) -> Result<F, SumcheckError> {
+    // A longer round message could be a polynomial of higher degree, which weakens soundness.
+    if evaluations.len() != degree + 1 || evaluations.len() < 2 {
+        return Err(SumcheckError::Degree { round });
+    }
+    if evaluations[0] + evaluations[1] != claim {
+        return Err(SumcheckError::RoundSum { round });
+    }
+    Ok(interpolate(evaluations, challenge))
+}
+
+/// Evaluate the polynomial of degree `evaluations.len() - 1` through `(i, evaluations[i])` at
+/// `x`, by Lagrange interpolation.
+fn interpolate<F: Field>(evaluations: &[F], x: F) -> F {
+    let points: Vec<F> = (0..evaluations.len() as u64).map(F::from).collect();
+    evaluations
+        .iter()
+        .zip(&points)
+        .map(|(y, xi)| {
+            let (num, den) = points
+                .iter()
+                .filter(|xj| *xj != xi)
+                .fold((F::ONE, F::ONE), |(num, den), xj| {
+                    (num * (x - xj), den * (*xi - xj))
+                });
+            *y * num * den.inverse().unwrap()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr;
+    use ark_std::UniformRand;
+    use mpc_net::{LocalTestNet, MPCNet, MultiplexedStreamID};
+
+    use super::{SumcheckError, SumcheckProof};
+    use crate::dsumcheck::{
+        d_sumcheck, d_sumcheck_product, d_sumcheck_product_fs, sumcheck, sumcheck_fs,
+        sumcheck_product, sumcheck_product_fs,
+    };
+    use crate::transcript::Transcript;
+
+    const N: usize = 4;
+
+    #[test]
+    fn verify_test() {
+        let rng = &mut ark_std::test_rng();
+        let f: Vec<Fr> = (0..1 << N).map(|_| Fr::rand(rng)).collect();
+        let g: Vec<Fr> = (0..1 << N).map(|_| Fr::rand(rng)).collect();
+        let challenge: Vec<Fr> = (0..N).map(|_| Fr::rand(rng)).collect();
+        let sum: Fr = f.iter().sum();
+        let product_sum: Fr = f.iter().zip(&g).map(|(f, g)| f * g).sum();
+
+        let proof = SumcheckProof::from(sumcheck(&f, &challenge));
+        assert_eq!(proof.verify(sum, 1, &challenge), Ok(proof.evaluation));
+        let product_proof = SumcheckProof::from(sumcheck_product(&f, &g, &challenge));
+        assert_eq!(product_proof.verify(product_sum, 2, &challenge), Ok(product_proof.evaluation));
+
+        let mut transcript = Transcript::new(b"test");
+        let (proof, challenge) = sumcheck_fs(&f, &mut transcript);
+        let (product_proof, product_challenge) = sumcheck_product_fs(&f, &g, &mut transcript);
+        let mut verifier = Transcript::new(b"test");
+        let (evaluation, verifier_challenge) =
+            SumcheckProof::from(proof).verify_fs(sum, N, 1, &mut verifier).unwrap();
+        assert_eq!(verifier_challenge, challenge);
+        let (product_evaluation, verifier_challenge) = SumcheckProof::from(product_proof)
+            .verify_fs(product_sum, N, 2, &mut verifier)
+            .unwrap();
+        assert_eq!(verifier_challenge, product_challenge);
+        assert_eq!(verifier, transcript);
+
+        // The claimed evaluations are the polynomials at the challenges.
+        let eq = |r: &[Fr], i: usize| {
+            r.iter()
+                .enumerate()
+                .map(|(k, r)| if (i >> (N - 1 - k)) & 1 == 1 { *r } else { Fr::from(1u64) - r })
+                .product::<Fr>()
+        };
+        let at = |x: &[Fr], r: &[Fr]| (0..1 << N).map(|i| x[i] * eq(r, i)).sum::<Fr>();
+        assert_eq!(evaluation, at(&f, &challenge));
+        assert_eq!(product_evaluation, at(&f, &product_challenge) * at(&g, &product_challenge));
+    }
+
+    #[test]
+    fn verify_rejects_test() {
+        let rng = &mut ark_std::test_rng();
+        let f: Vec<Fr> = (0..1 << N).map(|_| Fr::rand(rng)).collect();
+        let challenge: Vec<Fr> = (0..N).map(|_| Fr::rand(rng)).collect();
+        let sum: Fr = f.iter().sum();
+        let proof = SumcheckProof::from(sumcheck_product(&f, &f, &challenge));
+        let sum_squares: Fr = f.iter().map(|f| f * f).sum();
+
+        assert_eq!(proof.verify(sum, 2, &challenge), Err(SumcheckError::RoundSum { round: 0 }));
+        assert_eq!(
+            proof.verify(sum_squares, 2, &challenge[1..]),
+            Err(SumcheckError::RoundCount { expected: N - 1, actual: N })
+        );
+        let mut tampered = proof.clone();
+        tampered.rounds[2][2] += Fr::from(1u64);
+        assert_eq!(
+            tampered.verify(sum_squares, 2, &challenge),
+            Err(SumcheckError::RoundSum { round: 3 })
+        );
+        let mut tampered = proof.clone();
+        tampered.evaluation += Fr::from(1u64);
+        assert_eq!(tampered.verify(sum_squares, 2, &challenge), Err(SumcheckError::Evaluation));
+        let mut tampered = proof.clone();
+        tampered.rounds[1].truncate(1);
+        assert_eq!(
+            tampered.verify(sum_squares, 2, &challenge),
+            Err(SumcheckError::Degree { round: 1 })
+        );
+        // An over-long round message keeps the round sum but is of higher degree.
+        let mut tampered = proof.clone();
+        tampered.rounds[1].push(Fr::from(1u64));
+        assert_eq!(
+            tampered.verify(sum_squares, 2, &challenge),
+            Err(SumcheckError::Degree { round: 1 })
+        );
+        assert_eq!(
+            proof.verify(sum_squares, 1, &challenge),
+            Err(SumcheckError::Degree { round: 0 })
+        );
+
+        // With Fiat–Shamir, the number of rounds is not given by the challenges.
+        let mut transcript = Transcript::new(b"test");
+        let (fs_proof, _) = sumcheck_product_fs(&f, &f, &mut transcript);
+        let fs_proof = SumcheckProof::from(fs_proof);
+        // Without rounds, the claimed evaluation is the claimed sum.
+        let empty = SumcheckProof { rounds: vec![], evaluation: sum_squares };
+        assert_eq!(
+            empty.verify_fs(sum_squares, N, 2, &mut Transcript::new(b"test")),
+            Err(SumcheckError::RoundCount { expected: N, actual: 0 })
+        );
+        let mut short = fs_proof.clone();
+        short.rounds.pop();
+        assert_eq!(
+            short.verify_fs(sum_squares, N, 2, &mut Transcript::new(b"test")),
+            Err(SumcheckError::RoundCount { expected: N, actual: N - 1 })
+        );
+        assert!(fs_proof.verify_fs(sum_squares, N, 2, &mut Transcript::new(b"test")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn verify_distributed_test() {
+        const PARTIES: usize = 4;
+        let net = LocalTestNet::new_local_testnet(PARTIES).await.unwrap();
+        let rng = &mut ark_std::test_rng();
+        let f: Vec<Fr> = (0..1 << N).map(|_| Fr::rand(rng)).collect();
+        let g: Vec<Fr> = (0..1 << N).map(|_| Fr::rand(rng)).collect();
+        let challenge: Vec<Fr> = (0..N).map(|_| Fr::rand(rng)).collect();
+        let sum: Fr = f.iter().sum();
+        let product_sum: Fr = f.iter().zip(&g).map(|(f, g)| f * g).sum();
+        // Evaluation `c * PARTIES + party` is evaluation `c` of `party`.
+        let partial = |x: &Vec<Fr>| {
+            (0..PARTIES)
+                .map(|party| x.iter().skip(party).step_by(PARTIES).copied().collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        let results = net
+            .simulate_network_round(
+                (partial(&f), partial(&g), challenge.clone()),
+                |net, (partial_f, partial_g, challenge)| async move {
+                    let id = net.party_id() as usize;
+                    let sid = MultiplexedStreamID::Zero;
+                    let proof = d_sumcheck(&partial_f[id], &challenge, &net, sid).await.unwrap();
+                    let product_proof =
+                        d_sumcheck_product(&partial_f[id], &partial_g[id], &challenge, &net, sid)
+                            .await
+                            .unwrap();
+                    let mut transcript = Transcript::new(b"test");
+                    let (fs_proof, _) = d_sumcheck_product_fs(
+                        &partial_f[id],
+                        &partial_g[id],
+                        &mut transcript,
+                        &net,
+                        sid,
+                    )
+                    .await
+                    .unwrap();
+                    (proof, product_proof, fs_proof)
+                },
+            )
+            .await;
+        let (proof, product_proof, _) = &results[0];
+        let proof = SumcheckProof::from(proof.clone());
+        assert_eq!(proof, SumcheckProof::from(sumcheck(&f, &challenge)));
+        assert!(proof.verify(sum, 1, &challenge).is_ok());
+        let product_proof = SumcheckProof::from(product_proof.clone());
+        assert!(product_proof.verify(product_sum, 2, &challenge).is_ok());
+        for (_, _, fs_proof) in results {
+            let mut verifier = Transcript::new(b"test");
+            let fs_proof = SumcheckProof::from(fs_proof);
+            assert!(fs_proof.verify_fs(product_sum, N, 2, &mut verifier).is_ok());
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Fiat–Shamir transcripts.
+//!
+//! A [`Transcript`] replaces the verifier of an interactive protocol: the prover absorbs its
+//! messages and squeezes the challenges the verifier would have sent. The interface follows
+//! Merlin, every message and challenge is labeled, but the state is a SHA-256 hash chain.
+//!
+//! In a collaborative proof every party keeps its own transcript. The parties absorb the same
+//! opened messages, so they squeeze the same challenges without communicating.
+use ark_ff::PrimeField;
+use ark_serialize::CanonicalSerialize;
+use sha2::{Digest, Sha256};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transcript {
+    state: [u8; 32],
+}
+
+impl Transcript {
+    /// Start a transcript for the protocol named `label`.
+    pub fn new(label: &'static [u8]) -> Self {
+        let mut transcript = Self { state: [0; 32] };
+        transcript.append_message(b"protocol", label);
+        transcript
+    }
+
+    /// Absorb `message`.
+    pub fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.update(self.state);
+        // The lengths keep the encoding of consecutive messages injective.
+        hasher.update((label.len() as u64).to_le_bytes());
+        hasher.update(label);
+        hasher.update((message.len() as u64).to_le_bytes());
+        hasher.update(message);
+        self.state = hasher.finalize().into();
+    }
+
+    /// Absorb `value`, compressed.
+    pub fn append_serializable<T: CanonicalSerialize + ?Sized>(
+        &mut self,
+        label: &'static [u8],
+        value: &T,
+    ) {
+        let mut bytes = Vec::new();
+        value.serialize_compressed(&mut bytes).unwrap();
+        self.append_message(label, &bytes);
+    }
+
+    /// Squeeze `dest.len()` bytes.
+    pub fn challenge_bytes(&mut self, label: &'static [u8], dest: &mut [u8]) {
+        self.append_message(label, &(dest.len() as u64).to_le_bytes());
+        for (i, chunk) in dest.chunks_mut(32).enumerate() {
+            let mut hasher = Sha256::new();
+            hasher.update(self.state);
+            hasher.update((i as u64).to_le_bytes());
+            let block: [u8; 32] = hasher.finalize().into();
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+        // Later challenges must not repeat the bytes squeezed here.
+        self.append_message(b"squeezed", &[]);
+    }
+
+    /// Squeeze a field element. Twice the bytes of the modulus are reduced, so the element is
+    /// close to uniform.
+    pub fn challenge_scalar<F: PrimeField>(&mut self, label: &'static [u8]) -> F {
+        let mut bytes = vec![0; 2 * F::MODULUS_BIT_SIZE.div_ceil(8) as usize];
+        self.challenge_bytes(label, &mut bytes);
+        F::from_le_bytes_mod_order(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_bls12_377::Fr;
+
+    use super::Transcript;
+
+    #[test]
+    fn test_transcript() {
+        let mut a = Transcript::new(b"test");
+        let mut b = Transcript::new(b"test");
+        a.append_serializable(b"message", &Fr::from(7u64));
+        b.append_serializable(b"message", &Fr::from(7u64));
+        let challenge: Fr = a.challenge_scalar(b"challenge");
+        assert_eq!(challenge, b.challenge_scalar(b"challenge"));
+        // Squeezing moves the state on.
+        assert_ne!(challenge, a.challenge_scalar::<Fr>(b"challenge"));
+
+        // Different messages, labels or protocols give different challenges.
+        let challenge = |protocol, label, message: u64| {
+            let mut t = Transcript::new(protocol);
+            t.append_serializable(label, &Fr::from(message));
+            t.challenge_scalar::<Fr>(b"challenge")
+        };
+        let base = challenge(b"test", b"message", 7);
+        assert_ne!(base, challenge(b"test", b"message", 8));
+        assert_ne!(base, challenge(b"test", b"other", 7));
+        assert_ne!(base, challenge(b"other", b"message", 7));
+    }
+}
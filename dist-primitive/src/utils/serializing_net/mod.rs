@@ -0,0 +1,601 @@
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, SerializationError, Validate,
+};
+use async_trait::async_trait;
+use futures::stream::{FuturesOrdered, TryStreamExt};
+use mpc_net::envelope::typed;
+use tokio_util::bytes::{Buf, Bytes, BytesMut};
+
+use mpc_net::{BroadcastMode, MPCNet, MPCNetError, MultiplexedStreamID, PointEncoding};
+use mpc_net::tree_parent;
+use mpc_net::{end_timer, start_timer};
+
+mod simulated;
+
+/// Bytes serialized at a time when streaming elements.
+const STREAM_CHUNK_SIZE: usize = 1 << 20;
+
+/// Bytes of an element a [`StreamingDeserializer`] buffers before giving up on it. Streamed
+/// elements must serialize to at most this many bytes.
+const MAX_PENDING: usize = 4 * STREAM_CHUNK_SIZE;
+
+/// Serializes `elements` like a `Vec<T>`, lazily and in chunks of about [`STREAM_CHUNK_SIZE`]
+/// bytes.
+fn serialize_chunks<T: CanonicalSerialize>(
+    elements: &[T],
+    compress: Compress,
+) -> impl Iterator<Item = Bytes> + '_ {
+    let mut header = Vec::new();
+    (elements.len() as u64)
+        .serialize_with_mode(&mut header, compress)
+        .unwrap();
+    let mut header = Some(header);
+    let mut rest = elements;
+    std::iter::from_fn(move || {
+        let mut buf = match header.take() {
+            Some(header) => header,
+            None if rest.is_empty() => return None,
+            None => Vec::new(),
+        };
+        while let Some((first, tail)) = rest.split_first() {
+            if buf.len() >= STREAM_CHUNK_SIZE {
+                break;
+            }
+            first.serialize_with_mode(&mut buf, compress).unwrap();
+            rest = tail;
+        }
+        Some(buf.into())
+    })
+}
+
+/// Reports that the message `party` sent in the current step could not be deserialized.
+fn deserialize_error(
+    net: &(impl MPCNet + ?Sized),
+    party: u32,
+    err: SerializationError,
+) -> MPCNetError {
+    MPCNetError::Deserialize {
+        party,
+        step: net.current_step(),
+        source: Box::new(err),
+    }
+}
+
+/// Deserializes an element sent by `party`.
+fn deserialize_from<T: CanonicalDeserialize>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    party: u32,
+    bytes: &[u8],
+) -> Result<T, MPCNetError> {
+    T::deserialize_with_mode(bytes, net.compress(), Validate::Yes)
+        .map_err(|err| deserialize_error(net, party, err))
+}
+
+/// Deserializes a `Vec<T>` from chunks with arbitrary boundaries, element by element as the
+/// chunks arrive, so that the serialized vector is never in memory as a whole.
+struct StreamingDeserializer<T> {
+    compress: Compress,
+    buf: BytesMut,
+    len: Option<usize>,
+    elements: Vec<T>,
+    /// Why the bytes in `buf` could not be deserialized yet.
+    pending: Option<SerializationError>,
+}
+
+impl<T: CanonicalDeserialize> StreamingDeserializer<T> {
+    fn new(compress: Compress) -> Self {
+        Self {
+            compress,
+            buf: BytesMut::new(),
+            len: None,
+            elements: Vec::new(),
+            pending: None,
+        }
+    }
+
+    fn push(&mut self, chunk: Bytes) -> Result<(), SerializationError> {
+        let Self {
+            compress,
+            buf,
+            len,
+            elements,
+            pending,
+        } = self;
+        buf.extend_from_slice(&chunk);
+        let mut reader = &buf[..];
+        let mut consumed = 0;
+        loop {
+            let result = match *len {
+                None => u64::deserialize_with_mode(&mut reader, *compress, Validate::Yes).map(|l| {
+                    *len = Some(l as usize);
+                    elements.reserve((l as usize).min(STREAM_CHUNK_SIZE));
+                }),
+                Some(l) if elements.len() < l => {
+                    T::deserialize_with_mode(&mut reader, *compress, Validate::Yes)
+                        .map(|e| elements.push(e))
+                }
+                Some(_) => break,
+            };
+            match result {
+                Ok(()) => {
+                    consumed = buf.len() - reader.len();
+                    *pending = None;
+                }
+                // Truncated elements do not consistently fail with `UnexpectedEof`, so any
+                // error may just mean that the rest of the element is in the next chunk, unless
+                // more than an element is buffered already.
+                Err(e) if buf.len() - consumed > MAX_PENDING => return Err(e),
+                Err(e) => {
+                    *pending = Some(e);
+                    break;
+                }
+            }
+        }
+        buf.advance(consumed);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<T>, SerializationError> {
+        if let Some(e) = self.pending {
+            return Err(e);
+        }
+        match self.len {
+            Some(len) if self.elements.len() == len && self.buf.is_empty() => Ok(self.elements),
+            _ => Err(SerializationError::InvalidData),
+        }
+    }
+}
+
+/// The MPC net can serialize and deserialize elements. Should be useful for arkworks computation.
+///
+/// On a simulated network, see [`MPCNet::is_simulated`], the collectives pretend that every party
+/// holds what this party holds and only account the communication, see [`simulated`].
+#[async_trait]
+pub trait MPCSerializeNet: MPCNet {
+    /// The serialization mode matching the negotiated [`MPCNet::point_encoding`].
+    fn compress(&self) -> Compress {
+        match self.point_encoding() {
+            PointEncoding::Compressed => Compress::Yes,
+            PointEncoding::Uncompressed => Compress::No,
+        }
+    }
+
+    async fn worker_send_or_leader_receive_element<
+        T: CanonicalDeserialize + CanonicalSerialize + Clone,
+    >(
+        &self,
+        out: &T,
+        sid: MultiplexedStreamID,
+    ) -> Result<Option<Vec<T>>, MPCNetError> {
+        if self.is_simulated() {
+            return simulated::worker_send_or_leader_receive_element(self, out, 0);
+        }
+        let mut bytes_out = Vec::new();
+        out.serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+        let bytes_in = typed::<T, _>(self.worker_send_or_leader_receive(&bytes_out, sid)).await?;
+        if let Some(bytes_in) = bytes_in {
+            // This is leader
+            debug_assert!(self.is_leader());
+            let results: Vec<Result<T, MPCNetError>> = bytes_in
+                .into_iter()
+                .enumerate()
+                .map(|(party, b)| deserialize_from(self, party as u32, &b))
+                .collect();
+
+            let mut ret = Vec::new();
+            for result in results {
+                ret.push(result?);
+            }
+
+            Ok(Some(ret))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn dynamic_worker_send_or_leader_receive_element<
+        T: CanonicalDeserialize + CanonicalSerialize + Clone,
+    >(
+        &self,
+        out: &T,
+        receiver: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Option<Vec<T>>, MPCNetError> {
+        if self.is_simulated() {
+            return simulated::worker_send_or_leader_receive_element(self, out, receiver);
+        }
+        let mut bytes_out = Vec::new();
+        out.serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+        let bytes_in = typed::<T, _>(self.dynamic_worker_send_or_leader_receive(
+            &bytes_out, receiver, sid,
+        ))
+        .await?;
+        if let Some(bytes_in) = bytes_in {
+            // This is leader
+            debug_assert!(receiver == self.party_id());
+            let results: Vec<Result<T, MPCNetError>> = bytes_in
+                .into_iter()
+                .enumerate()
+                .map(|(party, b)| deserialize_from(self, party as u32, &b))
+                .collect();
+
+            let mut ret = Vec::new();
+            for result in results {
+                ret.push(result?);
+            }
+
+            Ok(Some(ret))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn worker_receive_or_leader_send_element<
+        T: CanonicalDeserialize + CanonicalSerialize + Send + Default,
+        // A bug of rustc, T does not have to be Send actually. See https://github.com/rust-lang/rust/issues/63768
+    >(
+        &self,
+        out: Option<Vec<T>>,
+        sid: MultiplexedStreamID,
+    ) -> Result<T, MPCNetError> {
+        if self.is_simulated() {
+            return simulated::worker_receive_or_leader_send_element(self, out);
+        }
+        let bytes = out.map(|outs| {
+            outs.iter()
+                .map(|out| {
+                    let mut bytes_out = Vec::new();
+                    out.serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+                    bytes_out.into()
+                })
+                .collect()
+        });
+
+        let bytes_in = typed::<T, _>(self.worker_receive_or_leader_send(bytes, sid)).await?;
+        deserialize_from(self, 0, &bytes_in)
+    }
+
+    async fn dynamic_worker_receive_or_worker_send_element<
+        T: CanonicalDeserialize + CanonicalSerialize + Send + Default,
+        // A bug of rustc, T does not have to be Send actually. See https://github.com/rust-lang/rust/issues/63768
+    >(
+        &self,
+        out: Option<Vec<T>>,
+        sender: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<T, MPCNetError> {
+        if self.is_simulated() {
+            return simulated::worker_receive_or_leader_send_element(self, out);
+        }
+        let bytes = out.map(|outs| {
+            outs.iter()
+                .map(|out| {
+                    let mut bytes_out = Vec::new();
+                    out.serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+                    bytes_out.into()
+                })
+                .collect()
+        });
+
+        let bytes_in =
+            typed::<T, _>(self.dynamic_worker_receive_or_leader_send(bytes, sender, sid)).await?;
+        deserialize_from(self, sender, &bytes_in)
+    }
+
+    /// Everyone sends bytes to the leader, who receives those bytes, runs a computation on them, and
+    /// redistributes the resulting bytes.
+    ///
+    /// The leader's computation is given by a function, `f`
+    /// proceeds.
+    async fn leader_compute_element<
+        T: CanonicalDeserialize + CanonicalSerialize + Send + Clone + Default,
+    >(
+        &self,
+        out: &T,
+        sid: MultiplexedStreamID,
+        f: impl Fn(Vec<T>) -> Vec<T> + Send,
+        for_what: &str,
+    ) -> Result<T, MPCNetError> {
+        let leader_response = self.worker_send_or_leader_receive_element(out, sid).await?;
+        let timer = start_timer!(format!("Leader: Compute element ({})", for_what), self.is_leader());
+        let leader_response = leader_response.map(f);
+        end_timer!(timer);
+        self.worker_receive_or_leader_send_element(leader_response, sid)
+            .await
+    }
+
+    /// Like [`MPCSerializeNet::leader_compute_element`] for a computation whose result is the same
+    /// for every party. The result is distributed with [`MPCNet::reliable_broadcast_from`].
+    async fn leader_compute_element_reliable<
+        T: CanonicalDeserialize + CanonicalSerialize + Send + Clone + Default,
+    >(
+        &self,
+        out: &T,
+        sid: MultiplexedStreamID,
+        f: impl Fn(Vec<T>) -> T + Send,
+        for_what: &str,
+        mode: BroadcastMode,
+    ) -> Result<T, MPCNetError> {
+        let leader_response = self.worker_send_or_leader_receive_element(out, sid).await?;
+        let timer = start_timer!(format!("Leader: Compute element ({})", for_what), self.is_leader());
+        if self.is_simulated() {
+            let leader_response = leader_response.map(f);
+            end_timer!(timer);
+            return Ok(simulated::reliable_broadcast_element(self, leader_response, mode));
+        }
+        let leader_response = leader_response.map(|r| {
+            let mut bytes_out = Vec::new();
+            f(r).serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+            bytes_out.into()
+        });
+        end_timer!(timer);
+        let bytes_in =
+            typed::<T, _>(self.reliable_broadcast_from(leader_response, 0, sid, mode)).await?;
+        deserialize_from(self, 0, &bytes_in)
+    }
+
+    /// Like [`MPCSerializeNet::worker_send_or_leader_receive_element`], but the elements are
+    /// combined with the associative `combine` along a binomial tree on their way to the leader,
+    /// see [`MPCNet::tree_worker_send_or_leader_receive`]. The leader receives the combination of
+    /// all elements.
+    async fn worker_send_or_leader_receive_element_tree<
+        T: CanonicalDeserialize + CanonicalSerialize + Send + Clone,
+    >(
+        &self,
+        out: &T,
+        sid: MultiplexedStreamID,
+        combine: impl Fn(T, T) -> T + Send + Sync,
+    ) -> Result<Option<T>, MPCNetError> {
+        if self.is_simulated() {
+            return Ok(simulated::worker_send_or_leader_receive_element_tree(self, out, combine));
+        }
+        let mut bytes_out = Vec::new();
+        out.serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+        let bytes_in = typed::<T, _>(self.tree_worker_send_or_leader_receive(&bytes_out, sid, |a, b| {
+            // Failures on `b` are attributed to the child that sent it by the tree collective.
+            let a = deserialize_from(self, self.party_id(), &a)?;
+            let b = deserialize_from(self, self.party_id(), &b)?;
+            let mut bytes_out = Vec::new();
+            combine(a, b).serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+            Ok(bytes_out.into())
+        }))
+        .await?;
+        match bytes_in {
+            Some(bytes_in) => Ok(Some(deserialize_from(self, self.party_id(), &bytes_in)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Tree-shaped variant of [`MPCSerializeNet::leader_compute_element`]. The elements are
+    /// combined with `combine` on their way to the leader, the leader applies `f` to the
+    /// combination and the result is sent back to every party along the same tree.
+    async fn leader_compute_element_tree<
+        T: CanonicalDeserialize + CanonicalSerialize + Send + Clone + Default,
+    >(
+        &self,
+        out: &T,
+        sid: MultiplexedStreamID,
+        combine: impl Fn(T, T) -> T + Send + Sync,
+        f: impl Fn(T) -> T + Send,
+        for_what: &str,
+    ) -> Result<T, MPCNetError> {
+        let leader_response = self
+            .worker_send_or_leader_receive_element_tree(out, sid, combine)
+            .await?;
+        let timer = start_timer!(format!("Leader: Compute element ({})", for_what), self.is_leader());
+        if self.is_simulated() {
+            let leader_response = leader_response.map(f);
+            end_timer!(timer);
+            return Ok(simulated::tree_send_element(self, leader_response));
+        }
+        let leader_response = leader_response.map(|r| {
+            let mut bytes_out = Vec::new();
+            f(r).serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+            bytes_out.into()
+        });
+        end_timer!(timer);
+        let bytes_in =
+            typed::<T, _>(self.tree_worker_receive_or_leader_send(leader_response, sid)).await?;
+        let parent = tree_parent(self.party_id()).unwrap_or(self.party_id());
+        deserialize_from(self, parent, &bytes_in)
+    }
+
+    /// Send `elements` to party `id`. They are serialized like a `Vec<T>`, chunk by chunk, so
+    /// that the serialized vector is never in memory as a whole.
+    async fn send_elements_to<T: CanonicalSerialize + Sync>(
+        &self,
+        elements: &[T],
+        id: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MPCNetError> {
+        let chunks = serialize_chunks(elements, self.compress());
+        typed::<[T], _>(self.send_chunks_to(id, chunks, sid)).await
+    }
+
+    /// Receive elements sent with [`MPCSerializeNet::send_elements_to`], deserializing them as
+    /// they arrive.
+    async fn recv_elements_from<T: CanonicalDeserialize + Send>(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Vec<T>, MPCNetError> {
+        let mut deserializer = StreamingDeserializer::new(self.compress());
+        let on_chunk = |chunk| {
+            deserializer
+                .push(chunk)
+                .map_err(|err| deserialize_error(self, id, err))
+        };
+        typed::<[T], _>(self.recv_chunks_from(id, sid, on_chunk)).await?;
+        deserializer.finish().map_err(|err| deserialize_error(self, id, err))
+    }
+
+    /// Like [`MPCSerializeNet::worker_send_or_leader_receive_element`] for a vector of elements,
+    /// which is streamed instead of being serialized at once. Useful for large vectors.
+    async fn worker_send_or_leader_receive_elements<
+        T: CanonicalDeserialize + CanonicalSerialize + Send + Sync + Clone,
+    >(
+        &self,
+        out: &[T],
+        sid: MultiplexedStreamID,
+    ) -> Result<Option<Vec<Vec<T>>>, MPCNetError> {
+        if self.is_simulated() {
+            return simulated::worker_send_or_leader_receive_elements(self, out);
+        }
+        let own_id = self.party_id();
+        if !self.is_leader() {
+            let timer = start_timer!(
+                format!("Comm: from {} to leader, {} elements", own_id, out.len()),
+                false
+            );
+            self.send_elements_to(out, 0, sid).await?;
+            end_timer!(timer);
+            return Ok(None);
+        }
+        let timer = start_timer!(
+            format!("Comm: from all to leader, {} elements", out.len()),
+            self.is_leader()
+        );
+        let mut received = (0..self.n_parties() as u32)
+            .filter(|id| *id != own_id)
+            .map(|id| self.recv_elements_from(id, sid))
+            .collect::<FuturesOrdered<_>>()
+            .try_collect::<Vec<_>>()
+            .await?;
+        received.insert(own_id as usize, out.to_vec());
+        end_timer!(timer);
+        Ok(Some(received))
+    }
+
+    /// Every party sends `out[i]` to party `i`. Returns the elements received, sorted by sender.
+    async fn all_to_all_element<T: CanonicalDeserialize + CanonicalSerialize + Send + Clone>(
+        &self,
+        out: Vec<T>,
+        sid: MultiplexedStreamID,
+    ) -> Result<Vec<T>, MPCNetError> {
+        if self.is_simulated() {
+            return simulated::all_to_all_element(self, out);
+        }
+        let bytes_out = out
+            .iter()
+            .map(|out| {
+                let mut bytes_out = Vec::new();
+                out.serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+                bytes_out.into()
+            })
+            .collect();
+        let bytes_in = typed::<T, _>(self.all_to_all(bytes_out, sid)).await?;
+        bytes_in
+            .into_iter()
+            .enumerate()
+            .map(|(party, b)| deserialize_from(self, party as u32, &b))
+            .collect()
+    }
+
+    /// `sender` sends the same element to every other party.
+    /// Provide the element iff you're the sender!
+    async fn broadcast_from_element<
+        T: CanonicalDeserialize + CanonicalSerialize + Send + Default,
+    >(
+        &self,
+        out: Option<T>,
+        sender: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<T, MPCNetError> {
+        if self.is_simulated() {
+            return Ok(simulated::broadcast_from_element(self, out));
+        }
+        let bytes = out.map(|out| {
+            let mut bytes_out = Vec::new();
+            out.serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+            bytes_out.into()
+        });
+        let bytes_in = typed::<T, _>(self.broadcast_from(bytes, sender, sid)).await?;
+        deserialize_from(self, sender, &bytes_in)
+    }
+
+    /// Every party sends its element to every other party. Returns all elements, sorted by sender.
+    async fn all_gather_element<T: CanonicalDeserialize + CanonicalSerialize + Clone>(
+        &self,
+        out: &T,
+        sid: MultiplexedStreamID,
+    ) -> Result<Vec<T>, MPCNetError> {
+        if self.is_simulated() {
+            return Ok(simulated::all_gather_element(self, out));
+        }
+        let mut bytes_out = Vec::new();
+        out.serialize_with_mode(&mut bytes_out, self.compress()).unwrap();
+        let bytes_in = typed::<T, _>(self.all_gather(&bytes_out, sid)).await?;
+        bytes_in
+            .into_iter()
+            .enumerate()
+            .map(|(party, b)| deserialize_from(self, party as u32, &b))
+            .collect()
+    }
+
+    /// Every party sends `out[i]` to party `i`, then reduces the elements it received with `f`.
+    async fn reduce_scatter_element<T: CanonicalDeserialize + CanonicalSerialize + Send + Clone>(
+        &self,
+        out: Vec<T>,
+        sid: MultiplexedStreamID,
+        f: impl Fn(Vec<T>) -> T + Send,
+    ) -> Result<T, MPCNetError> {
+        let elements = self.all_to_all_element(out, sid).await?;
+        Ok(f(elements))
+    }
+}
+
+impl<N: MPCNet> MPCSerializeNet for N {}
+// impl<N: MPCNet> TestMPCSerializeNet for N {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::{Fr, G1Projective as G1};
+    use ark_std::UniformRand;
+
+    #[test]
+    fn test_streaming_serialization() {
+        let rng = &mut ark_std::test_rng();
+        let fields: Vec<Fr> = (0..100_000).map(|_| Fr::rand(rng)).collect();
+        let chunks: Vec<Bytes> = serialize_chunks(&fields, Compress::Yes).collect();
+        assert!(chunks.len() > 1);
+
+        // The stream is a serialized `Vec<T>`.
+        let mut expected = Vec::new();
+        fields.serialize_compressed(&mut expected).unwrap();
+        assert_eq!(chunks.concat(), expected);
+
+        let mut deserializer = StreamingDeserializer::<Fr>::new(Compress::Yes);
+        for chunk in chunks {
+            deserializer.push(chunk).unwrap();
+        }
+        assert_eq!(deserializer.finish().unwrap(), fields);
+
+        // Chunk boundaries may split elements.
+        let points: Vec<G1> = (0..10).map(|_| G1::rand(rng)).collect();
+        let bytes = serialize_chunks(&points, Compress::No).collect::<Vec<_>>().concat();
+        let mut deserializer = StreamingDeserializer::<G1>::new(Compress::No);
+        for chunk in bytes.chunks(7) {
+            deserializer.push(Bytes::copy_from_slice(chunk)).unwrap();
+        }
+        assert_eq!(deserializer.finish().unwrap(), points);
+
+        // Truncated streams are rejected.
+        let mut deserializer = StreamingDeserializer::<G1>::new(Compress::No);
+        deserializer.push(Bytes::copy_from_slice(&bytes[..bytes.len() - 1])).unwrap();
+        assert!(deserializer.finish().is_err());
+
+        // Malformed elements are rejected once more than an element is buffered, not at the end.
+        let mut bytes = Vec::new();
+        (u64::MAX).serialize_compressed(&mut bytes).unwrap();
+        let invalid = vec![0xffu8; STREAM_CHUNK_SIZE];
+        let mut deserializer = StreamingDeserializer::<Fr>::new(Compress::Yes);
+        deserializer.push(bytes.into()).unwrap();
+        let mut pushed = 0;
+        while deserializer.push(Bytes::copy_from_slice(&invalid)).is_ok() {
+            pushed += 1;
+            assert!(pushed <= MAX_PENDING / STREAM_CHUNK_SIZE);
+        }
+        assert!(deserializer.buf.len() <= MAX_PENDING + STREAM_CHUNK_SIZE);
+    }
+}
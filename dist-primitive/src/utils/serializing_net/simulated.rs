@@ -0,0 +1,191 @@
+//! The collectives of [`MPCSerializeNet`] on a simulated network, see
+//! [`mpc_net::MPCNet::is_simulated`].
+//!
+//! Nothing is sent. Every party pretends that all the others hold the same element as itself,
+//! so the leader receives copies of its own element, and the communication the party would have
+//! is accounted with [`mpc_net::MPCNet::add_comm`].
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use mpc_net::broadcast::reliable_broadcast_comm;
+use mpc_net::{tree_children, tree_parent, BroadcastMode, MPCNetError};
+
+use super::{
+    deserialize_error, deserialize_from, serialize_chunks, MPCSerializeNet, StreamingDeserializer,
+};
+
+pub(super) fn worker_send_or_leader_receive_element<
+    T: CanonicalDeserialize + CanonicalSerialize + Clone,
+>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    out: &T,
+    receiver: u32,
+) -> Result<Option<Vec<T>>, MPCNetError> {
+    let mut bytes_out = Vec::new();
+    out.serialize_with_mode(&mut bytes_out, net.compress()).unwrap();
+    if receiver == net.party_id() {
+        net.add_comm(0, bytes_out.len() * (net.n_parties() - 1));
+        Ok(Some(vec![
+            deserialize_from(net, net.party_id(), &bytes_out)?;
+            net.n_parties()
+        ]))
+    } else {
+        net.add_comm(bytes_out.len(), 0);
+        Ok(None)
+    }
+}
+
+pub(super) fn worker_receive_or_leader_send_element<
+    T: CanonicalDeserialize + CanonicalSerialize + Default,
+>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    out: Option<Vec<T>>,
+) -> Result<T, MPCNetError> {
+    let Some(outs) = out else {
+        return Ok(T::default());
+    };
+    let bytes: Vec<Vec<u8>> = outs
+        .iter()
+        .map(|out| {
+            let mut bytes_out = Vec::new();
+            out.serialize_with_mode(&mut bytes_out, net.compress()).unwrap();
+            bytes_out
+        })
+        .collect();
+    net.add_comm(bytes.iter().skip(1).map(|b| b.len()).sum::<usize>(), 0);
+    deserialize_from(net, net.party_id(), &bytes[0])
+}
+
+/// Accounts the leader sending `response` with [`mpc_net::MPCNet::reliable_broadcast_from`].
+pub(super) fn reliable_broadcast_element<T: CanonicalSerialize + Default>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    response: Option<T>,
+    mode: BroadcastMode,
+) -> T {
+    let Some(response) = response else {
+        return T::default();
+    };
+    let (up, down) = reliable_broadcast_comm(
+        mode,
+        response.serialized_size(net.compress()),
+        net.n_parties(),
+        true,
+    );
+    net.add_comm(up, down);
+    response
+}
+
+pub(super) fn worker_send_or_leader_receive_element_tree<T: CanonicalSerialize + Clone>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    out: &T,
+    combine: impl Fn(T, T) -> T,
+) -> Option<T> {
+    // A subtree of `size` parties combines to `size` copies of `out`.
+    let own_id = net.party_id();
+    let n = net.n_parties();
+    let len = out.serialized_size(net.compress());
+    let mut acc = out.clone();
+    let mut size = 1;
+    for child in tree_children(own_id, n).collect::<Vec<_>>().into_iter().rev() {
+        let child_size = size.min(n - child as usize);
+        let child_acc = if child_size == size {
+            acc.clone()
+        } else {
+            (1..child_size).fold(out.clone(), |a, _| combine(a, out.clone()))
+        };
+        acc = combine(acc, child_acc);
+        size += child_size;
+        net.add_comm(0, len);
+    }
+    if tree_parent(own_id).is_some() {
+        net.add_comm(len, 0);
+        None
+    } else {
+        Some(acc)
+    }
+}
+
+/// Accounts the leader sending `response` down the tree.
+pub(super) fn tree_send_element<T: CanonicalSerialize + Default>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    response: Option<T>,
+) -> T {
+    let Some(response) = response else {
+        return T::default();
+    };
+    let children = tree_children(net.party_id(), net.n_parties()).count();
+    net.add_comm(response.serialized_size(net.compress()) * children, 0);
+    response
+}
+
+pub(super) fn worker_send_or_leader_receive_elements<
+    T: CanonicalDeserialize + CanonicalSerialize + Clone,
+>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    out: &[T],
+) -> Result<Option<Vec<Vec<T>>>, MPCNetError> {
+    let mut len = 0;
+    let mut deserializer = StreamingDeserializer::new(net.compress());
+    for chunk in serialize_chunks(out, net.compress()) {
+        len += chunk.len();
+        if net.is_leader() {
+            deserializer
+                .push(chunk)
+                .map_err(|err| deserialize_error(net, net.party_id(), err))?;
+        }
+    }
+    if net.is_leader() {
+        net.add_comm(0, len * (net.n_parties() - 1));
+        let elements = deserializer
+            .finish()
+            .map_err(|err| deserialize_error(net, net.party_id(), err))?;
+        Ok(Some(vec![elements; net.n_parties()]))
+    } else {
+        net.add_comm(len, 0);
+        Ok(None)
+    }
+}
+
+pub(super) fn all_to_all_element<T: CanonicalDeserialize + CanonicalSerialize + Clone>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    out: Vec<T>,
+) -> Result<Vec<T>, MPCNetError> {
+    let own_id = net.party_id() as usize;
+    let bytes: Vec<Vec<u8>> = out
+        .iter()
+        .map(|out| {
+            let mut bytes_out = Vec::new();
+            out.serialize_with_mode(&mut bytes_out, net.compress()).unwrap();
+            bytes_out
+        })
+        .collect();
+    let up = bytes
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != own_id)
+        .map(|(_, b)| b.len())
+        .sum::<usize>();
+    net.add_comm(up, bytes[own_id].len() * (net.n_parties() - 1));
+    Ok(vec![
+        deserialize_from(net, own_id as u32, &bytes[own_id])?;
+        net.n_parties()
+    ])
+}
+
+pub(super) fn broadcast_from_element<T: CanonicalSerialize + Default>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    out: Option<T>,
+) -> T {
+    let Some(out) = out else {
+        return T::default();
+    };
+    net.add_comm(out.serialized_size(net.compress()) * (net.n_parties() - 1), 0);
+    out
+}
+
+pub(super) fn all_gather_element<T: CanonicalSerialize + Clone>(
+    net: &(impl MPCSerializeNet + ?Sized),
+    out: &T,
+) -> Vec<T> {
+    let len = out.serialized_size(net.compress()) * (net.n_parties() - 1);
+    net.add_comm(len, len);
+    vec![out.clone(); net.n_parties()]
+}
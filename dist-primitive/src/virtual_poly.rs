@@ -0,0 +1,115 @@
+//! Virtual polynomials, sums of products of multilinear extensions.
+use ark_ff::Field;
+
+/// The polynomial `Σ_j c_j · Π_{i ∈ P_j} f_i` of multilinear extensions `f_i`, which is not
+/// multilinear itself. Its degree in every variable is the size of the largest product.
+///
+/// The extensions are given by their evaluations over the hypercube, or by the shares or the
+/// partial evaluations of them a party holds, depending on the sumcheck run on it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VirtualPolynomial<F> {
+    /// The evaluations of the extensions, all of the same length.
+    pub mles: Vec<Vec<F>>,
+    /// The products, each a coefficient and the indices of its extensions in `mles`.
+    pub products: Vec<(F, Vec<usize>)>,
+}
+
+impl<F: Field> VirtualPolynomial<F> {
+    pub fn new() -> Self {
+        Self {
+            mles: Vec::new(),
+            products: Vec::new(),
+        }
+    }
+
+    /// Add an extension and return its index.
+    pub fn add_mle(&mut self, evaluations: Vec<F>) -> usize {
+        if let Some(first) = self.mles.first() {
+            assert_eq!(first.len(), evaluations.len(), "The extensions differ in length");
+        }
+        self.mles.push(evaluations);
+        self.mles.len() - 1
+    }
+
+    /// Add the product of the extensions at `indices`, scaled by `coefficient`.
+    pub fn add_product(&mut self, coefficient: F, indices: impl IntoIterator<Item = usize>) {
+        let indices: Vec<usize> = indices.into_iter().collect();
+        assert!(indices.iter().all(|i| *i < self.mles.len()), "No such extension");
+        self.products.push((coefficient, indices));
+    }
+
+    /// The number of variables of the extensions.
+    pub fn num_vars(&self) -> usize {
+        self.mles.first().map_or(0, |mle| mle.len().trailing_zeros() as usize)
+    }
+
+    /// The degree in every variable, at least 1 so that the round polynomials have evaluations
+    /// at 0 and 1.
+    pub fn degree(&self) -> usize {
+        self.products.iter().map(|(_, indices)| indices.len()).max().unwrap_or(0).max(1)
+    }
+
+    /// Evaluate the products given the values of the extensions.
+    fn combine(&self, values: &[F]) -> F {
+        self.products
+            .iter()
+            .map(|(coefficient, indices)| {
+                indices.iter().fold(*coefficient, |acc, i| acc * values[*i])
+            })
+            .sum()
+    }
+
+    /// The round polynomial for the first variable, summed over the others and evaluated at
+    /// 0, 1, ..., the degree.
+    pub fn round_message(&self) -> Vec<F> {
+        let degree = self.degree();
+        let half = self.mles.first().map_or(0, |mle| mle.len() / 2);
+        let mut message = vec![F::ZERO; degree + 1];
+        let mut values = vec![F::ZERO; self.mles.len()];
+        let mut steps = vec![F::ZERO; self.mles.len()];
+        for k in 0..half {
+            for (i, mle) in self.mles.iter().enumerate() {
+                values[i] = mle[k];
+                steps[i] = mle[half + k] - mle[k];
+            }
+            message[0] += self.combine(&values);
+            // At t the evaluations are not in the table, they are x0 + t*(x1-x0).
+            for evaluation in message.iter_mut().skip(1) {
+                values.iter_mut().zip(&steps).for_each(|(v, step)| *v += step);
+                *evaluation += self.combine(&values);
+            }
+        }
+        message
+    }
+
+    /// Bind the first variable of every extension to `challenge`: (1-u)*x0 + u*x1.
+    pub fn bind(&mut self, challenge: F) {
+        for mle in self.mles.iter_mut() {
+            let half = mle.len() / 2;
+            let (low, high) = mle.split_at(half);
+            *mle = low
+                .iter()
+                .zip(high)
+                .map(|(a, b)| *a + (*b - *a) * challenge)
+                .collect();
+        }
+    }
+
+    /// The value of the polynomial once every variable is bound.
+    pub fn evaluation(&self) -> F {
+        debug_assert!(self.mles.iter().all(|mle| mle.len() == 1));
+        let values: Vec<F> = self.mles.iter().map(|mle| mle[0]).collect();
+        self.combine(&values)
+    }
+
+    /// The sum over the hypercube.
+    pub fn sum(&self) -> F {
+        let len = self.mles.first().map_or(1, |mle| mle.len());
+        (0..len)
+            .map(|k| {
+                let values: Vec<F> = self.mles.iter().map(|mle| mle[k]).collect();
+                self.combine(&values)
+            })
+            .sum()
+    }
+}
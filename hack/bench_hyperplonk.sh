@@ -5,6 +5,6 @@ do
     for i in {16..26}
     do
         # Run the hyperplonk example with varying server counts and circuit sizes
-        just run --release -F local --example hyperplonk -- --l $j --n $i > ./hyperplonk_${j}_${i}.txt
+        just run --release --example hyperplonk -- --l $j --n $i --mode local > ./hyperplonk_${j}_${i}.txt
     done
 done
\ No newline at end of file
@@ -2,5 +2,5 @@
 
 for i in {20..30}
 do
-    just run --release -F leader --example poly_comm -- --l 32 --n $i > poly_comm_$i.txt
+    just run --release --example poly_comm -- --l 32 --n $i --mode leader > poly_comm_$i.txt
 done
\ No newline at end of file
@@ -2,5 +2,5 @@
 
 for i in {20..30}
 do
-    just run --release -F leader --example sumcheck -- --l 32 --n $i > sumcheck_$i.txt
+    just run --release --example sumcheck -- --l 32 --n $i --mode leader > sumcheck_$i.txt
 done
\ No newline at end of file
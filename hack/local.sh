@@ -1,4 +0,0 @@
-./target/release/examples/bench_hyperplonk --file ./network-address/test --l 1 --n 10 --id 0 > output/test-0.txt &
-./target/release/examples/bench_hyperplonk --file ./network-address/test --l 1 --n 10 --id 1 > output/test-1.txt &
-./target/release/examples/bench_hyperplonk --file ./network-address/test --l 1 --n 10 --id 2 > output/test-2.txt &
-./target/release/examples/bench_hyperplonk --file ./network-address/test --l 1 --n 10 --id 3 > output/test-3.txt &
\ No newline at end of file
@@ -7,11 +7,15 @@ use clap::Parser;
 
 use hyperplonk::dhyperplonk::cpermcheck;
 use hyperplonk::dhyperplonk::PackedProvingParameters;
+use mpc_net::memory::{self, TrackingAllocator};
 use mpc_net::multi::MPCNetConnection;
 use env_logger;
-use mpc_net::MultiplexedStreamID;
+use mpc_net::{MPCNet, MultiplexedStreamID};
 use secret_sharing::pss::PackedSharingParams;
-use tokio::net::TcpStream;
+
+/// Every timer also reports the memory of its phase.
+#[global_allocator]
+static ALLOC: TrackingAllocator = TrackingAllocator;
 
 #[derive(Parser)]
 struct Cli {
@@ -27,8 +31,7 @@ struct Cli {
     id: u32,
 }
 
-#[cfg_attr(feature = "single_thread", tokio::main(flavor = "current_thread"))]
-#[cfg_attr(not(feature = "single_thread"), tokio::main)]
+#[tokio::main]
 async fn main() {
     env_logger::builder().format_timestamp(None).filter_level(log::LevelFilter::Trace).init();
     let args = Cli::parse();
@@ -36,10 +39,12 @@ async fn main() {
     net.listen().await.unwrap();
     net.connect_to_all().await.unwrap();
     permcheck_distributed_bench(&net, args.n, args.l).await;
+    println!("Comm: {:?}", net.get_comm());
+    println!("Memory: {:?}", (memory::peak(), memory::current()));
 }
 
 
-async fn permcheck_distributed_bench(net: &MPCNetConnection<TcpStream>, n: usize, l: usize) {
+async fn permcheck_distributed_bench(net: &MPCNetConnection, n: usize, l: usize) {
     let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
     let params = PackedProvingParameters::new(n, l, &pp);
     black_box(
@@ -1,19 +1,29 @@
 use std::hint::black_box;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 use ark_ec::{bls12::Bls12, pairing::Pairing};
 
-use clap::Parser;
+use clap::{ArgGroup, Parser};
 
 use hyperplonk::dhyperplonk::dhyperplonk;
 use hyperplonk::dhyperplonk::PackedProvingParameters;
+use mpc_net::memory::{self, TrackingAllocator};
 use mpc_net::multi::MPCNetConnection;
+use mpc_net::profile::Profiler;
 use env_logger;
-use mpc_net::MultiplexedStreamID;
+use mpc_net::{
+    MPCNet, MultiplexedStreamID, PointEncoding, Quic, Shm, Tcp, Transport, TransportKind, Unix,
+    WireConfig,
+};
 use secret_sharing::pss::PackedSharingParams;
-use tokio::net::TcpStream;
+
+/// Every timer also reports the memory of its phase.
+#[global_allocator]
+static ALLOC: TrackingAllocator = TrackingAllocator;
 
 #[derive(Parser)]
+#[command(group(ArgGroup::new("peers").required(true).args(["file", "coordinator"])))]
 struct Cli {
     /// The packing size, should be 1/8 of the party size as well as a power of 2.
     #[arg(long)]
@@ -21,25 +31,100 @@ struct Cli {
     /// log2 of the total number of variables.
     #[arg(long)]
     n: usize,
+    /// The host file, one address per party. The scheme of the addresses selects the transport,
+    /// e.g. `unix:/tmp/party0.sock` or `shm:/tmp/party0.sock` for parties on this machine.
+    #[arg(long)]
+    file: Option<PathBuf>,
+    /// Get the other parties from the coordinator at this address instead of a host file.
     #[arg(long)]
-    file: PathBuf,
+    coordinator: Option<SocketAddr>,
+    /// Where to listen when using a coordinator. The scheme selects the transport like in a host
+    /// file, and an unspecified IP is announced as the IP the coordinator sees.
+    #[arg(long, default_value = "0.0.0.0:0")]
+    listen: String,
     #[arg(long)]
     id: u32,
+    /// Write the per-scope communication tree of this party to a JSON file.
+    #[arg(long)]
+    comm_json: Option<PathBuf>,
+    /// Record the timers of this party and write them to `<PROFILE>.json` as a Chrome trace and
+    /// to `<PROFILE>.csv`.
+    #[arg(long)]
+    profile: Option<PathBuf>,
+    /// Hold back small messages so that messages sent in a burst are written together.
+    #[arg(long)]
+    coalesce: bool,
+    /// Send group points uncompressed, if all parties ask for it.
+    #[arg(long)]
+    uncompressed: bool,
+    /// Check every message against a header naming its step and type, if all parties ask for it.
+    #[arg(long)]
+    envelopes: bool,
+    /// Connect the parties over QUIC instead of TCP, like `quic:` addresses.
+    #[arg(long)]
+    quic: bool,
 }
 
-#[cfg_attr(feature = "single_thread", tokio::main(flavor = "current_thread"))]
-#[cfg_attr(not(feature = "single_thread"), tokio::main)]
+#[tokio::main]
 async fn main() {
     env_logger::builder().format_timestamp(None).filter_level(log::LevelFilter::Trace).init();
     let args = Cli::parse();
-    let mut net = MPCNetConnection::init_from_path(&args.file, args.id);
-    net.listen().await.unwrap();
+    let kind = match &args.file {
+        Some(file) => TransportKind::of_host_file(file).expect("host configuration path"),
+        None => TransportKind::of_addr(&args.listen),
+    };
+    match kind {
+        TransportKind::Tcp if args.quic => run::<Quic>(&args).await,
+        TransportKind::Tcp => run::<Tcp>(&args).await,
+        TransportKind::Quic => run::<Quic>(&args).await,
+        TransportKind::Unix => run::<Unix>(&args).await,
+        TransportKind::Shm => run::<Shm>(&args).await,
+    }
+}
+
+async fn run<T: Transport>(args: &Cli) {
+    let profiler = args.profile.as_ref().map(|_| Profiler::init().unwrap());
+    let mut net = match (&args.file, args.coordinator) {
+        (Some(file), _) => {
+            let mut net = MPCNetConnection::<T>::init_from_path(file, args.id);
+            net.listen().await.unwrap();
+            net
+        }
+        (None, Some(coordinator)) => {
+            let listen = T::parse_addr(&args.listen).expect("bad listen address");
+            MPCNetConnection::<T>::init_from_coordinator(coordinator, args.id, listen, Vec::new())
+                .await
+                .unwrap()
+        }
+        (None, None) => unreachable!("clap requires one of them"),
+    };
+    net.wire = WireConfig::default()
+        .with_coalescing(args.coalesce)
+        .with_point_encoding(if args.uncompressed {
+            PointEncoding::Uncompressed
+        } else {
+            PointEncoding::Compressed
+        })
+        .with_envelopes(args.envelopes);
     net.connect_to_all().await.unwrap();
     hyperplonk_distributed_bench(&net, args.n, args.l).await;
+    println!("Comm: {:?}", net.get_comm());
+    println!("Memory: {:?}", (memory::peak(), memory::current()));
+    if let Some(path) = &args.comm_json {
+        net.comm.write_json(path).unwrap();
+    }
+    if let (Some(profiler), Some(path)) = (profiler, &args.profile) {
+        profiler.write_chrome_trace(path.with_extension("json"), args.id).unwrap();
+        profiler.write_csv(path.with_extension("csv")).unwrap();
+    }
 }
 
 
-async fn hyperplonk_distributed_bench(net: &MPCNetConnection<TcpStream>, n: usize, l: usize) {
+async fn hyperplonk_distributed_bench<T: Transport>(
+    net: &MPCNetConnection<T>,
+    n: usize,
+    l: usize,
+) {
     let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
     let params = PackedProvingParameters::new(n, l, &pp);
     black_box(
@@ -4,14 +4,12 @@ use ark_ec::{bls12::Bls12, pairing::Pairing};
 
 use clap::Parser;
 
-use hyperplonk::dhyperplonk::dhyperplonk;
+use hyperplonk::dhyperplonk::{dhyperplonk, dhyperplonk_data_parallel};
 use hyperplonk::dhyperplonk::PackedProvingParameters;
 use hyperplonk::hyperplonk::local_hyperplonk;
 use hyperplonk::hyperplonk::local_hyperplonkpp;
-use mpc_net::LocalTestNet;
-use mpc_net::MPCNet; 
+use mpc_net::{LeaderNet, LocalTestNet, MPCNet, MultiplexedStreamID, NetMode};
 use mpc_net::{end_timer, start_timer};
-use mpc_net::MultiplexedStreamID;
 use secret_sharing::pss::PackedSharingParams;
 
 #[derive(Parser)]
@@ -22,19 +20,71 @@ struct Cli {
     /// log2 of the total number of variables.
     #[arg(long)]
     n: usize,
+    /// Run the data-parallel variant of collaborative HyperPlonk.
+    #[arg(long)]
+    data_parallel: bool,
+    /// How to run the parties: local, local-multi-thread or leader.
+    #[arg(long, default_value_t = NetMode::Local)]
+    mode: NetMode,
 }
 
-#[cfg_attr(feature = "single_thread", tokio::main(flavor = "current_thread"))]
-#[cfg_attr(not(feature = "single_thread"), tokio::main)]
-async fn main() {
+fn main() {
     let args = Cli::parse();
 
     hyperplonk_local_bench(args.n);
-    hyperplonk_collaborative_bench(args.n, args.l).await;
+    args.mode.runtime().unwrap().block_on(async {
+        match (args.mode, args.data_parallel) {
+            (NetMode::Leader, true) => hyperplonk_data_parallel_leader_bench(args.n, args.l).await,
+            (NetMode::Leader, false) => hyperplonk_collaborative_leader_bench(args.n, args.l).await,
+            (_, true) => hyperplonk_data_parallel_local_bench(args.n, args.l).await,
+            (_, false) => hyperplonk_collaborative_local_bench(args.n, args.l).await,
+        }
+    });
+}
+
+/// This benchmark just runs the leader's part of collaborative HyperPlonk without any networking
+/// involved.
+async fn hyperplonk_collaborative_leader_bench(n: usize, l: usize) {
+    let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
+    let params = PackedProvingParameters::new(n, l, &pp);
+    let net = LeaderNet::new(l * 8);
+    let timer = start_timer!("Collaborative Hyperplonk++ Leader");
+    black_box(
+        dhyperplonk::<Bls12<ark_bls12_381::Config>, _>(
+            n,
+            &params,
+            &pp,
+            &net,
+            MultiplexedStreamID::Zero,
+        )
+        .await
+        .unwrap(),
+    );
+    end_timer!(timer);
+}
+
+/// This benchmark just runs the leader's part of data-parallel collaborative HyperPlonk without
+/// any networking involved.
+async fn hyperplonk_data_parallel_leader_bench(n: usize, l: usize) {
+    let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
+    let params = PackedProvingParameters::new(n, l, &pp);
+    let net = LeaderNet::new(l * 8);
+    let timer = start_timer!("Data-parallel Collaborative Hyperplonk Leader");
+    black_box(
+        dhyperplonk_data_parallel::<Bls12<ark_bls12_381::Config>, _>(
+            n,
+            &params,
+            &pp,
+            &net,
+            MultiplexedStreamID::Zero,
+        )
+        .await
+        .unwrap(),
+    );
+    end_timer!(timer);
 }
 
-#[cfg(not(feature = "leader"))]
-async fn hyperplonk_collaborative_bench(n: usize, l: usize) {
+async fn hyperplonk_collaborative_local_bench(n: usize, l: usize) {
     let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
     let params = PackedProvingParameters::new(n, l, &pp); 
     let net = LocalTestNet::new_local_testnet(l * 8).await.unwrap();
@@ -64,6 +114,36 @@ async fn hyperplonk_collaborative_bench(n: usize, l: usize) {
     end_timer!(timer);
 }
 
+async fn hyperplonk_data_parallel_local_bench(n: usize, l: usize) {
+    let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
+    let params = PackedProvingParameters::new(n, l, &pp);
+    let net = LocalTestNet::new_local_testnet(l * 8).await.unwrap();
+    // Now simulate the protocol
+    let timer = start_timer!("Simulate Data-parallel Collaborative Hyperplonk");
+    let _ = net
+        .simulate_network_round(params, move |net, params| async move {
+            let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
+
+            black_box(
+                dhyperplonk_data_parallel::<Bls12<ark_bls12_381::Config>, _>(
+                    n,
+                    &params,
+                    &pp,
+                    &net,
+                    MultiplexedStreamID::Zero,
+                )
+                .await
+                .unwrap(),
+            );
+
+            if net.is_leader() {
+                println!("Comm: {:?}", net.get_comm());
+            }
+        })
+        .await;
+    end_timer!(timer);
+}
+
 fn hyperplonk_local_bench(n: usize) {
     // generate shares
     let res = local_hyperplonk::<Bls12<ark_bls12_381::Config>>(n);
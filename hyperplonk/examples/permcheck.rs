@@ -5,10 +5,8 @@ use ark_ec::{bls12::Bls12, pairing::Pairing};
 use clap::Parser;
 
 use hyperplonk::dhyperplonk::PackedProvingParameters;
-use mpc_net::LocalTestNet;
-use mpc_net::MPCNet; 
+use mpc_net::{LeaderNet, LocalTestNet, MPCNet, MultiplexedStreamID, NetMode};
 use mpc_net::{end_timer, start_timer};
-use mpc_net::MultiplexedStreamID;
 use secret_sharing::pss::PackedSharingParams;
 
 #[derive(Parser)]
@@ -19,19 +17,50 @@ struct Cli {
     /// log2 of the total number of variables.
     #[arg(long)]
     n: usize,
+    /// How to run the parties: local, local-multi-thread or leader.
+    #[arg(long, default_value_t = NetMode::Local)]
+    mode: NetMode,
 }
 
-#[cfg_attr(feature = "single_thread", tokio::main(flavor = "current_thread"))]
-#[cfg_attr(not(feature = "single_thread"), tokio::main)]
-async fn main() {
+fn main() {
     let args = Cli::parse();
+    args.mode.runtime().unwrap().block_on(async {
+        match args.mode {
+            NetMode::Leader => {
+                cpermcheck_leader_bench(args.n, args.l).await;
+                dpermcheck_leader_bench(args.n, args.l).await;
+            }
+            NetMode::Local | NetMode::LocalMultiThread => {
+                cpermcheck_local_bench(args.n, args.l).await;
+                dpermcheck_local_bench(args.n, args.l).await;
+            }
+        }
+    });
+}
+
+/// This benchmark just runs the leader's part of the collaborative Permutation Check without any networking involved.
+async fn cpermcheck_leader_bench(n: usize, l: usize) {
+    use hyperplonk::dhyperplonk::cpermcheck;
 
-    cpermcheck_bench(args.n, args.l).await;
-    dpermcheck_bench(args.n, args.l).await;
+    let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
+    let params = PackedProvingParameters::new(n, l, &pp);
+    let net = LeaderNet::new(l * 8);
+    let timer = start_timer!("Collaborative Permutation Check Leader");
+    black_box(
+        cpermcheck::<Bls12<ark_bls12_381::Config>, _>(
+            n,
+            &params,
+            &pp,
+            &net,
+            MultiplexedStreamID::Zero,
+        )
+        .await
+        .unwrap(),
+    );
+    end_timer!(timer);
 }
 
-#[cfg(not(feature = "leader"))]
-async fn cpermcheck_bench(n: usize, l: usize) {
+async fn cpermcheck_local_bench(n: usize, l: usize) {
     use hyperplonk::dhyperplonk::cpermcheck;
 
     let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
@@ -62,8 +91,29 @@ async fn cpermcheck_bench(n: usize, l: usize) {
     end_timer!(timer);
 }
 
-#[cfg(not(feature = "leader"))]
-async fn dpermcheck_bench(n: usize, l: usize) {
+/// This benchmark just runs the leader's part of the improved Collaborative Permutation Check without any networking involved.
+async fn dpermcheck_leader_bench(n: usize, l: usize) {
+    use hyperplonk::dhyperplonk::dpermcheck;
+
+    let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
+    let params = PackedProvingParameters::new(n, l, &pp);
+    let net = LeaderNet::new(l * 8);
+    let timer = start_timer!("Improved Collaborative Permutation Check Leader");
+    black_box(
+        dpermcheck::<Bls12<ark_bls12_381::Config>, _>(
+            n,
+            &params,
+            &pp,
+            &net,
+            MultiplexedStreamID::Zero,
+        )
+        .await
+        .unwrap(),
+    );
+    end_timer!(timer);
+}
+
+async fn dpermcheck_local_bench(n: usize, l: usize) {
     use hyperplonk::dhyperplonk::dpermcheck;
     
     let pp = PackedSharingParams::<<Bls12<ark_bls12_381::Config> as Pairing>::ScalarField>::new(l);
@@ -9,14 +9,17 @@ use dist_primitive::random_evaluations;
 use dist_primitive::{
     dacc_product::c_acc_product_and_share,
     dpoly_comm::{PolynomialCommitment, PolynomialCommitmentCub},
-    dsumcheck::{c_sumcheck_product, sumcheck_product},
+    dsumcheck::{c_sumcheck_product, c_sumcheck_virtual, sumcheck_product},
     mle::fix_variable,
+    sumcheck_proof::SumcheckProof,
     utils::serializing_net::MPCSerializeNet,
 };
 use mpc_net::{end_timer, start_timer};
 use mpc_net::{MPCNetError, MultiplexedStreamID};
 use secret_sharing::pss::PackedSharingParams;
 
+use crate::hyperplonk::gate_identity;
+
 // Those fields with _p suffixes are plain and distributed values, those without are shares.
 #[derive(Clone, Debug)]
 pub struct PackedProvingParameters<E: Pairing> {
@@ -165,7 +168,7 @@ pub async fn dhyperplonk<E: Pairing, Net: MPCSerializeNet>(
 ) -> Result<
     (
         (
-            Vec<Vec<(E::ScalarField, E::ScalarField, E::ScalarField)>>,
+            SumcheckProof<E::ScalarField>,
             Vec<(E::G1, (E::ScalarField, Vec<E::G1>))>,
         ),
         (
@@ -195,6 +198,7 @@ pub async fn dhyperplonk<E: Pairing, Net: MPCSerializeNet>(
 
     // Step 1: commit
     let commit_timer = start_timer!("Commit", net.is_leader());
+    let commit_scope = net.comm_scope("Commit");
     let com_a = pk
         .c_commitment
         .c_commit(&vec![pk.a_evals.clone()], &pp, &net, sid)
@@ -215,49 +219,24 @@ pub async fn dhyperplonk<E: Pairing, Net: MPCSerializeNet>(
     let com_S2 = pk.d_commitment.d_commit(&pk.S2_p, &net, sid).await.unwrap();
 
     end_timer!(commit_timer);
+    drop(commit_scope);
     // End of step 1
 
     let prover_timer = start_timer!("Distributed HyperPlonk Prover", net.is_leader());
 
     // Step 3: gate identity
     let gate_timer = start_timer!("Gate identity", net.is_leader());
-    let mut gate_identity_proofs = Vec::new();
+    let gate_scope = net.comm_scope("Gate identity");
     let mut gate_identity_commitments = Vec::new();
     // Collaborative sumcheck for F(x)=eq(x)*[q_1(x)*(a(x)+b(x))+q_2(x)*a(x)*b(x)-c(x)+I(x)]
-    // In original Hyperplonk this is done with a virtual circuit.
-    // We use different sumcheck product to simulate it for implementation simplicity. The computation complexity is the same.
-    // Part 1
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.eq, &pk.S1, &pk.challenge, pp, net, sid).await?);
-    let timer = start_timer!("Local: Sum a and b", net.is_leader());
-    let sum_ab = pk
-        .a_evals
-        .iter()
-        .zip(pk.b_evals.iter())
-        .map(|(a, b)| *a + *b)
-        .collect();
+    let timer = start_timer!("Local: Gate identity polynomial", net.is_leader());
+    let gate_identity =
+        gate_identity(&pk.eq, &pk.S1, &pk.S2, &pk.a_evals, &pk.b_evals, &pk.c_evals, &pk.I);
     end_timer!(timer);
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.S1, &sum_ab, &pk.challenge, pp, net, sid).await?);
-    // Part 2
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.eq, &pk.S2, &pk.challenge, pp, net, sid).await?);
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.a_evals, &pk.b_evals, &pk.challenge, pp, net, sid).await?);
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.S2, &pk.a_evals, &pk.challenge, pp, net, sid).await?);
-    // Part 3
-    let timer = start_timer!("Local: c-I", net.is_leader());
-    let sum_ci = pk
-        .c_evals
-        .iter()
-        .zip(pk.I.iter())
-        .map(|(a, b)| -*a + b)
-        .collect();
-    end_timer!(timer);
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.eq, &sum_ci, &pk.challenge, pp, net, sid).await?);
+    let gate_identity_proof =
+        c_sumcheck_virtual(&gate_identity, &pk.challenge, pp, net, sid).await?;
     end_timer!(gate_timer);
+    drop(gate_scope);
     // End of step 3
 
     // Step 2: wiring identity
@@ -265,6 +244,7 @@ pub async fn dhyperplonk<E: Pairing, Net: MPCSerializeNet>(
     let mut wiring_commits = Vec::new();
     let mut wiring_opens = Vec::new();
     let wire_timer = start_timer!("Wire identity", net.is_leader());
+    let wire_scope = net.comm_scope("Wire identity");
     // 2.a compute A_s, s is M' in the paper
     // A_s is the PSS of a vector with length n. Just get some random value here as local value.
     let mut s = Vec::with_capacity(gate_count * 4 / pp.l);
@@ -280,14 +260,10 @@ pub async fn dhyperplonk<E: Pairing, Net: MPCSerializeNet>(
             .dynamic_worker_receive_or_worker_send_element(send, i as u32, sid)
             .await
             .unwrap();
-        #[cfg(feature = "comm")]
-        {
+        if !net.is_simulated() {
             s.extend_from_slice(&recv);
-        }
-
-        // If no actual communication, just use the input as a placeholder.
-        #[cfg(not(feature = "comm"))]
-        {
+        } else {
+            // If no actual communication, just use the input as a placeholder.
             black_box(recv);
             s.extend_from_slice(&local_s);
         }
@@ -511,10 +487,12 @@ pub async fn dhyperplonk<E: Pairing, Net: MPCSerializeNet>(
     }
 
     end_timer!(wire_timer);
+    drop(wire_scope);
     // end of step 2
 
     // Open
     let open_timer = start_timer!("Open", net.is_leader());
+    let open_scope = net.comm_scope("Open");
     gate_identity_commitments.push((
         com_a,
         pk.c_commitment
@@ -552,6 +530,7 @@ pub async fn dhyperplonk<E: Pairing, Net: MPCSerializeNet>(
             .await?,
     ));
     end_timer!(open_timer);
+    drop(open_scope);
 
     // let degree_reduce_timer = start_timer!("Degree reduce", net.is_leader());
     // degree_reduce_many(&pk.reduce_target, pp, net, sid).await?;
@@ -565,7 +544,7 @@ pub async fn dhyperplonk<E: Pairing, Net: MPCSerializeNet>(
     }
 
     Ok((
-        (gate_identity_proofs, gate_identity_commitments),
+        (gate_identity_proof, gate_identity_commitments),
         (wiring_proofs, wiring_commits, wiring_opens),
     ))
 }
@@ -579,7 +558,7 @@ pub async fn dhyperplonk_data_parallel<E: Pairing, Net: MPCSerializeNet>(
 ) -> Result<
     (
         (
-            Vec<Vec<(E::ScalarField, E::ScalarField, E::ScalarField)>>,
+            SumcheckProof<E::ScalarField>,
             Vec<(E::G1, (E::ScalarField, Vec<E::G1>))>,
         ),
         (
@@ -609,6 +588,7 @@ pub async fn dhyperplonk_data_parallel<E: Pairing, Net: MPCSerializeNet>(
 
     // Step 1: commit
     let commit_timer = start_timer!("Commit", net.is_leader());
+    let commit_scope = net.comm_scope("Commit");
     let com_a = pk
         .c_commitment
         .c_commit(&vec![pk.a_evals.clone()], &pp, &net, sid)
@@ -629,49 +609,24 @@ pub async fn dhyperplonk_data_parallel<E: Pairing, Net: MPCSerializeNet>(
     let com_S2 = pk.d_commitment.d_commit(&pk.S2_p, &net, sid).await.unwrap();
 
     end_timer!(commit_timer);
+    drop(commit_scope);
     // End of step 1
 
     let prover_timer = start_timer!("Distributed HyperPlonk Prover", net.is_leader());
 
     // Step 3: gate identity
     let gate_timer = start_timer!("Gate identity", net.is_leader());
-    let mut gate_identity_proofs = Vec::new();
+    let gate_scope = net.comm_scope("Gate identity");
     let mut gate_identity_commitments = Vec::new();
     // Collaborative sumcheck for F(x)=eq(x)*[q_1(x)*(a(x)+b(x))+q_2(x)*a(x)*b(x)-c(x)+I(x)]
-    // In original Hyperplonk this is done with a virtual circuit.
-    // We use different sumcheck product to simulate it for implementation simplicity. The computation complexity is the same.
-    // Part 1
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.eq, &pk.S1, &pk.challenge, pp, net, sid).await?);
-    let timer = start_timer!("Local: Sum a and b", net.is_leader());
-    let sum_ab = pk
-        .a_evals
-        .iter()
-        .zip(pk.b_evals.iter())
-        .map(|(a, b)| *a + *b)
-        .collect();
-    end_timer!(timer);
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.S1, &sum_ab, &pk.challenge, pp, net, sid).await?);
-    // Part 2
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.eq, &pk.S2, &pk.challenge, pp, net, sid).await?);
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.a_evals, &pk.b_evals, &pk.challenge, pp, net, sid).await?);
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.S2, &pk.a_evals, &pk.challenge, pp, net, sid).await?);
-    // Part 3
-    let timer = start_timer!("Local: c-I", net.is_leader());
-    let sum_ci = pk
-        .c_evals
-        .iter()
-        .zip(pk.I.iter())
-        .map(|(a, b)| -*a + b)
-        .collect();
+    let timer = start_timer!("Local: Gate identity polynomial", net.is_leader());
+    let gate_identity =
+        gate_identity(&pk.eq, &pk.S1, &pk.S2, &pk.a_evals, &pk.b_evals, &pk.c_evals, &pk.I);
     end_timer!(timer);
-    gate_identity_proofs
-        .push(c_sumcheck_product(&pk.eq, &sum_ci, &pk.challenge, pp, net, sid).await?);
+    let gate_identity_proof =
+        c_sumcheck_virtual(&gate_identity, &pk.challenge, pp, net, sid).await?;
     end_timer!(gate_timer);
+    drop(gate_scope);
     // End of step 3
 
     // Step 2: wiring identity
@@ -679,6 +634,7 @@ pub async fn dhyperplonk_data_parallel<E: Pairing, Net: MPCSerializeNet>(
     let mut wiring_commits = Vec::new();
     let mut wiring_opens = Vec::new();
     let wire_timer = start_timer!("Wire identity", net.is_leader());
+    let wire_scope = net.comm_scope("Wire identity");
     // 2.a compute A_s, s is M' in the paper
     // A_s is the PSS of a vector with length n. Just get some random value here as local value.
 
@@ -900,10 +856,12 @@ pub async fn dhyperplonk_data_parallel<E: Pairing, Net: MPCSerializeNet>(
     }
 
     end_timer!(wire_timer);
+    drop(wire_scope);
     // end of step 2
 
     // Open
     let open_timer = start_timer!("Open", net.is_leader());
+    let open_scope = net.comm_scope("Open");
     gate_identity_commitments.push((
         com_a,
         pk.c_commitment
@@ -941,6 +899,7 @@ pub async fn dhyperplonk_data_parallel<E: Pairing, Net: MPCSerializeNet>(
             .await?,
     ));
     end_timer!(open_timer);
+    drop(open_scope);
 
     // let degree_reduce_timer = start_timer!("Degree reduce", net.is_leader());
     // degree_reduce_many(&pk.reduce_target, pp, net, sid).await?;
@@ -954,7 +913,7 @@ pub async fn dhyperplonk_data_parallel<E: Pairing, Net: MPCSerializeNet>(
     }
 
     Ok((
-        (gate_identity_proofs, gate_identity_commitments),
+        (gate_identity_proof, gate_identity_commitments),
         (wiring_proofs, wiring_commits, wiring_opens),
     ))
 }
@@ -992,6 +951,7 @@ pub async fn dpermcheck<E: Pairing, Net: MPCSerializeNet>(
     let mut wiring_proofs = Vec::new();
     let mut wiring_commits = Vec::new();
     let mut wiring_opens = Vec::new();
+    let share_timer = start_timer!("Share s", net.is_leader());
     // 2.a compute A_s, s is M' in the paper
     // A_s is the PSS of a vector with length n. Just get some random value here as local value.
     let mut s = Vec::with_capacity(gate_count * 4 / pp.l);
@@ -1007,19 +967,17 @@ pub async fn dpermcheck<E: Pairing, Net: MPCSerializeNet>(
             .dynamic_worker_receive_or_worker_send_element(send, i as u32, sid)
             .await
             .unwrap();
-        #[cfg(feature = "comm")]
-        {
+        if !net.is_simulated() {
             s.extend_from_slice(&recv);
-        }
-
-        // If no actual communication, just use the input as a placeholder.
-        #[cfg(not(feature = "comm"))]
-        {
+        } else {
+            // If no actual communication, just use the input as a placeholder.
             black_box(recv);
             s.extend_from_slice(&local_s);
         }
     }
 
+    end_timer!(share_timer);
+
     // 2.b compute com_s using distributed PCS
     wiring_commits.push(
         pk.d_commitment
@@ -1087,6 +1045,7 @@ pub async fn dpermcheck<E: Pairing, Net: MPCSerializeNet>(
     end_timer!(timer);
 
     // Commit polynomials
+    let commit_timer = start_timer!("Commit", net.is_leader());
     wiring_commits.push(
         pk.d_commitment
             .d_commit(&pk.ssigma_p, &net, sid)
@@ -1105,8 +1064,10 @@ pub async fn dpermcheck<E: Pairing, Net: MPCSerializeNet>(
     wiring_commits.push(pk.d_commitment.d_commit(&v1x, &net, sid).await.unwrap());
     wiring_commits.push(pk.d_commitment.d_commit(&vx0, &net, sid).await.unwrap());
     wiring_commits.push(pk.d_commitment.d_commit(&vx1, &net, sid).await.unwrap());
+    end_timer!(commit_timer);
 
     // Open polynomials
+    let open_timer = start_timer!("Open", net.is_leader());
     wiring_opens.push(
         pk.d_commitment
             .d_open(&pk.ssigma_p, &pk.challenge_r2, net, sid)
@@ -1133,7 +1094,10 @@ pub async fn dpermcheck<E: Pairing, Net: MPCSerializeNet>(
             .await?,
     );
 
+    end_timer!(open_timer);
+
     // 2.e.1 zerocheck on p(x) = g*v0x-f
+    let zerocheck_timer = start_timer!("Zerocheck", net.is_leader());
     // this is direct sumcheck product
     wiring_proofs.push(d_sumcheck_product(&den, &pk.eq_r2_p, &pk.challenge_r2, net, sid).await?);
     wiring_proofs.push(d_sumcheck_product(&h_p, &den, &pk.challenge_r2, net, sid).await?);
@@ -1236,6 +1200,7 @@ pub async fn dpermcheck<E: Pairing, Net: MPCSerializeNet>(
         wiring_proofs.push(sumcheck_product(&vx0, &vx1, &pk.challenge_r2[..s].to_vec()));
         end_timer!(timer);
     }
+    end_timer!(zerocheck_timer);
 
     end_timer!(timer_all);
 
@@ -1286,6 +1251,7 @@ pub async fn cpermcheck<E: Pairing, Net: MPCSerializeNet>(
     let mut wiring_proofs = Vec::new();
     let mut wiring_commits = Vec::new();
     let mut wiring_opens = Vec::new();
+    let timer = start_timer!("Commit and open sigma and id", net.is_leader());
     wiring_commits.push(
         pk.c_commitment
             .c_commit(&vec![pk.ssigma.clone()], &pp, &net, sid)
@@ -1306,8 +1272,10 @@ pub async fn cpermcheck<E: Pairing, Net: MPCSerializeNet>(
             .c_open(&pk.sid, &pk.challenge_r1, &pp, &net, sid)
             .await?,
     );
+    end_timer!(timer);
     for evaluations in &fs {
         // Compute V
+        let timer = start_timer!("Accumulated product", net.is_leader());
         let (vx0, vx1, v1x) = c_acc_product_and_share(
             evaluations,
             &pk.mask,
@@ -1320,7 +1288,9 @@ pub async fn cpermcheck<E: Pairing, Net: MPCSerializeNet>(
         )
         .await
         .unwrap();
+        end_timer!(timer);
         // Commit
+        let timer = start_timer!("Commit and open V", net.is_leader());
         wiring_commits.push(
             pk.c_commitment
                 .c_commit(&vec![evaluations.clone()], &pp, &net, sid)
@@ -1361,12 +1331,15 @@ pub async fn cpermcheck<E: Pairing, Net: MPCSerializeNet>(
                 .c_open(&v1x, &pk.challenge_r1, &pp, &net, sid)
                 .await?,
         );
+        end_timer!(timer);
         // Sumcheck for F(x)=eq(x)*(v1x-vx0*vx1).
+        let timer = start_timer!("Sumcheck", net.is_leader());
         wiring_proofs
             .push(c_sumcheck_product(&pk.eq_r1, &v1x, &pk.challenge_r1, &pp, &net, sid).await?);
         wiring_proofs
             .push(c_sumcheck_product(&pk.eq_r1, &vx0, &pk.challenge_r1, &pp, &net, sid).await?);
         wiring_proofs.push(c_sumcheck_product(&vx0, &vx1, &pk.challenge_r1, &pp, &net, sid).await?);
+        end_timer!(timer);
         // q(x) can be checked by evaluation
         wiring_opens.push(
             pk.c_commitment
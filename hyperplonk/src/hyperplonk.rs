@@ -4,17 +4,41 @@ use ark_std::UniformRand;
 use dist_primitive::{
     dacc_product::acc_product,
     dpoly_comm::{PolynomialCommitment, PolynomialCommitmentCub},
-    dsumcheck::sumcheck_product,
+    dsumcheck::{sumcheck_product, sumcheck_virtual},
     mle::fix_variable,
+    sumcheck_proof::SumcheckProof,
+    virtual_poly::VirtualPolynomial,
 };
 
 use dist_primitive::random_evaluations;
 use mpc_net::{end_timer, start_timer};
 
+/// The gate identity eq(x)*[q_1(x)*(a(x)+b(x))+q_2(x)*a(x)*b(x)-c(x)+I(x)] as a virtual
+/// polynomial. The extensions may be evaluations, shares or partial evaluations.
+pub fn gate_identity<F: Field>(
+    eq: &[F],
+    q1: &[F],
+    q2: &[F],
+    a: &[F],
+    b: &[F],
+    c: &[F],
+    input: &[F],
+) -> VirtualPolynomial<F> {
+    let mut poly = VirtualPolynomial::new();
+    let [eq, q1, q2, a, b, c, input] =
+        [eq, q1, q2, a, b, c, input].map(|mle| poly.add_mle(mle.to_vec()));
+    poly.add_product(F::ONE, [eq, q1, a]);
+    poly.add_product(F::ONE, [eq, q1, b]);
+    poly.add_product(F::ONE, [eq, q2, a, b]);
+    poly.add_product(-F::ONE, [eq, c]);
+    poly.add_product(F::ONE, [eq, input]);
+    poly
+}
+
 /// This is a simplified version without any optimization to simulate the complexity.
 pub fn local_hyperplonk<E: Pairing>(
     n: usize, // n is the log2 of the circuit size
-) -> ((Vec<Vec<(<E as Pairing>::ScalarField, <E as Pairing>::ScalarField, <E as Pairing>::ScalarField)>>, Vec<(<E as Pairing>::G1, (<E as Pairing>::ScalarField, Vec<<E as Pairing>::G1>))>), (Vec<Vec<(<E as Pairing>::ScalarField, <E as Pairing>::ScalarField, <E as Pairing>::ScalarField)>>, Vec<<E as Pairing>::G1>, Vec<(<E as Pairing>::ScalarField, Vec<<E as Pairing>::G1>)>)) {
+) -> ((SumcheckProof<<E as Pairing>::ScalarField>, Vec<(<E as Pairing>::G1, (<E as Pairing>::ScalarField, Vec<<E as Pairing>::G1>))>), (Vec<Vec<(<E as Pairing>::ScalarField, <E as Pairing>::ScalarField, <E as Pairing>::ScalarField)>>, Vec<<E as Pairing>::G1>, Vec<(<E as Pairing>::ScalarField, Vec<<E as Pairing>::G1>)>)) {
     use rand::{rngs::StdRng, SeedableRng};
     let rng = &mut StdRng::from_entropy();
     let gate_count = 1 << n;
@@ -65,30 +89,12 @@ pub fn local_hyperplonk<E: Pairing>(
 
     // Gate identity
     let gate_timer = start_timer!("Gate identity");
-    let mut gate_identity_proofs = Vec::new();
     let mut gate_identity_commitments = Vec::new();
     // Sumcheck F(x)=eq(x)*[q_1(x)*(a(x)+b(x))+q_2(x)*a(x)*b(x)-c(x)+I(x)]
-    // In original Hyperplonk this is done with a virtual circuit.
-    // We use different sumcheck product to simulate it for implementation simplicity. The computation complexity is the same.
-    // Part 1
-    gate_identity_proofs.push(sumcheck_product(&eq, &q1, &challenge));
-    let sum_ab = a_evals
-        .iter()
-        .zip(b_evals.iter())
-        .map(|(a, b)| *a + *b)
-        .collect();
-    gate_identity_proofs.push(sumcheck_product(&q1, &sum_ab, &challenge));
-    // Part 2
-    gate_identity_proofs.push(sumcheck_product(&eq, &q2, &challenge));
-    gate_identity_proofs.push(sumcheck_product(&a_evals, &b_evals, &challenge));
-    gate_identity_proofs.push(sumcheck_product(&q2, &a_evals, &challenge));
-    // Part 3
-    let sum_ci = c_evals
-        .iter()
-        .zip(input.iter())
-        .map(|(a, b)| -*a + *b)
-        .collect();
-    gate_identity_proofs.push(sumcheck_product(&eq, &sum_ci, &challenge));
+    let gate_identity_proof = sumcheck_virtual(
+        &gate_identity(&eq, &q1, &q2, &a_evals, &b_evals, &c_evals, &input),
+        &challenge,
+    );
     end_timer!(gate_timer);
 
     // Wire identity
@@ -154,14 +160,14 @@ pub fn local_hyperplonk<E: Pairing>(
 
     end_timer!(timer_all);
     (
-        (gate_identity_proofs, gate_identity_commitments),
+        (gate_identity_proof, gate_identity_commitments),
         (wiring_proofs, wiring_commits, wiring_opens),
     )
 }
 
 pub fn local_hyperplonkpp<E: Pairing>(
     n: usize, // n is the log2 of the circuit size
-) -> ((Vec<Vec<(<E as Pairing>::ScalarField, <E as Pairing>::ScalarField, <E as Pairing>::ScalarField)>>, Vec<(<E as Pairing>::G1, (<E as Pairing>::ScalarField, Vec<<E as Pairing>::G1>))>), (Vec<Vec<(<E as Pairing>::ScalarField, <E as Pairing>::ScalarField, <E as Pairing>::ScalarField)>>, Vec<<E as Pairing>::G1>, Vec<(<E as Pairing>::ScalarField, Vec<<E as Pairing>::G1>)>)) {
+) -> ((SumcheckProof<<E as Pairing>::ScalarField>, Vec<(<E as Pairing>::G1, (<E as Pairing>::ScalarField, Vec<<E as Pairing>::G1>))>), (Vec<Vec<(<E as Pairing>::ScalarField, <E as Pairing>::ScalarField, <E as Pairing>::ScalarField)>>, Vec<<E as Pairing>::G1>, Vec<(<E as Pairing>::ScalarField, Vec<<E as Pairing>::G1>)>)) {
     use rand::{rngs::StdRng, SeedableRng};
     let rng = &mut StdRng::from_entropy();
     let gate_count = 1 << n;
@@ -213,30 +219,12 @@ pub fn local_hyperplonkpp<E: Pairing>(
 
     // Gate identity
     let gate_timer = start_timer!("Gate identity");
-    let mut gate_identity_proofs = Vec::new();
     let mut gate_identity_commitments = Vec::new();
     // Sumcheck F(x)=eq(x)*[q_1(x)*(a(x)+b(x))+q_2(x)*a(x)*b(x)-c(x)+I(x)]
-    // In original Hyperplonk this is done with a virtual circuit.
-    // We use different sumcheck product to simulate it for implementation simplicity. The computation complexity is the same.
-    // Part 1
-    gate_identity_proofs.push(sumcheck_product(&eq, &q1, &challenge));
-    let sum_ab = a_evals
-        .iter()
-        .zip(b_evals.iter())
-        .map(|(a, b)| *a + *b)
-        .collect();
-    gate_identity_proofs.push(sumcheck_product(&q1, &sum_ab, &challenge));
-    // Part 2
-    gate_identity_proofs.push(sumcheck_product(&eq, &q2, &challenge));
-    gate_identity_proofs.push(sumcheck_product(&a_evals, &b_evals, &challenge));
-    gate_identity_proofs.push(sumcheck_product(&q2, &a_evals, &challenge));
-    // Part 3
-    let sum_ci = c_evals
-        .iter()
-        .zip(input.iter())
-        .map(|(a, b)| -*a + *b)
-        .collect();
-    gate_identity_proofs.push(sumcheck_product(&eq, &sum_ci, &challenge));
+    let gate_identity_proof = sumcheck_virtual(
+        &gate_identity(&eq, &q1, &q2, &a_evals, &b_evals, &c_evals, &input),
+        &challenge,
+    );
     end_timer!(gate_timer);
 
     // Wire identity
@@ -310,7 +298,7 @@ pub fn local_hyperplonkpp<E: Pairing>(
 
     end_timer!(timer_all);
     (
-        (gate_identity_proofs, gate_identity_commitments),
+        (gate_identity_proof, gate_identity_commitments),
         (wiring_proofs, wiring_commits, wiring_opens),
     )
 }
@@ -0,0 +1,268 @@
+//! Runs all parties of an example as processes on this machine.
+//!
+//! Writes a host file with free addresses, starts one process per party and waits for them.
+//! When a party fails or the timeout passes, the remaining parties are killed. Every party's
+//! output goes to its own file, and a report of their timers and communication is printed and
+//! written next to them. For example, with the example built first:
+//!
+//! ```bash
+//! just build --release -F benchmark --example bench_hyperplonk
+//! just run --release -p launcher -- --example bench_hyperplonk --l 2 -- --n 12
+//! ```
+mod report;
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitCode, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use clap::{ArgGroup, Parser, ValueEnum};
+
+use report::{PartyOutput, PartyResult};
+
+#[derive(Parser)]
+#[command(group(ArgGroup::new("target").required(true).args(["example", "program"])))]
+struct Cli {
+    /// An example built with the same profile as the launcher, e.g. `bench_hyperplonk`.
+    #[arg(long)]
+    example: Option<String>,
+    /// A program to run instead of an example. It gets `--file`, `--id` and `--l` like the
+    /// examples.
+    #[arg(long)]
+    program: Option<PathBuf>,
+    /// The packing size, passed on to the parties.
+    #[arg(long)]
+    l: usize,
+    /// The number of parties, `8 * l` by default.
+    #[arg(long)]
+    parties: Option<usize>,
+    /// How the parties connect.
+    #[arg(long, value_enum, default_value_t = Transport::Tcp)]
+    transport: Transport,
+    /// Where the host file, the output of every party and the report go.
+    #[arg(long, default_value = "output")]
+    out: PathBuf,
+    /// Kill the parties after this many seconds.
+    #[arg(long)]
+    timeout: Option<u64>,
+    /// Report the timers nested at most this deep.
+    #[arg(long, default_value_t = 1)]
+    depth: usize,
+    /// Have every party write its trace to `party-<id>.json`, see `trace-merge`.
+    #[arg(long)]
+    profile: bool,
+    /// Passed on to every party, e.g. `-- --n 20`.
+    #[arg(last = true)]
+    args: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Transport {
+    Tcp,
+    Quic,
+    Unix,
+    Shm,
+}
+
+/// The parties still running, killed when dropped.
+struct Parties(Vec<Option<Child>>);
+
+impl Parties {
+    fn kill_all(&mut self) {
+        for child in self.0.iter_mut().flatten() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for Parties {
+    fn drop(&mut self) {
+        self.kill_all();
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("launcher: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Run the parties, returning whether all of them succeeded.
+fn run(args: &Cli) -> io::Result<bool> {
+    let program = match (&args.example, &args.program) {
+        (Some(example), _) => example_path(example)?,
+        (None, Some(program)) => program.clone(),
+        (None, None) => unreachable!("clap requires one of them"),
+    };
+    let n_parties = args.parties.unwrap_or(8 * args.l);
+    fs::create_dir_all(&args.out)?;
+    let hosts = args.out.join("hosts.txt");
+    let mut file = File::create(&hosts)?;
+    for addr in addresses(args.transport, n_parties)? {
+        writeln!(file, "{addr}")?;
+    }
+    drop(file);
+
+    let start = Instant::now();
+    let mut parties = Parties(Vec::with_capacity(n_parties));
+    let mut readers = Vec::with_capacity(n_parties);
+    for id in 0..n_parties {
+        let (child, reader) = spawn(&program, &hosts, id, args)?;
+        parties.0.push(Some(child));
+        readers.push(reader);
+    }
+
+    let deadline = args.timeout.map(|secs| start + Duration::from_secs(secs));
+    let mut ended: Vec<Option<(String, bool, Duration)>> = vec![None; n_parties];
+    loop {
+        let mut failed = false;
+        for (id, slot) in parties.0.iter_mut().enumerate() {
+            let Some(child) = slot else {
+                continue;
+            };
+            if let Some(status) = child.try_wait()? {
+                let success = status.success();
+                failed |= !success;
+                let status = if success {
+                    "ok".to_string()
+                } else {
+                    status.to_string()
+                };
+                ended[id] = Some((status, success, start.elapsed()));
+                *slot = None;
+            }
+        }
+        if parties.0.iter().all(Option::is_none) {
+            break;
+        }
+        let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if failed || timed_out {
+            let reason = if timed_out { "timed out" } else { "killed" };
+            for (id, slot) in parties.0.iter().enumerate() {
+                if slot.is_some() {
+                    ended[id] = Some((reason.to_string(), false, start.elapsed()));
+                }
+            }
+            parties.kill_all();
+            parties.0.iter_mut().for_each(|slot| *slot = None);
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    let results = ended
+        .into_iter()
+        .zip(readers)
+        .enumerate()
+        .map(|(id, (ended, reader))| {
+            let (status, success, wall) = ended.expect("all parties ended");
+            let output = reader.join().unwrap_or_default();
+            PartyResult {
+                id,
+                status,
+                success,
+                wall,
+                output,
+            }
+        })
+        .collect::<Vec<_>>();
+    let report = report::render(&results, args.depth);
+    print!("{report}");
+    fs::write(args.out.join("report.txt"), report)?;
+    Ok(results.iter().all(|r| r.success))
+}
+
+/// The example `name`, built next to the launcher.
+fn example_path(name: &str) -> io::Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let path = exe
+        .parent()
+        .map(|dir| dir.join("examples").join(name))
+        .filter(|path| path.exists())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("example {name} not found, build it with the profile of the launcher"),
+            )
+        })?;
+    Ok(path)
+}
+
+/// Host file lines for `n_parties` on this machine.
+fn addresses(transport: Transport, n_parties: usize) -> io::Result<Vec<String>> {
+    let localhost: SocketAddr = ([127, 0, 0, 1], 0).into();
+    match transport {
+        // Hold all ports until every party has one, so they differ.
+        Transport::Tcp => {
+            let listeners = (0..n_parties)
+                .map(|_| TcpListener::bind(localhost))
+                .collect::<io::Result<Vec<_>>>()?;
+            listeners
+                .iter()
+                .map(|listener| Ok(listener.local_addr()?.to_string()))
+                .collect()
+        }
+        Transport::Quic => {
+            let sockets = (0..n_parties)
+                .map(|_| UdpSocket::bind(localhost))
+                .collect::<io::Result<Vec<_>>>()?;
+            sockets
+                .iter()
+                .map(|socket| Ok(format!("quic:{}", socket.local_addr()?)))
+                .collect()
+        }
+        Transport::Unix | Transport::Shm => {
+            let scheme = if transport == Transport::Unix { "unix" } else { "shm" };
+            // Socket paths are short, keep them out of the output directory.
+            let dir = std::env::temp_dir().join(format!("mpc-net-launcher-{}", std::process::id()));
+            Ok((0..n_parties)
+                .map(|id| format!("{scheme}:{}", dir.join(format!("party-{id}.sock")).display()))
+                .collect())
+        }
+    }
+}
+
+/// Start party `id`, its output parsed by the returned thread.
+fn spawn(
+    program: &Path,
+    hosts: &Path,
+    id: usize,
+    args: &Cli,
+) -> io::Result<(Child, JoinHandle<PartyOutput>)> {
+    let mut command = Command::new(program);
+    if args.profile {
+        command.arg("--profile").arg(args.out.join(format!("party-{id}")));
+    }
+    let mut child = command
+        .arg("--file")
+        .arg(hosts)
+        .arg("--id")
+        .arg(id.to_string())
+        .arg("--l")
+        .arg(args.l.to_string())
+        .args(&args.args)
+        .stdout(Stdio::piped())
+        .stderr(File::create(args.out.join(format!("party-{id}.log")))?)
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut log = File::create(args.out.join(format!("party-{id}.txt")))?;
+    let reader = thread::spawn(move || {
+        let mut output = PartyOutput::default();
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let _ = writeln!(log, "{line}");
+            output.parse_line(&line);
+        }
+        output
+    });
+    Ok((child, reader))
+}
@@ -0,0 +1,298 @@
+//! Parses what the parties print and summarizes it.
+//!
+//! Parties print timers with `start_timer!`/`end_timer!` and, at the end, their communication as
+//! `Comm: (sent, received)`. With their allocations tracked, a timer also prints the peak memory of
+//! its phase, and a party its peak and current memory in bytes as `Memory: (peak, current)`.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// A timer that ended.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timer {
+    /// How many timers enclose it.
+    pub depth: usize,
+    pub name: String,
+    pub time: Duration,
+    /// The peak memory of the phase in MiB.
+    pub peak: Option<f64>,
+}
+
+/// What a party printed.
+#[derive(Clone, Debug, Default)]
+pub struct PartyOutput {
+    pub timers: Vec<Timer>,
+    /// Bytes sent and received.
+    pub comm: Option<(usize, usize)>,
+    /// Peak and current memory in bytes.
+    pub memory: Option<(usize, usize)>,
+}
+
+impl PartyOutput {
+    pub fn parse_line(&mut self, line: &str) {
+        let line = strip_ansi(line);
+        let rest = line.trim_start_matches('·');
+        let depth = (line.len() - rest.len()) / '·'.len_utf8();
+        if let Some(rest) = rest.strip_prefix("End:") {
+            let (rest, peak) = split_memory(rest.trim_start());
+            if let Some((msg, time)) = split_time(rest) {
+                self.timers.push(Timer {
+                    depth,
+                    name: strip_thread(msg),
+                    time,
+                    peak,
+                });
+            }
+        } else if let Some(comm) = rest.strip_prefix("Comm: ") {
+            self.comm = parse_pair(comm).or(self.comm);
+        } else if let Some(memory) = rest.strip_prefix("Memory: ") {
+            self.memory = parse_pair(memory).or(self.memory);
+        }
+    }
+}
+
+/// Parse `(a, b)`.
+fn parse_pair(pair: &str) -> Option<(usize, usize)> {
+    let (a, b) = pair.strip_prefix('(')?.strip_suffix(')')?.split_once(", ")?;
+    Some((a.parse().ok()?, b.parse().ok()?))
+}
+
+/// Split the memory off the end of a timer line, e.g. `Commit 1.5ms [peak 2.000 MiB, current
+/// 1.000 MiB]`, giving the peak in MiB.
+fn split_memory(line: &str) -> (&str, Option<f64>) {
+    let line = line.trim_end();
+    let Some(start) = line.rfind(" [peak ").filter(|_| line.ends_with(" MiB]")) else {
+        return (line, None);
+    };
+    let peak = line[start + " [peak ".len()..]
+        .split_once(" MiB")
+        .and_then(|(peak, _)| peak.parse().ok());
+    (&line[..start], peak)
+}
+
+/// How a party ended.
+#[derive(Clone, Debug)]
+pub struct PartyResult {
+    pub id: usize,
+    pub status: String,
+    pub success: bool,
+    /// From launch to exit.
+    pub wall: Duration,
+    pub output: PartyOutput,
+}
+
+/// Summarize the parties: how they ended, their communication, and the timers nested at most
+/// `depth` deep, summed per party.
+pub fn render(results: &[PartyResult], depth: usize) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<6} {:<24} {:>12} {:>14} {:>14} {:>12}",
+        "party", "status", "wall", "sent", "received", "peak MiB"
+    );
+    let (mut sent, mut received) = (0, 0);
+    for result in results {
+        let (up, down) = result.output.comm.unwrap_or_default();
+        sent += up;
+        received += down;
+        let comm = |bytes: usize| match result.output.comm {
+            Some(_) => bytes.to_string(),
+            None => "-".to_string(),
+        };
+        let peak = match result.output.memory {
+            Some((peak, _)) => format!("{:.3}", peak as f64 / (1 << 20) as f64),
+            None => "-".to_string(),
+        };
+        let _ = writeln!(
+            out,
+            "{:<6} {:<24} {:>12} {:>14} {:>14} {:>12}",
+            result.id,
+            result.status,
+            format!("{:.3?}", result.wall),
+            comm(up),
+            comm(down),
+            peak
+        );
+    }
+    let _ = writeln!(out, "{:<6} {:<24} {:>12} {:>14} {:>14}", "total", "", "", sent, received);
+
+    // Per timer, the total time of every party that printed it.
+    let mut order = Vec::new();
+    let mut totals = HashMap::<(usize, &str), Vec<Duration>>::new();
+    // Per timer, the largest peak of every party that printed one.
+    let mut peaks = HashMap::<(usize, &str), Vec<f64>>::new();
+    for result in results {
+        let mut own = HashMap::<(usize, &str), Duration>::new();
+        let mut own_peaks = HashMap::<(usize, &str), f64>::new();
+        for timer in result.output.timers.iter().filter(|t| t.depth <= depth) {
+            let key = (timer.depth, timer.name.as_str());
+            if !totals.contains_key(&key) && !own.contains_key(&key) {
+                order.push(key);
+            }
+            *own.entry(key).or_default() += timer.time;
+            if let Some(peak) = timer.peak {
+                let max = own_peaks.entry(key).or_default();
+                *max = max.max(peak);
+            }
+        }
+        for (key, time) in own {
+            totals.entry(key).or_default().push(time);
+        }
+        for (key, peak) in own_peaks {
+            peaks.entry(key).or_default().push(peak);
+        }
+    }
+    if order.is_empty() {
+        return out;
+    }
+    let _ = writeln!(out);
+    let _ = write!(out, "{:<60} {:>8} {:>12} {:>12}", "timer", "parties", "min", "max");
+    if !peaks.is_empty() {
+        let _ = write!(out, " {:>14} {:>14}", "min peak MiB", "max peak MiB");
+    }
+    let _ = writeln!(out);
+    for key in order {
+        let times = &totals[&key];
+        let name = format!("{}{}", "  ".repeat(key.0), key.1);
+        let _ = write!(
+            out,
+            "{:<60} {:>8} {:>12} {:>12}",
+            name,
+            times.len(),
+            format!("{:.3?}", times.iter().min().unwrap()),
+            format!("{:.3?}", times.iter().max().unwrap())
+        );
+        if !peaks.is_empty() {
+            let (min, max) = match peaks.get(&key) {
+                Some(peaks) => (
+                    format!("{:.3}", peaks.iter().copied().fold(f64::INFINITY, f64::min)),
+                    format!("{:.3}", peaks.iter().copied().fold(0.0, f64::max)),
+                ),
+                None => ("-".to_string(), "-".to_string()),
+            };
+            let _ = write!(out, " {:>14} {:>14}", min, max);
+        }
+        let _ = writeln!(out);
+    }
+    out
+}
+
+/// Remove the color codes the timers print.
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // Skip up to the letter ending the escape sequence.
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Split the time off the end of a timer line, e.g. `Commit (thread ThreadId(1)) 1.5ms`.
+fn split_time(line: &str) -> Option<(&str, Duration)> {
+    let line = line.trim_end();
+    for (unit, scale) in [("ns", 1e-9), ("µs", 1e-6), ("ms", 1e-3), ("s", 1.0)] {
+        let Some(head) = line.strip_suffix(unit) else {
+            continue;
+        };
+        let start = head
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !c.is_ascii_digit() && *c != '.')
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        // Short messages are padded with dots.
+        let start = start + (head[start..].len() - head[start..].trim_start_matches('.').len());
+        let value: f64 = head[start..].parse().ok()?;
+        let msg = head[..start].trim_end_matches(['.', ' ']);
+        return Some((msg, Duration::from_secs_f64(value * scale)));
+    }
+    None
+}
+
+/// Remove the ` (thread ThreadId(1))` the timers add to their message.
+fn strip_thread(msg: &str) -> String {
+    match msg.find(" (thread ").and_then(|start| {
+        let end = start + msg[start..].find("))")? + 2;
+        Some((start, end))
+    }) {
+        Some((start, end)) => format!("{}{}", &msg[..start], &msg[end..]).trim().to_string(),
+        None => msg.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{render, PartyOutput, PartyResult, Timer};
+
+    #[test]
+    fn test_parse_and_render() {
+        let mut output = PartyOutput::default();
+        for line in [
+            "\u{1b}[1;33mStart:\u{1b}[0m   Commit (thread ThreadId(1))",
+            "\u{1b}[37m·\u{1b}[0m\u{1b}[1;32mEnd:\u{1b}[0m     Local: d_commit (thread ThreadId(1)) \u{1b}[1m12.500ms\u{1b}[0m",
+            "\u{1b}[1;32mEnd:\u{1b}[0m     Commit (thread ThreadId(1)) \u{1b}[1m1.250s\u{1b}[0m",
+            "End:     Commit (thread ThreadId(1)) extra\u{1b}[1m250.000µs\u{1b}[0m",
+            "End:     a (thread ThreadId(2)) .....7ns",
+            "End:     Open (thread ThreadId(2)) 2.000ms [peak 12.500 MiB, current 3.000 MiB]",
+            "Comm: (1024, 2048)",
+            "Memory: (52428800, 1048576)",
+            "Comm: from 0 to leader, 1B",
+            "unrelated",
+        ] {
+            output.parse_line(line);
+        }
+        let timer = |depth, name: &str, time| Timer {
+            depth,
+            name: name.to_string(),
+            time,
+            peak: None,
+        };
+        assert_eq!(
+            output.timers,
+            vec![
+                timer(1, "Local: d_commit", Duration::from_micros(12_500)),
+                timer(0, "Commit", Duration::from_millis(1250)),
+                timer(0, "Commit extra", Duration::from_micros(250)),
+                timer(0, "a", Duration::from_nanos(7)),
+                Timer {
+                    peak: Some(12.5),
+                    ..timer(0, "Open", Duration::from_millis(2))
+                },
+            ]
+        );
+        assert_eq!(output.comm, Some((1024, 2048)));
+        assert_eq!(output.memory, Some((50 << 20, 1 << 20)));
+
+        let result = |id, output| PartyResult {
+            id,
+            status: "ok".to_string(),
+            success: true,
+            wall: Duration::from_secs(2),
+            output,
+        };
+        let mut other = PartyOutput::default();
+        other.parse_line("End:     Commit (thread ThreadId(1)) 0.500s");
+        other.parse_line("End:     Commit (thread ThreadId(1)) 1.000s");
+        let report = render(&[result(0, output), result(1, other)], 0);
+        let line = |prefix: &str| {
+            let line = report.lines().find(|l| l.starts_with(prefix)).unwrap();
+            line.split_whitespace().collect::<Vec<_>>()
+        };
+        assert_eq!(line("total"), ["total", "1024", "2048"]);
+        assert_eq!(line("0 "), ["0", "ok", "2.000s", "1024", "2048", "50.000"]);
+        assert_eq!(line("1 "), ["1", "ok", "2.000s", "-", "-", "-"]);
+        assert_eq!(line("Commit "), ["Commit", "2", "1.250s", "1.500s", "-", "-"]);
+        assert_eq!(line("Open "), ["Open", "1", "2.000ms", "2.000ms", "12.500", "12.500"]);
+        assert!(!report.contains("d_commit"));
+    }
+}
@@ -0,0 +1,40 @@
+//! A coordinator the parties of an example register with instead of reading a host file, e.g.
+//!
+//! ```bash
+//! cargo run -p mpc-net --example coordinator -- --addr 0.0.0.0:7000 --parties 8
+//! ```
+//!
+//! and every party run with `--coordinator <coordinator-ip>:7000` in place of `--file`.
+use std::net::SocketAddr;
+
+use clap::Parser;
+
+use mpc_net::rendezvous::Coordinator;
+
+#[derive(Parser)]
+struct Cli {
+    /// Where the parties register.
+    #[arg(long, default_value = "0.0.0.0:7000")]
+    addr: SocketAddr,
+    /// The number of parties of a session.
+    #[arg(long)]
+    parties: usize,
+    /// Serve this many sessions one after the other, then exit.
+    #[arg(long, default_value_t = 1)]
+    sessions: usize,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::builder().format_timestamp(None).filter_level(log::LevelFilter::Trace).init();
+    let args = Cli::parse();
+    let coordinator = Coordinator::bind(args.addr, args.parties).await.unwrap();
+    println!("Coordinator listening at {}", coordinator.local_addr().unwrap());
+    for _ in 0..args.sessions {
+        let table = coordinator.run_session().await.unwrap();
+        println!("Session {:016x}:", table.session_id);
+        for peer in &table.peers {
+            println!("  party {} at {}", peer.id, peer.addr);
+        }
+    }
+}
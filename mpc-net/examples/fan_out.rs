@@ -0,0 +1,102 @@
+//! How long the parties of a [`LocalTestNet`] wait for a message from the leader, when the leader
+//! sends to one party after the other versus with the bounded concurrent fan-out of the
+//! collectives. With `--slow-ms`, party 1 starts reading that much later, like a busy peer, e.g.
+//!
+//! ```bash
+//! cargo run --release -p mpc-net --example fan_out -- --parties 64 --size 1048576 --slow-ms 50
+//! ```
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use tokio_util::bytes::Bytes;
+
+use mpc_net::profile::Profiler;
+use mpc_net::{end_timer, start_timer, LocalTestNet, MPCNet, MultiplexedStreamID};
+
+#[derive(Parser)]
+struct Cli {
+    #[arg(long, default_value_t = 64)]
+    parties: usize,
+    /// Bytes sent to every party.
+    #[arg(long, default_value_t = 1 << 20)]
+    size: usize,
+    #[arg(long, default_value_t = 10)]
+    rounds: usize,
+    /// How much later party 1 starts reading.
+    #[arg(long, default_value_t = 0)]
+    slow_ms: u64,
+    /// Record the timers of all parties and write them to `<PROFILE>.json` as a Chrome trace and
+    /// to `<PROFILE>.csv`.
+    #[arg(long)]
+    profile: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Cli::parse();
+    let profiler = args.profile.as_ref().map(|_| Profiler::init().unwrap());
+    let testnet = LocalTestNet::new_local_testnet(args.parties).await.unwrap();
+    let sid = MultiplexedStreamID::Zero;
+    let slow = Duration::from_millis(args.slow_ms);
+    let times = testnet
+        .simulate_network_round((args.size, args.rounds), move |net, (size, rounds)| async move {
+            let messages = || (0..net.n_parties()).map(|_| Bytes::from(vec![7u8; size])).collect();
+            let receive = || async {
+                if net.party_id() == 1 {
+                    tokio::time::sleep(slow).await;
+                }
+                net.recv_from(0, sid).await.unwrap();
+            };
+            let (mut sequential, mut concurrent) = (Duration::ZERO, Duration::ZERO);
+            for _ in 0..rounds {
+                net.sync().await.unwrap();
+                let timer = start_timer!("Sequential", false);
+                let start = Instant::now();
+                if net.is_leader() {
+                    let messages: Vec<Bytes> = messages();
+                    for (id, bytes) in messages.into_iter().enumerate().skip(1) {
+                        net.send_to(id as u32, bytes, sid).await.unwrap();
+                    }
+                } else {
+                    receive().await;
+                }
+                sequential += start.elapsed();
+                end_timer!(timer);
+
+                net.sync().await.unwrap();
+                let timer = start_timer!("Concurrent", false);
+                let start = Instant::now();
+                if net.is_leader() {
+                    net.worker_receive_or_leader_send(Some(messages()), sid).await.unwrap();
+                } else {
+                    if net.party_id() == 1 {
+                        tokio::time::sleep(slow).await;
+                    }
+                    net.worker_receive_or_leader_send(None, sid).await.unwrap();
+                }
+                concurrent += start.elapsed();
+                end_timer!(timer);
+            }
+            (sequential / rounds as u32, concurrent / rounds as u32)
+        })
+        .await;
+    let mean = |times: &mut dyn Iterator<Item = Duration>| times.sum::<Duration>() / (args.parties - 1) as u32;
+    let sequential = mean(&mut times[1..].iter().map(|t| t.0));
+    let concurrent = mean(&mut times[1..].iter().map(|t| t.1));
+    println!(
+        "{} parties, {}B each: leader sends in {:.3?} sequentially, {:.3?} concurrently; \
+         parties receive in {:.3?} sequentially, {:.3?} concurrently ({:.2}x)",
+        args.parties,
+        args.size,
+        times[0].0,
+        times[0].1,
+        sequential,
+        concurrent,
+        sequential.as_secs_f64() / concurrent.as_secs_f64(),
+    );
+    if let (Some(profiler), Some(path)) = (profiler, &args.profile) {
+        profiler.write_chrome_trace(path.with_extension("json"), 0).unwrap();
+        profiler.write_csv(path.with_extension("csv")).unwrap();
+    }
+}
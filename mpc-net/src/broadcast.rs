@@ -0,0 +1,233 @@
+//! Reliable broadcast.
+//!
+//! A plain [`MPCNet::broadcast_from`] trusts the sender to send the same bytes to everyone. A
+//! malicious sender can equivocate and give different parties different values. The protocol here
+//! is a lockstep variant of Bracha's reliable broadcast that tolerates `t = (n - 1) / 3` corrupted
+//! parties:
+//!
+//! 1. The sender sends its value to every party.
+//! 2. *Echo*: every party sends what it received (or its hash) to every party.
+//! 3. *Ready*: a party that saw `n - t` identical echoes sends the hash of that value to everyone,
+//!    otherwise it sends an empty ready.
+//! 4. A party delivers the value once it sees `2t + 1` ready messages for the same hash.
+//!
+//! Two quorums of `n - t` echoes intersect in at least one honest party, so no two honest parties
+//! can deliver different values. Parties that cannot deliver abort with [`MPCNetError::Protocol`]
+//! blaming the sender instead of silently continuing.
+use sha2::{Digest, Sha256};
+use tokio_util::bytes::Bytes;
+
+use crate::{MPCNet, MPCNetError, MultiplexedStreamID};
+
+const DIGEST_LEN: usize = 32;
+
+/// How a value is distributed by the `*_reliable` collectives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BroadcastMode {
+    /// Plain sends, no protection against equivocation.
+    #[default]
+    Trusted,
+    /// Echo the whole value. Costs `n` times the traffic of a plain broadcast, but an honest
+    /// party that received a wrong value from the sender can still recover the agreed one.
+    Echo,
+    /// Echo only a SHA-256 hash of the value. Cheap, but a party that received a wrong value
+    /// aborts instead of recovering.
+    HashEcho,
+}
+
+fn digest(bytes: &[u8]) -> [u8; DIGEST_LEN] {
+    Sha256::digest(bytes).into()
+}
+
+/// Maximum number of corrupted parties tolerated among `n`.
+pub fn max_faults(n: usize) -> usize {
+    (n - 1) / 3
+}
+
+/// Returns the item that occurs at least `threshold` times, if any.
+fn quorum<T: PartialEq>(items: &[T], threshold: usize) -> Option<&T> {
+    items
+        .iter()
+        .find(|x| items.iter().filter(|y| y == x).count() >= threshold)
+}
+
+/// Reliably broadcast a value from `sender`. Provide bytes iff you're the sender!
+/// All parties must use the same `mode`.
+pub async fn reliable_broadcast<N: MPCNet + ?Sized>(
+    net: &N,
+    bytes: Option<Bytes>,
+    sender: u32,
+    sid: MultiplexedStreamID,
+    mode: BroadcastMode,
+) -> Result<Bytes, MPCNetError> {
+    let received = net.broadcast_from(bytes, sender, sid).await?;
+    if mode == BroadcastMode::Trusted {
+        return Ok(received);
+    }
+    let n = net.n_parties();
+    let t = max_faults(n);
+    let equivocation = |err: &str| MPCNetError::Protocol {
+        reason: format!("Reliable broadcast from {}: {}", sender, err),
+        party: sender,
+    };
+
+    // Echo
+    let (value, ready) = match mode {
+        BroadcastMode::Echo => {
+            let echoes = net.all_gather(&received, sid).await?;
+            let value = quorum(&echoes, n - t).cloned();
+            let ready = value.as_ref().map(|v| digest(v));
+            (value.unwrap_or(received), ready)
+        }
+        _ => {
+            let echoes = net.all_gather(&digest(&received), sid).await?;
+            let ready = quorum(&echoes, n - t).map(|d| {
+                let mut ready = [0u8; DIGEST_LEN];
+                ready.copy_from_slice(d);
+                ready
+            });
+            (received, ready)
+        }
+    };
+
+    // Ready
+    let readies = net
+        .all_gather(ready.as_ref().map_or(&[][..], |d| &d[..]), sid)
+        .await?;
+    let agreed = quorum(&readies, 2 * t + 1)
+        .filter(|d| !d.is_empty())
+        .ok_or_else(|| equivocation("no agreement on the broadcast value"))?;
+    if agreed[..] != digest(&value) {
+        return Err(equivocation("received a value different from the agreed one"));
+    }
+    Ok(value)
+}
+
+/// Bytes `(sent, received)` by one party during a reliable broadcast of `len` bytes, for networks
+/// that only account for traffic.
+pub fn reliable_broadcast_comm(
+    mode: BroadcastMode,
+    len: usize,
+    n: usize,
+    is_sender: bool,
+) -> (usize, usize) {
+    let (initial_up, initial_down) = if is_sender {
+        (len * (n - 1), 0)
+    } else {
+        (0, len)
+    };
+    let echo = match mode {
+        BroadcastMode::Trusted => return (initial_up, initial_down),
+        BroadcastMode::Echo => len,
+        BroadcastMode::HashEcho => DIGEST_LEN,
+    };
+    let rounds = (echo + DIGEST_LEN) * (n - 1);
+    (initial_up + rounds, initial_down + rounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_util::bytes::Bytes;
+
+    use super::{reliable_broadcast, BroadcastMode};
+    use crate::{LocalTestNet, MPCNet, MPCNetError, MultiplexedStreamID};
+
+    const N_PARTIES: usize = 4;
+    const SENDER: u32 = 2;
+
+    /// Runs a reliable broadcast where the sender gives `values[i]` to party `i`.
+    async fn run(values: Vec<Bytes>, mode: BroadcastMode) -> Vec<Result<Bytes, MPCNetError>> {
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        testnet
+            .simulate_network_round(values, move |net, values| async move {
+                let sid = MultiplexedStreamID::Zero;
+                if net.party_id() == SENDER {
+                    // Equivocate by hand, then follow the protocol honestly.
+                    for id in (0..N_PARTIES as u32).filter(|id| *id != SENDER) {
+                        net.send_to(id, values[id as usize].clone(), sid).await.unwrap();
+                    }
+                    let own = values[SENDER as usize].clone();
+                    let net = NoBroadcast(&net);
+                    reliable_broadcast(&net, Some(own), SENDER, sid, mode).await
+                } else {
+                    reliable_broadcast(&net, None, SENDER, sid, mode).await
+                }
+            })
+            .await
+    }
+
+    /// Skips the initial send of the sender, which the test performs manually.
+    struct NoBroadcast<'a, N: MPCNet>(&'a N);
+
+    #[async_trait::async_trait]
+    impl<N: MPCNet> MPCNet for NoBroadcast<'_, N> {
+        fn n_parties(&self) -> usize {
+            self.0.n_parties()
+        }
+        fn party_id(&self) -> u32 {
+            self.0.party_id()
+        }
+        fn is_init(&self) -> bool {
+            self.0.is_init()
+        }
+        fn get_comm(&self) -> (usize, usize) {
+            self.0.get_comm()
+        }
+        fn add_comm(&self, up: usize, down: usize) {
+            self.0.add_comm(up, down)
+        }
+        async fn recv_from(&self, id: u32, sid: MultiplexedStreamID) -> Result<Bytes, MPCNetError> {
+            self.0.recv_from(id, sid).await
+        }
+        async fn send_to(
+            &self,
+            id: u32,
+            bytes: Bytes,
+            sid: MultiplexedStreamID,
+        ) -> Result<(), MPCNetError> {
+            self.0.send_to(id, bytes, sid).await
+        }
+        async fn broadcast_from(
+            &self,
+            bytes: Option<Bytes>,
+            _sender: u32,
+            _sid: MultiplexedStreamID,
+        ) -> Result<Bytes, MPCNetError> {
+            Ok(bytes.unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_honest_sender() {
+        for mode in [BroadcastMode::Trusted, BroadcastMode::Echo, BroadcastMode::HashEcho] {
+            let values = vec![Bytes::from_static(b"value"); N_PARTIES];
+            for result in run(values, mode).await {
+                assert_eq!(result.unwrap(), Bytes::from_static(b"value"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_equivocating_sender() {
+        let mut values = vec![Bytes::from_static(b"value"); N_PARTIES];
+        values[0] = Bytes::from_static(b"other");
+
+        // Full echoes let the odd party recover the value of the majority.
+        for result in run(values.clone(), BroadcastMode::Echo).await {
+            assert_eq!(result.unwrap(), Bytes::from_static(b"value"));
+        }
+
+        // With hashes only, the odd party notices but cannot recover.
+        let results = run(values.clone(), BroadcastMode::HashEcho).await;
+        assert!(matches!(results[0], Err(MPCNetError::Protocol { party: SENDER, .. })));
+        for result in results.into_iter().skip(1) {
+            assert_eq!(result.unwrap(), Bytes::from_static(b"value"));
+        }
+
+        // Without a quorum nobody delivers.
+        values[1] = Bytes::from_static(b"third");
+        for result in run(values, BroadcastMode::HashEcho).await {
+            assert!(matches!(result, Err(MPCNetError::Protocol { party: SENDER, .. })));
+        }
+    }
+}
@@ -0,0 +1,438 @@
+//! Scoped communication accounting.
+//!
+//! A [`CommTracker`] keeps a tree of named scopes. Opening a scope with [`crate::MPCNet::comm_scope`]
+//! makes it a child of the currently open scope; every message sent or received while it is open is
+//! recorded in it and in all its ancestors, broken down per peer and per stream. Scopes with the same
+//! name under the same parent are merged, so a scope opened in a loop accumulates.
+//!
+//! Rounds are counted as one-way communication rounds: the number of maximal runs of sends or of
+//! receives observed inside the scope. A `leader_compute` is thus two rounds for every party.
+//!
+//! The open scopes are kept per task: futures run concurrently, for instance with `join!`, each
+//! get their own path when wrapped in [`scoped`]. Guards opened outside of any [`scoped`] future
+//! are kept by the tracker and come first in the path.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::{MPCNetError, MultiplexedStreamID};
+
+/// Traffic over a single peer or stream.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LinkStats {
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    pub messages_sent: usize,
+    pub messages_received: usize,
+    /// Bytes including the framing added by the transport.
+    pub wire_bytes_sent: usize,
+    pub wire_bytes_received: usize,
+}
+
+impl LinkStats {
+    fn record(&mut self, direction: Direction, len: usize, wire_len: usize) {
+        match direction {
+            Direction::Send => {
+                self.bytes_sent += len;
+                self.wire_bytes_sent += wire_len;
+                self.messages_sent += 1;
+            }
+            Direction::Recv => {
+                self.bytes_received += len;
+                self.wire_bytes_received += wire_len;
+                self.messages_received += 1;
+            }
+        }
+    }
+}
+
+/// Traffic recorded inside a scope, including all of its children.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommStats {
+    #[serde(flatten)]
+    pub total: LinkStats,
+    /// Bytes that were accounted without being sent, see [`crate::MPCNet::add_comm`].
+    pub simulated_bytes_sent: usize,
+    pub simulated_bytes_received: usize,
+    pub rounds: usize,
+    pub per_peer: BTreeMap<u32, LinkStats>,
+    pub per_stream: BTreeMap<MultiplexedStreamID, LinkStats>,
+}
+
+/// A scope in the exported tree.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct CommNode {
+    pub name: String,
+    #[serde(flatten)]
+    pub stats: CommStats,
+    pub children: Vec<CommNode>,
+}
+
+impl CommNode {
+    /// Find a direct or indirect child by its path of names.
+    pub fn find(&self, path: &[&str]) -> Option<&CommNode> {
+        match path.split_first() {
+            None => Some(self),
+            Some((name, rest)) => self
+                .children
+                .iter()
+                .find(|c| c.name == *name)
+                .and_then(|c| c.find(rest)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Send,
+    Recv,
+}
+
+tokio::task_local! {
+    /// The scopes opened by the enclosing [`scoped`] futures and the guards inside them.
+    static SCOPES: RefCell<Vec<Entry>>;
+}
+
+/// Source of the ids of opened scopes, 0 is the root.
+static NEXT_ENTRY: AtomicU64 = AtomicU64::new(1);
+
+/// An opened scope.
+#[derive(Clone, Debug)]
+struct Entry {
+    /// Tells apart the times a scope is opened.
+    id: u64,
+    name: String,
+}
+
+impl Entry {
+    fn new(name: &str) -> Self {
+        Self {
+            id: NEXT_ENTRY.fetch_add(1, Ordering::Relaxed),
+            name: name.to_string(),
+        }
+    }
+}
+
+/// Run `f` in a new scope named `name`, a child of the scopes open around it. Unlike a guard of
+/// [`crate::MPCNet::comm_scope`], the scope only applies to `f`, even if other futures run
+/// concurrently with it.
+pub async fn scoped<F: Future>(name: &str, f: F) -> F::Output {
+    let mut path = SCOPES.try_with(|scopes| scopes.borrow().clone()).unwrap_or_default();
+    path.push(Entry::new(name));
+    SCOPES.scope(RefCell::new(path), f).await
+}
+
+#[derive(Debug)]
+struct Scope {
+    name: String,
+    stats: CommStats,
+    /// The entry this scope was last recorded in.
+    entry: u64,
+    last_direction: Option<Direction>,
+    children: Vec<usize>,
+}
+
+impl Scope {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            stats: CommStats::default(),
+            entry: 0,
+            last_direction: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Note that the scope is open as `entry`. Re-entering a scope starts a new run of rounds.
+    fn enter(&mut self, entry: u64) {
+        if self.entry != entry {
+            self.entry = entry;
+            self.last_direction = None;
+        }
+    }
+
+    fn record(
+        &mut self,
+        direction: Direction,
+        peer: u32,
+        sid: MultiplexedStreamID,
+        len: (usize, usize),
+    ) {
+        if self.last_direction != Some(direction) {
+            self.stats.rounds += 1;
+            self.last_direction = Some(direction);
+        }
+        let (len, wire_len) = len;
+        self.stats.total.record(direction, len, wire_len);
+        self.stats.per_peer.entry(peer).or_default().record(direction, len, wire_len);
+        self.stats.per_stream.entry(sid).or_default().record(direction, len, wire_len);
+    }
+}
+
+/// Records traffic of one party into a tree of named scopes.
+#[derive(Debug)]
+pub struct CommTracker {
+    /// The scopes, the root first.
+    scopes: Mutex<Vec<Scope>>,
+    /// The scopes opened by guards outside of any [`scoped`] future.
+    open: Mutex<Vec<Entry>>,
+}
+
+impl Default for CommTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommTracker {
+    pub fn new() -> Self {
+        Self {
+            scopes: Mutex::new(vec![Scope::new("root")]),
+            open: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Record a message of `len` bytes that took `wire_len` bytes on the wire.
+    pub fn record_send(&self, peer: u32, sid: MultiplexedStreamID, len: usize, wire_len: usize) {
+        self.record(Direction::Send, peer, sid, (len, wire_len))
+    }
+
+    pub fn record_recv(&self, peer: u32, sid: MultiplexedStreamID, len: usize, wire_len: usize) {
+        self.record(Direction::Recv, peer, sid, (len, wire_len))
+    }
+
+    /// Record bytes that are accounted for but never hit the wire.
+    pub fn record_simulated(&self, up: usize, down: usize) {
+        self.for_each_open(|scope| {
+            scope.stats.simulated_bytes_sent += up;
+            scope.stats.simulated_bytes_received += down;
+        });
+    }
+
+    fn record(
+        &self,
+        direction: Direction,
+        peer: u32,
+        sid: MultiplexedStreamID,
+        len: (usize, usize),
+    ) {
+        self.for_each_open(|scope| scope.record(direction, peer, sid, len));
+    }
+
+    /// The open scopes below the root: those of the guards outside of any [`scoped`] future,
+    /// then those of the current task.
+    fn path(&self) -> Vec<Entry> {
+        let mut path = self.open.lock().clone();
+        let _ = SCOPES.try_with(|scopes| path.extend(scopes.borrow().iter().cloned()));
+        path
+    }
+
+    /// Names of the open scopes below the root, separated by `/`.
+    pub fn current_path(&self) -> String {
+        let names: Vec<_> = self.path().into_iter().map(|entry| entry.name).collect();
+        names.join("/")
+    }
+
+    /// Apply `f` to the root and every open scope, creating the scopes not seen before.
+    fn for_each_open(&self, mut f: impl FnMut(&mut Scope)) {
+        let path = self.path();
+        let mut scopes = self.scopes.lock();
+        let mut parent = 0;
+        f(&mut scopes[0]);
+        for entry in path {
+            let existing = scopes[parent]
+                .children
+                .iter()
+                .copied()
+                .find(|&c| scopes[c].name == entry.name);
+            let index = existing.unwrap_or_else(|| {
+                scopes.push(Scope::new(&entry.name));
+                let index = scopes.len() - 1;
+                scopes[parent].children.push(index);
+                index
+            });
+            scopes[index].enter(entry.id);
+            f(&mut scopes[index]);
+            parent = index;
+        }
+    }
+
+    /// Open a scope in the current task, or in the tracker outside of any [`scoped`] future.
+    fn enter(&self, entry: Entry) {
+        let entered = SCOPES.try_with(|scopes| scopes.borrow_mut().push(entry.clone()));
+        if entered.is_err() {
+            self.open.lock().push(entry);
+        }
+        // Create the scope even if nothing is recorded in it.
+        self.for_each_open(|_| {});
+    }
+
+    /// Close the scope opened as `id`, wherever it is in the path.
+    fn exit(&self, id: u64) {
+        let removed = SCOPES
+            .try_with(|scopes| remove_entry(&mut scopes.borrow_mut(), id))
+            .unwrap_or(false);
+        if !removed {
+            remove_entry(&mut self.open.lock(), id);
+        }
+    }
+
+    /// Snapshot of the whole tree.
+    pub fn snapshot(&self) -> CommNode {
+        fn build(scopes: &[Scope], i: usize) -> CommNode {
+            CommNode {
+                name: scopes[i].name.clone(),
+                stats: scopes[i].stats.clone(),
+                children: scopes[i].children.iter().map(|&c| build(scopes, c)).collect(),
+            }
+        }
+        build(&self.scopes.lock(), 0)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.snapshot()).expect("comm tree is serializable")
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> Result<(), MPCNetError> {
+        std::fs::write(path, self.to_json())?;
+        Ok(())
+    }
+}
+
+fn remove_entry(path: &mut Vec<Entry>, id: u64) -> bool {
+    let position = path.iter().position(|entry| entry.id == id);
+    position.map(|i| path.remove(i)).is_some()
+}
+
+/// Guard returned by [`crate::MPCNet::comm_scope`]. The scope is closed when the guard is dropped,
+/// in whatever order the guards are dropped.
+#[must_use = "the scope is closed as soon as the guard is dropped"]
+pub struct CommScope<'a> {
+    tracker: Option<&'a CommTracker>,
+    id: u64,
+}
+
+impl<'a> CommScope<'a> {
+    pub fn enter(tracker: Option<&'a CommTracker>, name: &str) -> Self {
+        let entry = Entry::new(name);
+        let id = entry.id;
+        if let Some(tracker) = tracker {
+            tracker.enter(entry);
+        }
+        Self { tracker, id }
+    }
+}
+
+impl Drop for CommScope<'_> {
+    fn drop(&mut self) {
+        if let Some(tracker) = self.tracker {
+            tracker.exit(self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::scoped;
+    use crate::{LocalTestNet, MPCNet, MultiplexedStreamID};
+
+    #[tokio::test]
+    async fn test_comm_scopes() {
+        const N_PARTIES: usize = 4;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let trees = testnet
+            .simulate_network_round((), |net, _| async move {
+                {
+                    let _outer = net.comm_scope("outer");
+                    {
+                        let _inner = net.comm_scope("leader compute");
+                        net.leader_compute(&[1, 2, 3], MultiplexedStreamID::One, |v| v)
+                            .await
+                            .unwrap();
+                    }
+                    let _inner = net.comm_scope("leader compute");
+                    net.leader_compute(&[4, 5], MultiplexedStreamID::Two, |v| v)
+                        .await
+                        .unwrap();
+                }
+                let json = net.comm_tracker().unwrap().to_json();
+                assert!(json.contains("\"leader compute\""));
+                net.comm_tracker().unwrap().snapshot()
+            })
+            .await;
+
+        let leader = trees[0].find(&["outer", "leader compute"]).unwrap();
+        assert_eq!(leader.stats.total.bytes_received, 3 * 3 + 3 * 2);
+        assert_eq!(leader.stats.total.messages_sent, 6);
+        assert_eq!(leader.stats.rounds, 4);
+        assert_eq!(leader.stats.per_peer[&1].bytes_sent, 3 + 2);
+        assert_eq!(
+            leader.stats.per_stream[&MultiplexedStreamID::Two].bytes_received,
+            3 * 2
+        );
+        assert_eq!(trees[0].find(&["outer"]).unwrap().stats.total, leader.stats.total);
+
+        let worker = trees[1].find(&["outer", "leader compute"]).unwrap();
+        assert_eq!(worker.stats.total.bytes_sent, 3 + 2);
+        assert_eq!(worker.stats.total.messages_received, 2);
+        assert_eq!(worker.stats.rounds, 4);
+        assert_eq!(worker.stats.per_peer.keys().copied().collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_comm_scopes() {
+        const N_PARTIES: usize = 3;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let trees = testnet
+            .simulate_network_round((), |net, _| async move {
+                let _outer = net.comm_scope("outer");
+                let (a, b) = tokio::join!(
+                    scoped("a", async {
+                        let _inner = net.comm_scope("one");
+                        assert_eq!(net.current_step(), "outer/a/one");
+                        net.leader_compute(&[1, 2, 3], MultiplexedStreamID::One, |v| v).await
+                    }),
+                    scoped("b", async {
+                        assert_eq!(net.current_step(), "outer/b");
+                        net.leader_compute(&[4], MultiplexedStreamID::Two, |v| v).await
+                    }),
+                );
+                a.unwrap();
+                b.unwrap();
+                assert_eq!(net.current_step(), "outer");
+
+                // Guards dropped out of order close their own scope.
+                let first = net.comm_scope("first");
+                let second = net.comm_scope("second");
+                drop(first);
+                assert_eq!(net.current_step(), "outer/second");
+                drop(second);
+                assert_eq!(net.current_step(), "outer");
+                net.comm_tracker().unwrap().snapshot()
+            })
+            .await;
+
+        let a = trees[0].find(&["outer", "a"]).unwrap();
+        assert_eq!(a.stats.total.bytes_received, 3 * 2);
+        assert_eq!(a.stats.rounds, 2);
+        let streams: Vec<_> = a.stats.per_stream.keys().copied().collect();
+        assert_eq!(streams, vec![MultiplexedStreamID::One]);
+        assert_eq!(a.find(&["one"]).unwrap().stats.total, a.stats.total);
+        let b = trees[0].find(&["outer", "b"]).unwrap();
+        assert_eq!(b.stats.total.bytes_received, 2);
+        let streams: Vec<_> = b.stats.per_stream.keys().copied().collect();
+        assert_eq!(streams, vec![MultiplexedStreamID::Two]);
+        for tree in &trees {
+            assert!(tree.find(&["outer", "first", "second"]).is_some());
+            assert!(tree.find(&["outer", "a", "b"]).is_none());
+            assert!(tree.find(&["outer", "b", "one"]).is_none());
+        }
+    }
+}
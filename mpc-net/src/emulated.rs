@@ -0,0 +1,469 @@
+//! Network emulation on top of any [`MPCNet`].
+//!
+//! [`EmulatedNet`] wraps an inner network (typically a [`crate::LocalTestNet`] connection) and
+//! models per-link latency, jitter, a token-bucket bandwidth cap and message-level reordering.
+//! Every party keeps a simulated clock: local computation advances it by the real time elapsed
+//! between network events, and every message carries its simulated arrival time, so that the
+//! receiver's clock jumps forward whenever it has to wait for a slow link. This reproduces LAN/WAN
+//! figures on a single machine without `tc`, root privileges or real NICs.
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio_util::bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::{CommTracker, MPCNet, MPCNetError, MultiplexedStreamID, PointEncoding};
+
+/// Size of the simulated-arrival header prepended to each message.
+const HEADER_LEN: usize = 8;
+
+/// Characteristics of a single directed link.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkModel {
+    /// One-way propagation delay.
+    pub latency: Duration,
+    /// Upper bound of the uniformly distributed extra delay added to each message.
+    pub jitter: Duration,
+    /// Sustained rate of the token bucket in bytes per second. `None` means unlimited.
+    pub bandwidth: Option<u64>,
+    /// Capacity of the token bucket in bytes.
+    pub burst: usize,
+    /// Probability that a message is held back by `reorder_delay`, letting messages on other
+    /// streams overtake it. Messages on the same stream are still delivered in order.
+    pub reorder_probability: f64,
+    /// Extra delay applied to a reordered message.
+    pub reorder_delay: Duration,
+}
+
+impl Default for LinkModel {
+    fn default() -> Self {
+        Self::ideal()
+    }
+}
+
+impl LinkModel {
+    /// No delay and no bandwidth cap.
+    pub fn ideal() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            bandwidth: None,
+            burst: 0,
+            reorder_probability: 0.0,
+            reorder_delay: Duration::ZERO,
+        }
+    }
+
+    /// A 1 Gbps link with 0.1ms one-way latency.
+    pub fn lan() -> Self {
+        Self {
+            latency: Duration::from_micros(100),
+            bandwidth: Some(125_000_000),
+            burst: 64 * 1024,
+            ..Self::ideal()
+        }
+    }
+
+    /// A 200 Mbps link with 50ms one-way latency and 5ms jitter.
+    pub fn wan() -> Self {
+        Self {
+            latency: Duration::from_millis(50),
+            jitter: Duration::from_millis(5),
+            bandwidth: Some(25_000_000),
+            burst: 64 * 1024,
+            ..Self::ideal()
+        }
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Cap the link at `bytes_per_sec`, allowing bursts of up to `burst` bytes.
+    pub fn with_bandwidth(mut self, bytes_per_sec: u64, burst: usize) -> Self {
+        self.bandwidth = Some(bytes_per_sec);
+        self.burst = burst;
+        self
+    }
+
+    pub fn with_reordering(mut self, probability: f64, delay: Duration) -> Self {
+        self.reorder_probability = probability;
+        self.reorder_delay = delay;
+        self
+    }
+}
+
+/// Configuration of an [`EmulatedNet`].
+#[derive(Clone, Debug, Default)]
+pub struct EmulationConfig {
+    /// Model used for every link without an explicit override.
+    pub default_link: LinkModel,
+    /// Overrides for directed links, keyed by `(sender, receiver)`.
+    pub links: HashMap<(u32, u32), LinkModel>,
+    /// Seed of the jitter and reordering randomness, mixed with the party id.
+    pub seed: u64,
+    /// Also sleep for the simulated delays, so that real time tracks simulated time.
+    pub real_delay: bool,
+}
+
+impl EmulationConfig {
+    pub fn new(default_link: LinkModel) -> Self {
+        Self {
+            default_link,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_link(mut self, from: u32, to: u32, link: LinkModel) -> Self {
+        self.links.insert((from, to), link);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_real_delay(mut self, real_delay: bool) -> Self {
+        self.real_delay = real_delay;
+        self
+    }
+
+    pub fn link(&self, from: u32, to: u32) -> &LinkModel {
+        self.links.get(&(from, to)).unwrap_or(&self.default_link)
+    }
+}
+
+/// Token bucket of an outgoing link, measured in simulated time.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Duration,
+}
+
+impl TokenBucket {
+    /// Returns the simulated time at which `len` bytes have left the bucket.
+    fn take(&mut self, now: Duration, len: usize, rate: u64, burst: usize) -> Duration {
+        let rate = rate as f64;
+        let now = now.max(self.updated);
+        let refill = (now - self.updated).as_secs_f64() * rate;
+        self.tokens = (self.tokens + refill).min(burst as f64);
+        self.tokens -= len as f64;
+        self.updated = now;
+        if self.tokens >= 0.0 {
+            now
+        } else {
+            // Tokens in debt: the last byte leaves once the bucket is refilled to zero.
+            self.updated = now + Duration::from_secs_f64(-self.tokens / rate);
+            self.tokens = 0.0;
+            self.updated
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ClockState {
+    /// Simulated time of the last network event.
+    clock: Duration,
+    /// Real time of the last network event.
+    last_event: Instant,
+    buckets: HashMap<u32, TokenBucket>,
+    rng: StdRng,
+}
+
+impl ClockState {
+    /// Charge the real time spent computing since the last network event to the simulated clock.
+    fn tick(&mut self) -> Duration {
+        let now = Instant::now();
+        self.clock += now - self.last_event;
+        self.last_event = now;
+        self.clock
+    }
+}
+
+/// An [`MPCNet`] that emulates link characteristics on top of an inner network.
+pub struct EmulatedNet<N: MPCNet> {
+    inner: N,
+    config: EmulationConfig,
+    state: Mutex<ClockState>,
+    started: Instant,
+    upload: AtomicUsize,
+    download: AtomicUsize,
+    comm: CommTracker,
+}
+
+impl<N: MPCNet> EmulatedNet<N> {
+    pub fn new(inner: N, config: EmulationConfig) -> Self {
+        let now = Instant::now();
+        let seed = config.seed ^ (inner.party_id() as u64).rotate_left(32);
+        Self {
+            inner,
+            config,
+            state: Mutex::new(ClockState {
+                clock: Duration::ZERO,
+                last_event: now,
+                buckets: HashMap::new(),
+                rng: StdRng::seed_from_u64(seed),
+            }),
+            started: now,
+            upload: AtomicUsize::new(0),
+            download: AtomicUsize::new(0),
+            comm: CommTracker::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &N {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> N {
+        self.inner
+    }
+
+    pub fn config(&self) -> &EmulationConfig {
+        &self.config
+    }
+
+    /// Simulated wall-clock time since this party's clock was started.
+    pub fn simulated_time(&self) -> Duration {
+        self.state.lock().tick()
+    }
+
+    /// Real wall-clock time since this party's clock was started.
+    pub fn real_time(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Returns `(simulated, real)` wall-clock time.
+    pub fn get_time(&self) -> (Duration, Duration) {
+        (self.simulated_time(), self.real_time())
+    }
+
+    /// Restart both clocks, e.g. right after `sync` so that setup is not measured.
+    /// Only meaningful if all parties reset at the same protocol point.
+    pub fn reset_clock(&mut self) {
+        let now = Instant::now();
+        let state = self.state.get_mut();
+        state.clock = Duration::ZERO;
+        state.last_event = now;
+        state.buckets.clear();
+        self.started = now;
+    }
+
+    /// Compute the simulated arrival time of `len` bytes sent to `to` right now.
+    fn departure(&self, to: u32, len: usize) -> Duration {
+        let link = self.config.link(self.inner.party_id(), to);
+        let mut state = self.state.lock();
+        let now = state.tick();
+        let sent = match link.bandwidth {
+            Some(rate) if rate > 0 => state
+                .buckets
+                .entry(to)
+                .or_insert_with(|| TokenBucket {
+                    tokens: link.burst as f64,
+                    updated: now,
+                })
+                .take(now, len, rate, link.burst),
+            _ => now,
+        };
+        let mut delay = link.latency;
+        if !link.jitter.is_zero() {
+            delay += link.jitter.mul_f64(state.rng.gen::<f64>());
+        }
+        if link.reorder_probability > 0.0 && state.rng.gen_bool(link.reorder_probability.min(1.0))
+        {
+            delay += link.reorder_delay;
+        }
+        sent + delay
+    }
+}
+
+#[async_trait]
+impl<N: MPCNet> MPCNet for EmulatedNet<N> {
+    fn n_parties(&self) -> usize {
+        self.inner.n_parties()
+    }
+
+    fn party_id(&self) -> u32 {
+        self.inner.party_id()
+    }
+
+    fn is_init(&self) -> bool {
+        self.inner.is_init()
+    }
+
+    fn get_comm(&self) -> (usize, usize) {
+        (
+            self.upload.load(Ordering::Relaxed),
+            self.download.load(Ordering::Relaxed),
+        )
+    }
+
+    fn add_comm(&self, up: usize, down: usize) {
+        self.upload.fetch_add(up, Ordering::Relaxed);
+        self.download.fetch_add(down, Ordering::Relaxed);
+        self.comm.record_simulated(up, down);
+    }
+
+    fn comm_tracker(&self) -> Option<&CommTracker> {
+        Some(&self.comm)
+    }
+
+    fn point_encoding(&self) -> PointEncoding {
+        self.inner.point_encoding()
+    }
+
+    async fn recv_from(&self, id: u32, sid: MultiplexedStreamID) -> Result<Bytes, MPCNetError> {
+        self.state.lock().tick();
+        let mut bytes = self.inner.recv_from(id, sid).await?;
+        if bytes.len() < HEADER_LEN {
+            return Err(MPCNetError::Protocol {
+                reason: "Message without emulation header".to_string(),
+                party: id,
+            });
+        }
+        let arrival = Duration::from_nanos(bytes.get_u64());
+        let wait = {
+            let mut state = self.state.lock();
+            // The real time spent blocked is not computation: it includes the other parties'
+            // work and any real latency. Only the simulated arrival advances the clock.
+            state.last_event = Instant::now();
+            let now = state.clock;
+            state.clock = now.max(arrival);
+            arrival.saturating_sub(now)
+        };
+        if self.config.real_delay && !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+            self.state.lock().last_event = Instant::now();
+        }
+        self.download.fetch_add(bytes.len(), Ordering::Relaxed);
+        self.comm.record_recv(id, sid, bytes.len(), bytes.len() + HEADER_LEN);
+        Ok(bytes)
+    }
+
+    async fn send_to(
+        &self,
+        id: u32,
+        bytes: Bytes,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MPCNetError> {
+        let len = bytes.len();
+        let arrival = self.departure(id, len);
+        let mut framed = BytesMut::with_capacity(HEADER_LEN + len);
+        framed.put_u64(arrival.as_nanos() as u64);
+        framed.put(bytes);
+        self.inner.send_to(id, framed.freeze(), sid).await?;
+        self.upload.fetch_add(len, Ordering::Relaxed);
+        self.comm.record_send(id, sid, len, len + HEADER_LEN);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio_util::bytes::Bytes;
+
+    use super::{EmulatedNet, EmulationConfig, LinkModel};
+    use crate::{LocalTestNet, MPCNet, MultiplexedStreamID};
+
+    #[tokio::test]
+    async fn test_emulated_latency_and_bandwidth() {
+        const N_PARTIES: usize = 2;
+        const LEN: usize = 1 << 20;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        // 1 MiB over a 1 MiB/s link with 50ms latency takes slightly more than one second.
+        let config = EmulationConfig::new(
+            LinkModel::ideal()
+                .with_latency(Duration::from_millis(50))
+                .with_bandwidth(1 << 20, 0),
+        );
+
+        let times = testnet
+            .simulate_network_round(config, |conn, config| async move {
+                let net = EmulatedNet::new(conn, config);
+                if net.is_leader() {
+                    net.send_to(1, Bytes::from(vec![7u8; LEN]), MultiplexedStreamID::Zero)
+                        .await
+                        .unwrap();
+                } else {
+                    let bytes = net.recv_from(0, MultiplexedStreamID::Zero).await.unwrap();
+                    assert_eq!(bytes.len(), LEN);
+                    assert!(bytes.iter().all(|b| *b == 7));
+                }
+                (net.get_comm(), net.get_time())
+            })
+            .await;
+
+        assert_eq!(times[0].0, (LEN, 0));
+        assert_eq!(times[1].0, (0, LEN));
+        let (simulated, _real) = times[1].1;
+        assert!(simulated >= Duration::from_millis(1050));
+        assert!(simulated < Duration::from_millis(1050) + Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_emulated_real_wait_not_charged() {
+        const N_PARTIES: usize = 2;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        let config =
+            EmulationConfig::new(LinkModel::ideal().with_latency(Duration::from_millis(10)));
+
+        let times = testnet
+            .simulate_network_round(config, |conn, config| async move {
+                let mut net = EmulatedNet::new(conn, config);
+                if net.is_leader() {
+                    // Late in real time, but at the very start of the simulated clock.
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                    net.reset_clock();
+                    net.send_to(1, Bytes::from_static(b"late"), MultiplexedStreamID::Zero)
+                        .await
+                        .unwrap();
+                } else {
+                    net.recv_from(0, MultiplexedStreamID::Zero).await.unwrap();
+                }
+                net.get_time()
+            })
+            .await;
+
+        let (simulated, real) = times[1];
+        assert!(real >= Duration::from_millis(300));
+        assert!(simulated >= Duration::from_millis(10));
+        assert!(simulated < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_emulated_collectives() {
+        const N_PARTIES: usize = 4;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        let config = EmulationConfig::new(
+            LinkModel::wan().with_reordering(0.5, Duration::from_millis(10)),
+        );
+
+        let times = testnet
+            .simulate_network_round(config, |conn, config| async move {
+                let net = EmulatedNet::new(conn, config);
+                for _ in 0..3 {
+                    net.sync().await.unwrap();
+                }
+                net.simulated_time()
+            })
+            .await;
+
+        // Three syncs are six one-way trips of at least 50ms each.
+        for simulated in times.into_iter().skip(1) {
+            assert!(simulated >= Duration::from_millis(300));
+        }
+    }
+}
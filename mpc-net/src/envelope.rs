@@ -0,0 +1,220 @@
+//! Message envelopes, checked by the receiver to catch parties that got out of step.
+//!
+//! With [`WireConfig::envelopes`] asked for by all parties, every message is preceded by a header
+//! naming the protocol step it belongs to, its index among the messages of its stream, its sender
+//! and the type of its payload. The step is the path of the [`crate::MPCNet::comm_scope`]s open at
+//! the sender, in the future sending the message if steps run concurrently, see
+//! [`crate::comm::scoped`]. The receiver compares the header with what it expects itself and fails with a
+//! [`MPCNetError::Protocol`] describing both sides, instead of misreading the payload.
+//!
+//! Payload types are declared with [`typed`] around the futures sending or receiving them, other
+//! messages are declared as `bytes`. The header is accounted like framing, in the wire bytes only.
+//!
+//! [`WireConfig::envelopes`]: crate::WireConfig::envelopes
+use std::future::Future;
+
+use tokio_util::bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::MPCNetError;
+
+tokio::task_local! {
+    static PAYLOAD: &'static str;
+}
+
+/// Run `f`, declaring that the messages it sends and receives carry a `T`.
+pub async fn typed<T: ?Sized, F: Future>(f: F) -> F::Output {
+    PAYLOAD.scope(std::any::type_name::<T>(), f).await
+}
+
+/// The payload type declared by the enclosing [`typed`].
+pub fn payload_type() -> &'static str {
+    PAYLOAD.try_with(|payload| *payload).unwrap_or("bytes")
+}
+
+/// The header of a message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Envelope {
+    pub step: String,
+    /// Index of the message among those sent on its stream to the same peer.
+    pub round: u32,
+    pub sender: u32,
+    pub payload: String,
+}
+
+impl Envelope {
+    pub fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(12 + self.step.len() + self.payload.len());
+        put_str(&mut buf, &self.step);
+        buf.put_u32(self.round);
+        buf.put_u32(self.sender);
+        put_str(&mut buf, &self.payload);
+        buf.freeze()
+    }
+
+    /// Decode a header received from `party`.
+    pub fn decode(mut bytes: Bytes, party: u32) -> Result<Self, MPCNetError> {
+        let malformed = || MPCNetError::Protocol {
+            reason: "Malformed message envelope".to_string(),
+            party,
+        };
+        let step = get_str(&mut bytes).ok_or_else(malformed)?;
+        if bytes.remaining() < 8 {
+            return Err(malformed());
+        }
+        let round = bytes.get_u32();
+        let sender = bytes.get_u32();
+        let payload = get_str(&mut bytes).ok_or_else(malformed)?;
+        if bytes.has_remaining() {
+            return Err(malformed());
+        }
+        Ok(Self {
+            step,
+            round,
+            sender,
+            payload,
+        })
+    }
+
+    /// Check a received header against the expected one.
+    pub fn check(&self, expected: &Envelope) -> Result<(), MPCNetError> {
+        if self == expected {
+            return Ok(());
+        }
+        Err(MPCNetError::Protocol {
+            reason: format!("Got {}, expected {}", self, expected),
+            party: expected.sender,
+        })
+    }
+}
+
+impl std::fmt::Display for Envelope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "message {} of step \"{}\" from party {} carrying {}",
+            self.round, self.step, self.sender, self.payload
+        )
+    }
+}
+
+fn put_str(buf: &mut BytesMut, s: &str) {
+    let len = s.len().min(u16::MAX as usize);
+    buf.put_u16(len as u16);
+    buf.put_slice(&s.as_bytes()[..len]);
+}
+
+fn get_str(bytes: &mut Bytes) -> Option<String> {
+    if bytes.remaining() < 2 {
+        return None;
+    }
+    let len = bytes.get_u16() as usize;
+    if bytes.remaining() < len {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes.split_to(len)).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{payload_type, typed, Envelope};
+    use crate::comm::scoped;
+    use crate::{LocalTestNet, MPCNet, MPCNetError, MultiplexedStreamID, WireConfig};
+
+    #[test]
+    fn test_envelope_encoding() {
+        let envelope = Envelope {
+            step: "Wire identity/Open".to_string(),
+            round: 7,
+            sender: 3,
+            payload: payload_type().to_string(),
+        };
+        assert_eq!(Envelope::decode(envelope.encode(), 3).unwrap(), envelope);
+        assert!(Envelope::decode(envelope.encode().slice(1..), 3).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_desynchronization() {
+        const N_PARTIES: usize = 3;
+        let wire = WireConfig::default().with_envelopes(true);
+        let testnet = LocalTestNet::new_local_testnet_with_wire(N_PARTIES, wire)
+            .await
+            .unwrap();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::One;
+                let (_, download) = net.get_comm();
+                {
+                    let _scope = net.comm_scope("step");
+                    let r = typed::<u64, _>(net.leader_compute(&[1], sid, |v| v)).await;
+                    assert_eq!(r.unwrap(), &[1][..]);
+                }
+                // Envelopes are not counted as payload.
+                if net.is_leader() {
+                    assert_eq!(net.get_comm().1 - download, N_PARTIES - 1);
+                }
+
+                // Party 2 skips ahead to the next step.
+                let name = if net.party_id() == 2 { "next" } else { "step" };
+                let _scope = net.comm_scope(name);
+                let r = net.worker_send_or_leader_receive(&[2], sid).await;
+                if net.is_leader() {
+                    let Err(MPCNetError::Protocol { party, reason }) = r else {
+                        panic!("desynchronization not detected");
+                    };
+                    assert_eq!(party, 2);
+                    assert!(reason.contains("step \"next\""), "{reason}");
+                    assert!(reason.contains("expected message 1 of step \"step\""), "{reason}");
+                }
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_steps() {
+        const N_PARTIES: usize = 3;
+        let wire = WireConfig::default().with_envelopes(true);
+        let testnet = LocalTestNet::new_local_testnet_with_wire(N_PARTIES, wire)
+            .await
+            .unwrap();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                let _scope = net.comm_scope("outer");
+                // Both steps are open at once, each message is stamped with its own.
+                for _ in 0..3 {
+                    let (one, two) = tokio::join!(
+                        scoped("one", typed::<u64, _>(async {
+                            let sid = MultiplexedStreamID::One;
+                            net.leader_compute(&[1], sid, |v| v).await?;
+                            net.leader_compute(&[2], sid, |v| v).await
+                        })),
+                        scoped("two", async {
+                            let sid = MultiplexedStreamID::Two;
+                            net.leader_compute(&[3, 4], sid, |v| v).await
+                        }),
+                    );
+                    assert_eq!(one.unwrap(), &[2][..]);
+                    assert_eq!(two.unwrap(), &[3, 4][..]);
+                }
+
+                // Party 2 gets the second step wrong, the first one is still fine.
+                let name = if net.party_id() == 2 { "three" } else { "two" };
+                let (one, two) = (MultiplexedStreamID::One, MultiplexedStreamID::Two);
+                let (one, two) = tokio::join!(
+                    scoped("one", net.worker_send_or_leader_receive(&[5], one)),
+                    scoped(name, net.worker_send_or_leader_receive(&[6], two)),
+                );
+                assert!(one.is_ok());
+                if net.is_leader() {
+                    let Err(MPCNetError::Protocol { party, reason }) = two else {
+                        panic!("desynchronization not detected");
+                    };
+                    assert_eq!(party, 2);
+                    assert!(reason.contains("step \"outer/three\""), "{reason}");
+                    assert!(reason.contains("of step \"outer/two\""), "{reason}");
+                }
+            })
+            .await;
+    }
+}
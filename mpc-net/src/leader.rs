@@ -0,0 +1,123 @@
+//! The network of a leader running without the other parties.
+//!
+//! To estimate the cost of one party for many parties, the leader can run its part of a protocol
+//! alone. The other parties are only simulated: the element collectives of `dist_primitive` see
+//! [`MPCNet::is_simulated`], pretend that every party holds what the leader holds, and account
+//! the bytes the leader would send and receive. Nothing is sent, so a [`LeaderNet`] for thousands
+//! of parties costs no more than one for eight.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_trait::async_trait;
+use tokio_util::bytes::Bytes;
+
+use crate::{profile, CommTracker, MPCNet, MPCNetError, MultiplexedStreamID, PointEncoding};
+
+/// The leader of `n_parties` parties that are only simulated.
+pub struct LeaderNet {
+    n_parties: usize,
+    upload: AtomicUsize,
+    download: AtomicUsize,
+    comm: CommTracker,
+    point_encoding: PointEncoding,
+}
+
+impl LeaderNet {
+    pub fn new(n_parties: usize) -> Self {
+        Self {
+            n_parties,
+            upload: AtomicUsize::new(0),
+            download: AtomicUsize::new(0),
+            comm: CommTracker::new(),
+            point_encoding: PointEncoding::Compressed,
+        }
+    }
+
+    /// Account elements as if sent with `point_encoding`.
+    pub fn with_point_encoding(mut self, point_encoding: PointEncoding) -> Self {
+        self.point_encoding = point_encoding;
+        self
+    }
+}
+
+#[async_trait]
+impl MPCNet for LeaderNet {
+    fn n_parties(&self) -> usize {
+        self.n_parties
+    }
+
+    fn party_id(&self) -> u32 {
+        0
+    }
+
+    fn is_init(&self) -> bool {
+        true
+    }
+
+    fn is_simulated(&self) -> bool {
+        true
+    }
+
+    fn get_comm(&self) -> (usize, usize) {
+        (
+            self.upload.load(Ordering::Relaxed),
+            self.download.load(Ordering::Relaxed),
+        )
+    }
+
+    fn add_comm(&self, up: usize, down: usize) {
+        self.upload.fetch_add(up, Ordering::Relaxed);
+        self.download.fetch_add(down, Ordering::Relaxed);
+        self.comm.record_simulated(up, down);
+        profile::record_comm(up, down);
+    }
+
+    fn point_encoding(&self) -> PointEncoding {
+        self.point_encoding
+    }
+
+    fn comm_tracker(&self) -> Option<&CommTracker> {
+        Some(&self.comm)
+    }
+
+    /// There is no one to receive from.
+    async fn recv_from(&self, id: u32, _sid: MultiplexedStreamID) -> Result<Bytes, MPCNetError> {
+        Err(MPCNetError::Config(format!(
+            "party {id} is only simulated, the leader cannot receive from it"
+        )))
+    }
+
+    /// Only accounts the bytes.
+    async fn send_to(
+        &self,
+        _id: u32,
+        bytes: Bytes,
+        _sid: MultiplexedStreamID,
+    ) -> Result<(), MPCNetError> {
+        self.add_comm(bytes.len(), 0);
+        Ok(())
+    }
+
+    /// There is no one to wait for.
+    async fn sync(&self) -> Result<(), MPCNetError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LeaderNet;
+    use crate::{MPCNet, MultiplexedStreamID};
+
+    #[tokio::test]
+    async fn test_leader_net() {
+        let net = LeaderNet::new(1024);
+        assert!(net.is_leader() && net.is_simulated());
+        net.sync().await.unwrap();
+        net.send_to(1, vec![0; 10].into(), MultiplexedStreamID::Zero)
+            .await
+            .unwrap();
+        net.add_comm(5, 7);
+        assert_eq!(net.get_comm(), (15, 7));
+        assert!(net.recv_from(1, MultiplexedStreamID::Zero).await.is_err());
+    }
+}
@@ -1,37 +1,143 @@
+pub mod broadcast;
+pub mod comm;
+pub mod emulated;
+pub mod envelope;
+pub mod leader;
+pub mod memory;
+pub mod mode;
 pub mod multi;
+pub mod profile;
+pub mod rendezvous;
+pub mod session;
+pub mod transport;
 pub mod utils;
+pub mod wire;
 
 use async_trait::async_trait;
 use auto_impl::auto_impl;
+pub use broadcast::BroadcastMode;
+pub use comm::{CommScope, CommTracker};
 use futures::stream::FuturesOrdered;
-use futures::TryStreamExt;
+use futures::{StreamExt, TryStreamExt};
+pub use emulated::{EmulatedNet, EmulationConfig, LinkModel};
+pub use leader::LeaderNet;
+pub use mode::NetMode;
 pub use multi::LocalTestNet;
+pub use transport::{Quic, Shm, Tcp, Transport, TransportKind, Unix};
+pub use wire::{PointEncoding, WireConfig};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Debug;
-use tokio_util::bytes::Bytes;
+use tokio_util::bytes::{Bytes, BytesMut};
 
-#[derive(Clone, Debug)]
+/// Errors of the network layer.
+#[derive(Debug)]
 pub enum MPCNetError {
-    Generic(String),
-    Protocol { err: String, party: u32 },
-    NotConnected,
+    /// The transport failed.
+    Io(std::io::Error),
+    /// `party` did not answer in time.
+    Timeout { party: u32 },
+    /// The message `party` sent during `step` could not be deserialized.
+    Deserialize {
+        party: u32,
+        /// Path of the [`MPCNet::comm_scope`]s open at the receiver.
+        step: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// `party` deviated from the protocol.
+    Protocol { party: u32, reason: String },
+    /// The parties are not set up consistently, or the network is not set up.
+    Config(String),
+    /// The connection to `party` was lost and could not be restored.
+    Aborted { party: u32, reason: String },
+    /// A collective was called with arguments that do not fit the role of the caller.
     BadInput { err: &'static str },
 }
 
-impl<T: ToString> From<T> for MPCNetError {
-    fn from(e: T) -> Self {
-        MPCNetError::Generic(e.to_string())
+impl std::fmt::Display for MPCNetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MPCNetError::Io(err) => write!(f, "I/O error: {}", err),
+            MPCNetError::Timeout { party } => write!(f, "Timed out waiting for party {}", party),
+            MPCNetError::Deserialize { party, step, .. } => write!(
+                f,
+                "Cannot deserialize the message of party {} in step \"{}\"",
+                party, step
+            ),
+            MPCNetError::Protocol { party, reason } => {
+                write!(f, "Party {} deviated from the protocol: {}", party, reason)
+            }
+            MPCNetError::Config(err) => write!(f, "Bad configuration: {}", err),
+            MPCNetError::Aborted { party, reason } => {
+                write!(f, "Connection to party {} aborted: {}", party, reason)
+            }
+            MPCNetError::BadInput { err } => write!(f, "Bad input: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for MPCNetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MPCNetError::Io(err) => Some(err),
+            MPCNetError::Deserialize { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for MPCNetError {
+    fn from(err: std::io::Error) -> Self {
+        MPCNetError::Io(err)
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, Copy)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy)]
 pub enum MultiplexedStreamID {
     Zero = 0,
     One = 1,
     Two = 2,
 }
 
+/// Parent of a party in the binomial tree rooted at the leader used by the `tree_*` collectives.
+/// A party's parent is obtained by clearing its lowest set bit.
+pub fn tree_parent(party_id: u32) -> Option<u32> {
+    (party_id != 0).then(|| party_id & (party_id - 1))
+}
+
+/// Children of a party in the binomial tree of [`tree_parent`], largest subtree first.
+pub fn tree_children(party_id: u32, n_parties: usize) -> impl Iterator<Item = u32> {
+    let own_id = party_id as usize;
+    let lowest_bit = if own_id == 0 {
+        n_parties.next_power_of_two()
+    } else {
+        1 << own_id.trailing_zeros()
+    };
+    (0..lowest_bit.trailing_zeros())
+        .rev()
+        .map(move |i| own_id + (1 << i))
+        .filter(move |child| *child < n_parties)
+        .map(|child| child as u32)
+}
+
+/// How many sends a collective has in flight at once, enough for every peer of 64 parties. A
+/// smaller bound leaves the last batch of peers waiting for the slowest send of the batches before.
+pub const MAX_CONCURRENT_SENDS: usize = 64;
+
+/// Send every message to its party concurrently, at most [`MAX_CONCURRENT_SENDS`] at a time, so
+/// that a slow peer does not hold back the others.
+pub async fn fan_out<N: MPCNet + ?Sized>(
+    net: &N,
+    messages: impl Iterator<Item = (u32, Bytes)> + Send,
+    sid: MultiplexedStreamID,
+) -> Result<(), MPCNetError> {
+    futures::stream::iter(messages.map(|(id, bytes)| net.send_to(id, bytes, sid)))
+        .buffer_unordered(MAX_CONCURRENT_SENDS)
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(())
+}
+
 #[async_trait]
 #[auto_impl(&, &mut, Arc)]
 pub trait MPCNet: Send + Sync {
@@ -46,12 +152,45 @@ pub trait MPCNet: Send + Sync {
     fn party_id(&self) -> u32;
     /// Is the network layer initalized?
     fn is_init(&self) -> bool;
+    /// Are the other parties only simulated, see [`LeaderNet`]?
+    fn is_simulated(&self) -> bool {
+        false
+    }
 
     /// Get upload/download in bytes
     fn get_comm(&self) -> (usize, usize);
 
     fn add_comm(&self, up: usize, down: usize);
 
+    /// Get upload/download in bytes as written to the transport, including framing.
+    fn get_wire_comm(&self) -> (usize, usize) {
+        self.get_comm()
+    }
+
+    /// The encoding of elements all parties agreed on.
+    fn point_encoding(&self) -> PointEncoding {
+        PointEncoding::Compressed
+    }
+
+    /// The tracker recording per-scope traffic, if this network keeps one.
+    fn comm_tracker(&self) -> Option<&CommTracker> {
+        None
+    }
+
+    /// Open a named accounting scope, closed when the returned guard is dropped.
+    /// See [`comm`] for what is recorded, and [`comm::scoped`] for futures run concurrently.
+    fn comm_scope(&self, name: &str) -> CommScope<'_> {
+        CommScope::enter(self.comm_tracker(), name)
+    }
+
+    /// Path of the open accounting scopes, naming the current protocol step. Empty without a
+    /// tracker.
+    fn current_step(&self) -> String {
+        self.comm_tracker()
+            .map(CommTracker::current_path)
+            .unwrap_or_default()
+    }
+
     async fn recv_from(&self, id: u32, sid: MultiplexedStreamID) -> Result<Bytes, MPCNetError>;
     async fn send_to(
         &self,
@@ -114,6 +253,11 @@ pub trait MPCNet: Send + Sync {
         receiver: u32,
         sid: MultiplexedStreamID,
     ) -> Result<Option<Vec<Bytes>>, MPCNetError> {
+        if receiver as usize >= self.n_parties() {
+            return Err(MPCNetError::BadInput {
+                err: "dynamic_worker_send_or_leader_receive called with an unknown receiver",
+            });
+        }
         let bytes_out = Bytes::copy_from_slice(bytes);
         let own_id = self.party_id();
         let timer = start_timer!(
@@ -170,27 +314,26 @@ pub trait MPCNet: Send + Sync {
         let own_id = self.party_id();
 
         if let Some(bytes_out) = bytes_out {
-            let timer = start_timer!(
-                format!("Comm: from leader to all, {}B", bytes_out[0].len()),
-                self.is_leader()
-            );
-
             if !self.is_leader() {
                 return Err(MPCNetError::BadInput {
                     err: "recv_from_leader called with bytes_out when not leader",
                 });
             }
+            if bytes_out.len() != self.n_parties() {
+                return Err(MPCNetError::BadInput {
+                    err: "recv_from_leader called with a wrong number of messages",
+                });
+            }
 
-            for id in (0..self.n_parties()).filter(|p| *p != own_id as usize) {
-                // if bytes_out[id].len() != m {
-                //     return Err(MPCNetError::Protocol {
-                //         err: format!("The leader sent wrong number of bytes to Peer {}", id),
-                //         party: id as u32,
-                //     });
-                // }
+            let timer = start_timer!(
+                format!("Comm: from leader to all, {}B", bytes_out[0].len()),
+                self.is_leader()
+            );
 
-                self.send_to(id as u32, bytes_out[id].clone(), sid).await?;
-            }
+            let messages = (0..self.n_parties() as u32)
+                .filter(|id| *id != own_id)
+                .map(|id| (id, bytes_out[id as usize].clone()));
+            fan_out(self, messages, sid).await?;
 
             end_timer!(timer);
 
@@ -202,7 +345,10 @@ pub trait MPCNet: Send + Sync {
                 });
             }
 
-            self.recv_from(0, sid).await
+            let timer = start_timer!(format!("Comm: from leader to {}", own_id), false);
+            let r = self.recv_from(0, sid).await;
+            end_timer!(timer);
+            r
         }
     }
 
@@ -216,30 +362,39 @@ pub trait MPCNet: Send + Sync {
     ) -> Result<Bytes, MPCNetError> {
         let own_id = self.party_id();
 
-        if let Some(bytes_out) = bytes_out {
-            let timer = start_timer!(
-                format!("Comm: from {} to all, {}B", own_id, bytes_out[0].len()),
-                self.is_leader()
-            );
+        if sender as usize >= self.n_parties() {
+            return Err(MPCNetError::BadInput {
+                err: "dynamic_worker_receive_or_leader_send called with an unknown sender",
+            });
+        }
 
-            if !own_id == sender {
+        if let Some(bytes_out) = bytes_out {
+            if own_id != sender {
                 return Err(MPCNetError::BadInput {
-                    err: "recv_from_leader called with bytes_out when not leader",
+                    err: "dynamic_worker_receive_or_leader_send called with bytes_out when not the sender",
+                });
+            }
+            if bytes_out.len() != self.n_parties() {
+                return Err(MPCNetError::BadInput {
+                    err: "dynamic_worker_receive_or_leader_send called with a wrong number of messages",
                 });
             }
-
             let m = bytes_out[0].len();
+            if bytes_out.iter().any(|b| b.len() != m) {
+                return Err(MPCNetError::BadInput {
+                    err: "dynamic_worker_receive_or_leader_send called with messages of different lengths",
+                });
+            }
 
-            for id in (0..self.n_parties()).filter(|p| *p != own_id as usize) {
-                if bytes_out[id].len() != m {
-                    return Err(MPCNetError::Protocol {
-                        err: format!("The leader sent wrong number of bytes to Peer {}", id),
-                        party: id as u32,
-                    });
-                }
+            let timer = start_timer!(
+                format!("Comm: from {} to all, {}B", own_id, m),
+                self.is_leader()
+            );
 
-                self.send_to(id as u32, bytes_out[id].clone(), sid).await?;
-            }
+            let messages = (0..self.n_parties() as u32)
+                .filter(|id| *id != own_id)
+                .map(|id| (id, bytes_out[id as usize].clone()));
+            fan_out(self, messages, sid).await?;
 
             end_timer!(timer);
 
@@ -247,11 +402,14 @@ pub trait MPCNet: Send + Sync {
         } else {
             if own_id == sender {
                 return Err(MPCNetError::BadInput {
-                    err: "recv_from_leader called with no bytes_out when leader",
+                    err: "dynamic_worker_receive_or_leader_send called with no bytes_out when the sender",
                 });
             }
 
-            self.recv_from(sender, sid).await
+            let timer = start_timer!(format!("Comm: from {} to {}", sender, own_id), false);
+            let r = self.recv_from(sender, sid).await;
+            end_timer!(timer);
+            r
         }
     }
 
@@ -271,6 +429,274 @@ pub trait MPCNet: Send + Sync {
             .await
     }
 
+    /// Every party sends `bytes_out[i]` to party `i` and receives one message from every party.
+    /// Returns the messages sorted by sender, including the own entry of `bytes_out`.
+    /// The sends and receives are issued concurrently, see [`fan_out`].
+    async fn all_to_all(
+        &self,
+        bytes_out: Vec<Bytes>,
+        sid: MultiplexedStreamID,
+    ) -> Result<Vec<Bytes>, MPCNetError> {
+        let own_id = self.party_id();
+        if bytes_out.len() != self.n_parties() {
+            return Err(MPCNetError::BadInput {
+                err: "all_to_all called with a wrong number of messages",
+            });
+        }
+        let timer = start_timer!(
+            format!("Comm: from {} to all, {}B", own_id, bytes_out[0].len()),
+            self.is_leader()
+        );
+
+        let messages = (0..self.n_parties() as u32)
+            .filter(|id| *id != own_id)
+            .map(|id| (id, bytes_out[id as usize].clone()));
+        let sends = fan_out(self, messages, sid);
+        let recvs = (0..self.n_parties() as u32)
+            .map(|id| {
+                let own = bytes_out[own_id as usize].clone();
+                async move {
+                    if id == own_id {
+                        Ok(own)
+                    } else {
+                        self.recv_from(id, sid).await
+                    }
+                }
+            })
+            .collect::<FuturesOrdered<_>>()
+            .try_collect::<Vec<_>>();
+        let (_, r) = futures::try_join!(sends, recvs)?;
+
+        end_timer!(timer);
+        Ok(r)
+    }
+
+    /// `sender` sends the same bytes to every other party.
+    /// Provide bytes iff you're the sender!
+    async fn broadcast_from(
+        &self,
+        bytes: Option<Bytes>,
+        sender: u32,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MPCNetError> {
+        let own_id = self.party_id();
+        if sender as usize >= self.n_parties() {
+            return Err(MPCNetError::BadInput {
+                err: "broadcast_from called with an unknown sender",
+            });
+        }
+        match bytes {
+            Some(bytes) => {
+                if own_id != sender {
+                    return Err(MPCNetError::BadInput {
+                        err: "broadcast_from called with bytes when not the sender",
+                    });
+                }
+                let timer = start_timer!(
+                    format!("Comm: broadcast from {}, {}B", own_id, bytes.len()),
+                    self.is_leader()
+                );
+                let messages = (0..self.n_parties() as u32)
+                    .filter(|id| *id != own_id)
+                    .map(|id| (id, bytes.clone()));
+                fan_out(self, messages, sid).await?;
+                end_timer!(timer);
+                Ok(bytes)
+            }
+            None => {
+                if own_id == sender {
+                    return Err(MPCNetError::BadInput {
+                        err: "broadcast_from called with no bytes when the sender",
+                    });
+                }
+                let timer =
+                    start_timer!(format!("Comm: broadcast from {} to {}", sender, own_id), false);
+                let r = self.recv_from(sender, sid).await;
+                end_timer!(timer);
+                r
+            }
+        }
+    }
+
+    /// Like [`MPCNet::broadcast_from`], but guarantees that all honest parties that return
+    /// obtain the same bytes even if the sender equivocates. See [`broadcast`].
+    /// All parties must use the same `mode`.
+    async fn reliable_broadcast_from(
+        &self,
+        bytes: Option<Bytes>,
+        sender: u32,
+        sid: MultiplexedStreamID,
+        mode: BroadcastMode,
+    ) -> Result<Bytes, MPCNetError> {
+        broadcast::reliable_broadcast(self, bytes, sender, sid, mode).await
+    }
+
+    /// Every party sends its bytes to every other party.
+    /// Returns the bytes of all parties sorted by sender.
+    async fn all_gather(
+        &self,
+        bytes: &[u8],
+        sid: MultiplexedStreamID,
+    ) -> Result<Vec<Bytes>, MPCNetError> {
+        let bytes_out = Bytes::copy_from_slice(bytes);
+        self.all_to_all(vec![bytes_out; self.n_parties()], sid)
+            .await
+    }
+
+    /// Every party sends `bytes_out[i]` to party `i`, then reduces the messages it received
+    /// (sorted by sender) with `f`.
+    async fn reduce_scatter(
+        &self,
+        bytes_out: Vec<Bytes>,
+        sid: MultiplexedStreamID,
+        f: impl Fn(Vec<Bytes>) -> Bytes + Send,
+    ) -> Result<Bytes, MPCNetError> {
+        let bytes_in = self.all_to_all(bytes_out, sid).await?;
+        Ok(f(bytes_in))
+    }
+
+    /// Send a message given as a sequence of chunks, so that it never has to be in memory as a
+    /// whole. The receiver sees a single message, like one sent with [`MPCNet::send_to`].
+    async fn send_chunks_to(
+        &self,
+        id: u32,
+        chunks: impl Iterator<Item = Bytes> + Send,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MPCNetError> {
+        let mut bytes = BytesMut::new();
+        for chunk in chunks {
+            bytes.extend_from_slice(&chunk);
+        }
+        self.send_to(id, bytes.freeze(), sid).await
+    }
+
+    /// Receive a message chunk by chunk, handing each chunk to `on_chunk` as it arrives. Chunk
+    /// boundaries need not match the ones of [`MPCNet::send_chunks_to`].
+    async fn recv_chunks_from(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+        mut on_chunk: impl FnMut(Bytes) -> Result<(), MPCNetError> + Send,
+    ) -> Result<(), MPCNetError> {
+        on_chunk(self.recv_from(id, sid).await?)
+    }
+
+    /// All parties send bytes to the leader along a binomial tree. Every inner node merges the
+    /// bytes of its subtree with `combine` before forwarding, so the leader receives only
+    /// `log2(n)` messages instead of `n - 1`. `combine` must be associative; it is always called
+    /// with the bytes of the lower-numbered parties first.
+    /// [`MPCNetError::Deserialize`] errors of `combine` are attributed to the child whose bytes
+    /// were combined.
+    /// The leader receives the combination of all the bytes.
+    async fn tree_worker_send_or_leader_receive(
+        &self,
+        bytes: &[u8],
+        sid: MultiplexedStreamID,
+        combine: impl Fn(Bytes, Bytes) -> Result<Bytes, MPCNetError> + Send,
+    ) -> Result<Option<Bytes>, MPCNetError> {
+        let own_id = self.party_id() as usize;
+        let n = self.n_parties();
+        let timer = start_timer!(
+            format!("Comm: from {} to leader via tree, {}B", own_id, bytes.len()),
+            self.is_leader()
+        );
+        let mut acc = Bytes::copy_from_slice(bytes);
+        let mut step = 1;
+        let r = loop {
+            if step >= n {
+                break Some(acc);
+            }
+            if own_id % (2 * step) == step {
+                self.send_to((own_id - step) as u32, acc, sid).await?;
+                break None;
+            }
+            if own_id + step < n {
+                let child = (own_id + step) as u32;
+                let bytes_in = self.recv_from(child, sid).await?;
+                acc = combine(acc, bytes_in).map_err(|err| match err {
+                    MPCNetError::Deserialize { step, source, .. } => MPCNetError::Deserialize {
+                        party: child,
+                        step,
+                        source,
+                    },
+                    err => err,
+                })?;
+            }
+            step *= 2;
+        };
+        end_timer!(timer);
+        Ok(r)
+    }
+
+    /// The leader sends the same bytes to all parties along a binomial tree, so that it only sends
+    /// `log2(n)` messages. Provide bytes iff you're the leader!
+    async fn tree_worker_receive_or_leader_send(
+        &self,
+        bytes: Option<Bytes>,
+        sid: MultiplexedStreamID,
+    ) -> Result<Bytes, MPCNetError> {
+        let own_id = self.party_id();
+        // Opened before receiving, so that every party has a span for the round.
+        let timer = start_timer!(
+            match &bytes {
+                Some(bytes) => format!("Comm: from leader to all via tree, {}B", bytes.len()),
+                None => format!("Comm: from leader to {} via tree", own_id),
+            },
+            self.is_leader()
+        );
+        let bytes = match (bytes, tree_parent(own_id)) {
+            (Some(bytes), None) => bytes,
+            (None, Some(parent)) => self.recv_from(parent, sid).await?,
+            (Some(_), Some(_)) => {
+                return Err(MPCNetError::BadInput {
+                    err: "tree_worker_receive_or_leader_send called with bytes when not leader",
+                })
+            }
+            (None, None) => {
+                return Err(MPCNetError::BadInput {
+                    err: "tree_worker_receive_or_leader_send called with no bytes when leader",
+                })
+            }
+        };
+        let messages = tree_children(own_id, self.n_parties()).map(|child| (child, bytes.clone()));
+        fan_out(self, messages, sid).await?;
+        end_timer!(timer);
+        Ok(bytes)
+    }
+
+    /// Tree-shaped variant of [`MPCNet::leader_compute`]: the bytes of all parties are combined
+    /// along a binomial tree, the leader applies `f` to the combination and the result is sent back
+    /// to everyone along the same tree.
+    async fn tree_leader_compute(
+        &self,
+        bytes: &[u8],
+        sid: MultiplexedStreamID,
+        combine: impl Fn(Bytes, Bytes) -> Result<Bytes, MPCNetError> + Send,
+        f: impl Fn(Bytes) -> Bytes + Send,
+    ) -> Result<Bytes, MPCNetError> {
+        let leader_response = self
+            .tree_worker_send_or_leader_receive(bytes, sid, combine)
+            .await?
+            .map(f);
+        self.tree_worker_receive_or_leader_send(leader_response, sid)
+            .await
+    }
+
+    /// Like [`MPCNet::leader_compute`] for a computation whose result is the same for every
+    /// party. The result is distributed with [`MPCNet::reliable_broadcast_from`], so a malicious
+    /// leader cannot give different parties different answers.
+    async fn leader_compute_reliable(
+        &self,
+        bytes: &[u8],
+        sid: MultiplexedStreamID,
+        f: impl Fn(Vec<Bytes>) -> Bytes + Send,
+        mode: BroadcastMode,
+    ) -> Result<Bytes, MPCNetError> {
+        let leader_response = self.worker_send_or_leader_receive(bytes, sid).await?.map(f);
+        self.reliable_broadcast_from(leader_response, 0, sid, mode)
+            .await
+    }
+
     // Sync MPC state
     async fn sync(&self) -> Result<(), MPCNetError> {
         let bytes = &vec![135u8; 1];
@@ -0,0 +1,173 @@
+//! Peak and current heap allocation per phase.
+//!
+//! A binary installs [`TrackingAllocator`] as its global allocator, after which every timer of
+//! [`crate::start_timer`] is also a phase: it records the memory allocated when it ended and the
+//! peak while it was open, prints them next to its time and adds them to its span, see
+//! [`crate::profile`]. Without the allocator nothing is tracked.
+//!
+//! The counters are per process, so they are per party when each party is its own process, as in
+//! the benchmarks. Peaks of nested phases are exact; phases that overlap without nesting, e.g. of
+//! concurrent tasks, may miss the part of their peak before the other one started.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::Serialize;
+
+/// The bytes allocated now and at most.
+struct Counters {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Counters {
+            current: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    fn allocated(&self, size: usize) {
+        let current = self.current.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(current, Ordering::Relaxed);
+    }
+
+    fn freed(&self, size: usize) {
+        self.current.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    fn start_phase(&self) -> Phase {
+        let start = self.current.load(Ordering::Relaxed);
+        let outer_peak = self.peak.swap(start, Ordering::Relaxed);
+        Phase { start, outer_peak }
+    }
+
+    fn end_phase(&self, phase: Phase) -> MemoryUsage {
+        let peak = self.peak.fetch_max(phase.outer_peak, Ordering::Relaxed);
+        MemoryUsage {
+            start: phase.start,
+            end: self.current.load(Ordering::Relaxed),
+            peak,
+        }
+    }
+}
+
+static COUNTERS: Counters = Counters::new();
+
+/// The system allocator, counting the bytes allocated. Install it with
+/// `#[global_allocator] static ALLOC: TrackingAllocator = TrackingAllocator;`.
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            COUNTERS.allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            COUNTERS.allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        COUNTERS.freed(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                COUNTERS.allocated(new_size - layout.size());
+            } else {
+                COUNTERS.freed(layout.size() - new_size);
+            }
+        }
+        new_ptr
+    }
+}
+
+/// Whether [`TrackingAllocator`] is the global allocator.
+pub fn is_tracking() -> bool {
+    COUNTERS.peak.load(Ordering::Relaxed) > 0
+}
+
+/// The bytes allocated now.
+pub fn current() -> usize {
+    COUNTERS.current.load(Ordering::Relaxed)
+}
+
+/// The most bytes allocated at once, since the start or within the innermost open phase.
+pub fn peak() -> usize {
+    COUNTERS.peak.load(Ordering::Relaxed)
+}
+
+/// The memory of a phase, in bytes.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Allocated when the phase started.
+    pub start: usize,
+    /// Allocated when the phase ended.
+    pub end: usize,
+    /// The most allocated at once during the phase.
+    pub peak: usize,
+}
+
+/// A phase started with [`start_phase`].
+#[derive(Debug)]
+pub struct Phase {
+    start: usize,
+    outer_peak: usize,
+}
+
+/// Start a phase, or `None` if the allocations are not tracked. The peak restarts from the
+/// current allocation until [`end_phase`].
+pub fn start_phase() -> Option<Phase> {
+    if !is_tracking() {
+        return None;
+    }
+    Some(COUNTERS.start_phase())
+}
+
+/// End a phase, giving the peak back to the enclosing phases.
+pub fn end_phase(phase: Phase) -> MemoryUsage {
+    COUNTERS.end_phase(phase)
+}
+
+/// Bytes as MiB, as the timers print them.
+pub fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1 << 20) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phases() {
+        let counters = Counters::new();
+        counters.allocated(1000);
+        let outer = counters.start_phase();
+        counters.allocated(500);
+        let inner = counters.start_phase();
+        counters.allocated(300);
+        counters.freed(300);
+        let inner = counters.end_phase(inner);
+        counters.freed(500);
+        counters.allocated(100);
+        let outer = counters.end_phase(outer);
+        assert_eq!(inner, MemoryUsage { start: 1500, end: 1500, peak: 1800 });
+        assert_eq!(outer, MemoryUsage { start: 1000, end: 1100, peak: 1800 });
+        // The peak before the outer phase is back.
+        counters.freed(1100);
+        assert_eq!(counters.peak.load(Ordering::Relaxed), 1800);
+        let phase = counters.start_phase();
+        assert_eq!(counters.end_phase(phase).peak, 0);
+    }
+}
@@ -0,0 +1,68 @@
+//! How the parties of a local example run.
+use std::fmt;
+use std::str::FromStr;
+
+use tokio::runtime::{Builder, Runtime};
+
+/// How the parties of a local example run, chosen with `--mode`. The benchmarks run every party as
+/// its own process instead.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NetMode {
+    /// All parties in this process, one after the other on a single thread, communicating over
+    /// a [`crate::LocalTestNet`]. Divide the time by the number of parties.
+    #[default]
+    Local,
+    /// Like [`NetMode::Local`], with the parties on the threads of a multi-threaded runtime.
+    LocalMultiThread,
+    /// Only the leader, on a [`crate::LeaderNet`] accounting what it would send and receive.
+    Leader,
+}
+
+impl NetMode {
+    pub const ALL: [NetMode; 3] = [NetMode::Local, NetMode::LocalMultiThread, NetMode::Leader];
+
+    /// The runtime to run the parties on.
+    pub fn runtime(self) -> std::io::Result<Runtime> {
+        match self {
+            NetMode::Local | NetMode::Leader => Builder::new_current_thread().enable_all().build(),
+            NetMode::LocalMultiThread => Builder::new_multi_thread().enable_all().build(),
+        }
+    }
+}
+
+impl fmt::Display for NetMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NetMode::Local => "local",
+            NetMode::LocalMultiThread => "local-multi-thread",
+            NetMode::Leader => "leader",
+        })
+    }
+}
+
+impl FromStr for NetMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NetMode::ALL
+            .into_iter()
+            .find(|mode| mode.to_string() == s)
+            .ok_or_else(|| {
+                let modes = NetMode::ALL.map(|mode| mode.to_string()).join(", ");
+                format!("unknown mode {s}, expected one of {modes}")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetMode;
+
+    #[test]
+    fn test_parse_mode() {
+        for mode in NetMode::ALL {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+        assert!("benchmark".parse::<NetMode>().is_err());
+    }
+}
@@ -1,66 +1,522 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::{Debug, Formatter};
 use std::fs::File;
 use std::future::Future;
 use std::io::{BufRead, BufReader};
+use std::ops::{Deref, DerefMut};
 use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
-use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
 
-use crate::{MPCNetError, MultiplexedStreamID};
-use async_smux::{MuxBuilder, MuxStream};
+use crate::envelope::{self, Envelope};
+use crate::profile::{self, profiled};
+use crate::rendezvous::{rendezvous, PeerEntry, PeerTable};
+use crate::session::{
+    Closer, Dialer, Link, ReplayBuffer, Resume, ACK_INTERVAL, RECONNECT_TIMEOUT, REPLAY_LIMIT,
+};
+use crate::transport::{multiplex, Tcp, Transport};
+use crate::{CommTracker, MPCNetError, MultiplexedStreamID, PointEncoding, WireConfig};
+use async_smux::MuxStream;
 use async_trait::async_trait;
 use futures::stream::{FuturesOrdered, FuturesUnordered};
 use futures::{SinkExt, StreamExt, TryStreamExt};
-use log::trace;
+use log::{trace, warn};
 use parking_lot::Mutex;
-use tokio::sync::Mutex as TokioMutex;
-use tokio_util::bytes::Bytes;
-use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tokio::sync::{mpsc, Mutex as TokioMutex, MutexGuard as TokioMutexGuard, Notify};
+use tokio::task::AbortHandle;
+use tokio_util::bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio_util::codec::{
+    length_delimited, Decoder, Encoder, Framed, FramedRead, FramedWrite, LengthDelimitedCodec,
+};
 
 use super::MPCNet;
 
 pub type WrappedStream<T> = Framed<T, LengthDelimitedCodec>;
 
-pub fn wrap_stream<T: AsyncRead + AsyncWrite>(stream: T) -> Framed<T, LengthDelimitedCodec> {
-    LengthDelimitedCodec::builder()
+const MAX_FRAME_LENGTH: usize = 32 * 1024 * 1024;
+/// Bytes added to every frame by [`ChunkCodec`]: the length and the frame header.
+pub const FRAME_OVERHEAD: usize = 4 + HEADER_LEN;
+const HEADER_LEN: usize = 1 + 8 + 8;
+/// Payload bytes carried by a single frame. Larger messages are split over several frames.
+pub const CHUNK_SIZE: usize = 16 * 1024 * 1024;
+/// Bytes added to every frame in a batch: its length, see [`WireConfig::coalesce`].
+pub const BATCH_ENTRY_OVERHEAD: usize = 4;
+/// With coalescing, frames up to this size are batched, and a batch is sent once it holds this
+/// much.
+const COALESCE_LIMIT: usize = 64 * 1024;
+/// Set in the length of the last frame of a message in a batch.
+const LAST_ENTRY: u32 = 1 << 31;
+
+const DATA: u8 = 0;
+const DATA_LAST: u8 = 1;
+const ACK: u8 = 2;
+const BATCH: u8 = 3;
+
+fn codec_builder() -> length_delimited::Builder {
+    let mut builder = LengthDelimitedCodec::builder();
+    builder
         .big_endian()
         .length_field_type::<u32>()
-        .max_frame_length(32*1024*1024)
-        .new_framed(stream)
+        .max_frame_length(MAX_FRAME_LENGTH);
+    builder
+}
+
+/// A frame of a message. `last` is set on the final frame of every message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    pub last: bool,
+    /// The data holds several small frames instead, each preceded by its length, with
+    /// [`LAST_ENTRY`] set on the last frame of a message.
+    pub batch: bool,
+    pub data: Bytes,
+}
+
+impl Chunk {
+    /// The frames of a batch, `None` if it is malformed.
+    fn unbatch(mut data: Bytes) -> Option<Vec<Chunk>> {
+        let mut chunks = Vec::new();
+        while !data.is_empty() {
+            if data.len() < BATCH_ENTRY_OVERHEAD {
+                return None;
+            }
+            let entry = data.get_u32();
+            let len = (entry & !LAST_ENTRY) as usize;
+            if len > data.len() {
+                return None;
+            }
+            chunks.push(Chunk {
+                last: entry & LAST_ENTRY != 0,
+                batch: false,
+                data: data.split_to(len),
+            });
+        }
+        Some(chunks)
+    }
+}
+
+/// What is sent on a stream, see [`crate::session`]. `seq` numbers the data frames of a stream,
+/// `ack` is the number of data frames received on the stream so far. A frame without a chunk
+/// only carries an acknowledgement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub seq: u64,
+    pub ack: u64,
+    pub chunk: Option<Chunk>,
+}
+
+/// Length-delimited frames whose payload is prefixed with a header holding the kind of the frame,
+/// its sequence number and an acknowledgement.
+#[derive(Debug)]
+pub struct ChunkCodec {
+    inner: LengthDelimitedCodec,
+}
+
+impl Default for ChunkCodec {
+    fn default() -> Self {
+        Self {
+            inner: codec_builder().new_codec(),
+        }
+    }
+}
+
+impl Decoder for ChunkCodec {
+    type Item = Frame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, io::Error> {
+        let Some(mut frame) = self.inner.decode(src)? else {
+            return Ok(None);
+        };
+        if frame.len() < HEADER_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "frame without header"));
+        }
+        let kind = frame.get_u8();
+        let seq = frame.get_u64();
+        let ack = frame.get_u64();
+        let chunk = match kind {
+            DATA | DATA_LAST | BATCH => Some(Chunk {
+                last: kind != DATA,
+                batch: kind == BATCH,
+                data: frame.freeze(),
+            }),
+            ACK => None,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown frame kind")),
+        };
+        Ok(Some(Frame { seq, ack, chunk }))
+    }
+}
+
+impl Encoder<Frame> for ChunkCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: Frame, dst: &mut BytesMut) -> Result<(), io::Error> {
+        let (kind, data) = match &frame.chunk {
+            Some(Chunk { batch: true, data, .. }) => (BATCH, &data[..]),
+            Some(Chunk { last: true, data, .. }) => (DATA_LAST, &data[..]),
+            Some(Chunk { last: false, data, .. }) => (DATA, &data[..]),
+            None => (ACK, &[][..]),
+        };
+        let len = HEADER_LEN + data.len();
+        if len > MAX_FRAME_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk exceeds the frame limit",
+            ));
+        }
+        dst.reserve(FRAME_OVERHEAD + data.len());
+        dst.put_u32(len as u32);
+        dst.put_u8(kind);
+        dst.put_u64(frame.seq);
+        dst.put_u64(frame.ack);
+        dst.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+pub fn wrap_stream<T: AsyncRead + AsyncWrite>(stream: T) -> Framed<T, LengthDelimitedCodec> {
+    codec_builder().new_framed(stream)
+}
+
+/// A framed stream whose directions are locked independently, so that a pending receive does not
+/// block a concurrent send on the same stream.
+pub struct FramedChannel<T: AsyncRead + AsyncWrite> {
+    pub tx: TokioMutex<FramedWrite<WriteHalf<T>, ChunkCodec>>,
+    pub rx: TokioMutex<FramedRead<ReadHalf<T>, ChunkCodec>>,
+    /// Held while a message is sent. `tx` is only held for a frame at a time, so that
+    /// acknowledgements get through while the sender waits for room in the replay buffer.
+    sending: TokioMutex<()>,
+    /// Small frames held back to be sent as a single frame, see [`WireConfig::coalesce`].
+    batch: Mutex<BytesMut>,
+    /// Whether a flush of coalesced messages is pending.
+    flush_scheduled: AtomicBool,
+    /// Data frames sent and not acknowledged yet.
+    replay: Mutex<ReplayBuffer>,
+    /// Notified when frames are acknowledged, and when the receiving half is released.
+    acked: Notify,
+    /// Data frames read and not received yet, with the bytes they took on the wire: the frames
+    /// of a batch, and frames read by a sender waiting for acknowledgements.
+    stash: Mutex<VecDeque<(Chunk, usize)>>,
+    /// Payload bytes in `stash`.
+    stashed: AtomicUsize,
+    /// Data frames received.
+    received: AtomicU64,
+    /// Payload bytes received since an acknowledgement was last sent.
+    unacked: AtomicUsize,
+    /// Whether an acknowledgement is about to be sent.
+    ack_scheduled: AtomicBool,
+    /// Messages sent and received, numbering the envelopes.
+    messages_sent: AtomicU32,
+    messages_received: AtomicU32,
+}
+
+impl<T: AsyncRead + AsyncWrite> FramedChannel<T> {
+    fn received(&self) -> u64 {
+        self.received.load(Ordering::Acquire)
+    }
+
+    /// Bytes of the frames sent and not acknowledged yet.
+    pub fn replay_bytes(&self) -> usize {
+        self.replay.lock().bytes()
+    }
+
+    /// Process the acknowledgement of a frame read from `peer`, and stash its data frames
+    /// unless they were received before already. Returns the length of the data taken.
+    fn take(
+        &self,
+        peer: u32,
+        sid: MultiplexedStreamID,
+        frame: Frame,
+    ) -> Result<Option<usize>, MPCNetError> {
+        if self.replay.lock().ack(frame.ack) {
+            self.acked.notify_waiters();
+        }
+        let Some(chunk) = frame.chunk else {
+            return Ok(None);
+        };
+        let expected = self.received();
+        if frame.seq < expected {
+            // Received before the connection was restored.
+            return Ok(None);
+        }
+        if frame.seq > expected {
+            return Err(MPCNetError::Protocol {
+                party: peer,
+                reason: format!("Frame {} on stream {sid:?}, expected {expected}", frame.seq),
+            });
+        }
+        self.received.store(expected + 1, Ordering::Release);
+        let len = chunk.data.len();
+        let mut stash = self.stash.lock();
+        if !chunk.batch {
+            self.stashed.fetch_add(len, Ordering::AcqRel);
+            stash.push_back((chunk, FRAME_OVERHEAD));
+            return Ok(Some(len));
+        }
+        let chunks = Chunk::unbatch(chunk.data).ok_or_else(|| MPCNetError::Protocol {
+            party: peer,
+            reason: format!("Malformed batch on stream {sid:?}"),
+        })?;
+        // The first frame of the batch accounts for the frame it came in.
+        let mut overhead = FRAME_OVERHEAD;
+        for chunk in chunks {
+            self.stashed.fetch_add(chunk.data.len(), Ordering::AcqRel);
+            stash.push_back((chunk, overhead + BATCH_ENTRY_OVERHEAD));
+            overhead = 0;
+        }
+        Ok(Some(len))
+    }
+
+    /// The next data frame read and not received yet.
+    fn unstash(&self) -> Option<(Chunk, usize)> {
+        let stashed = self.stash.lock().pop_front()?;
+        self.stashed.fetch_sub(stashed.0.data.len(), Ordering::AcqRel);
+        Some(stashed)
+    }
+
+    /// Locks the receiving half.
+    async fn lock_rx(&self) -> RxGuard<'_, T> {
+        RxGuard {
+            rx: Some(self.rx.lock().await),
+            acked: &self.acked,
+        }
+    }
+
+    /// Locks the receiving half unless it is in use.
+    fn try_lock_rx(&self) -> Option<RxGuard<'_, T>> {
+        Some(RxGuard {
+            rx: Some(self.rx.try_lock().ok()?),
+            acked: &self.acked,
+        })
+    }
+
+    /// Appends a small frame to the pending batch. Returns the bytes it adds on the wire.
+    fn push_batched(&self, chunk: Chunk) -> usize {
+        let mut batch = self.batch.lock();
+        let overhead = if batch.is_empty() { FRAME_OVERHEAD } else { 0 };
+        let last = if chunk.last { LAST_ENTRY } else { 0 };
+        batch.put_u32(chunk.data.len() as u32 | last);
+        batch.extend_from_slice(&chunk.data);
+        chunk.data.len() + overhead + BATCH_ENTRY_OVERHEAD
+    }
+
+    /// Keeps a data frame for replay and numbers it. The sending half must be locked, so that
+    /// frames are sent in the order of their numbers.
+    fn number(&self, chunk: Chunk) -> Frame {
+        let seq = self.replay.lock().push(chunk.clone());
+        // Frames received once the acknowledgement is taken are acknowledged again later.
+        self.unacked.store(0, Ordering::Release);
+        let ack = self.received();
+        Frame { seq, ack, chunk: Some(chunk) }
+    }
+
+    /// The pending batch as a frame, if any. The sending half must be locked.
+    fn take_batch(&self) -> Option<Frame> {
+        let data = self.batch.lock().split().freeze();
+        if data.is_empty() {
+            return None;
+        }
+        Some(self.number(Chunk {
+            last: true,
+            batch: true,
+            data,
+        }))
+    }
+
+    /// Continue on a new transport.
+    fn replace(
+        tx: &mut FramedWrite<WriteHalf<T>, ChunkCodec>,
+        rx: &mut FramedRead<ReadHalf<T>, ChunkCodec>,
+        stream: T,
+    ) {
+        let (new_rx, new_tx) = io::split(stream);
+        *tx = FramedWrite::new(new_tx, ChunkCodec::default());
+        *rx = FramedRead::new(new_rx, ChunkCodec::default());
+    }
 }
 
-pub struct Peer<IO: AsyncRead + AsyncWrite + Unpin> {
+/// The locked receiving half of a [`FramedChannel`]. Releasing it wakes a sender waiting for
+/// acknowledgements, which reads them itself unless another receive follows.
+struct RxGuard<'a, T: AsyncRead + AsyncWrite> {
+    rx: Option<TokioMutexGuard<'a, FramedRead<ReadHalf<T>, ChunkCodec>>>,
+    acked: &'a Notify,
+}
+
+impl<T: AsyncRead + AsyncWrite> Deref for RxGuard<'_, T> {
+    type Target = FramedRead<ReadHalf<T>, ChunkCodec>;
+
+    fn deref(&self) -> &Self::Target {
+        self.rx.as_ref().unwrap()
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> DerefMut for RxGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.rx.as_mut().unwrap()
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> Drop for RxGuard<'_, T> {
+    fn drop(&mut self) {
+        // Only once the lock is released can the woken sender take it.
+        drop(self.rx.take());
+        self.acked.notify_waiters();
+    }
+}
+
+pub fn wrap_channel<T: AsyncRead + AsyncWrite>(stream: T) -> FramedChannel<T> {
+    let (rx, tx) = io::split(stream);
+    FramedChannel {
+        tx: TokioMutex::new(FramedWrite::new(tx, ChunkCodec::default())),
+        rx: TokioMutex::new(FramedRead::new(rx, ChunkCodec::default())),
+        sending: TokioMutex::new(()),
+        batch: Mutex::new(BytesMut::new()),
+        flush_scheduled: AtomicBool::new(false),
+        replay: Mutex::new(ReplayBuffer::default()),
+        acked: Notify::new(),
+        stash: Mutex::new(VecDeque::new()),
+        stashed: AtomicUsize::new(0),
+        received: AtomicU64::new(0),
+        unacked: AtomicUsize::new(0),
+        ack_scheduled: AtomicBool::new(false),
+        messages_sent: AtomicU32::new(0),
+        messages_received: AtomicU32::new(0),
+    }
+}
+
+pub struct Peer<T: Transport> {
     pub id: u32,
-    pub listen_addr: SocketAddr,
-    pub streams: Option<Vec<TokioMutex<WrappedMuxStream<IO>>>>,
+    pub listen_addr: T::Addr,
+    pub streams: Option<Vec<WrappedChannel<T>>>,
+    /// The wire options announced by the peer when connecting.
+    pub wire: Option<WireConfig>,
+    /// How to restore the connection if it drops, see [`crate::session`].
+    pub link: Option<Arc<Link<T::Conn>>>,
 }
 
-impl<IO: AsyncRead + AsyncWrite + Unpin> Debug for Peer<IO> {
+impl<T: Transport> Debug for Peer<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let mut f = f.debug_struct("Peer");
         f.field("id", &self.id);
         f.field("listen_addr", &self.listen_addr);
         f.field("streams", &self.streams.is_some());
+        f.field("wire", &self.wire);
         f.finish()
     }
 }
 
-impl<IO: AsyncRead + AsyncWrite + Unpin> Clone for Peer<IO> {
+impl<T: Transport> Clone for Peer<T> {
     fn clone(&self) -> Self {
         Self {
             id: self.id,
-            listen_addr: self.listen_addr,
+            listen_addr: self.listen_addr.clone(),
             streams: None,
+            wire: self.wire,
+            link: self.link.clone(),
         }
     }
 }
 
-pub type WrappedMuxStream<T> = Framed<MuxStream<T>, LengthDelimitedCodec>;
+impl<T: Transport> Peer<T> {
+    fn stream(&self, sid: MultiplexedStreamID) -> Result<&WrappedChannel<T>, MPCNetError> {
+        self.streams
+            .as_ref()
+            .and_then(|r| r.get(sid as usize))
+            .ok_or_else(|| MPCNetError::Config(format!("Not connected to party {}", self.id)))
+    }
+
+    fn generation(&self) -> u64 {
+        self.link.as_ref().map_or(0, |link| link.generation())
+    }
+
+    /// Restore the connection after `cause` was observed on the transport of the given
+    /// generation. Fails with `cause` if the link cannot be restored.
+    async fn reconnect(
+        &self,
+        generation: u64,
+        wire: WireConfig,
+        cause: MPCNetError,
+    ) -> Result<(), MPCNetError> {
+        let Some((link, dialer)) = self
+            .link
+            .as_ref()
+            .and_then(|link| Some((link, link.dialer()?)))
+        else {
+            return Err(cause);
+        };
+        let Some(_reconnecting) = link.lock_for_reconnect(generation).await else {
+            // Another task already restored the connection.
+            return Ok(());
+        };
+        warn!("Connection to peer {} lost ({cause:?}), reconnecting", self.id);
+        let streams = self.streams.as_ref().ok_or_else(|| {
+            MPCNetError::Config(format!("Not connected to party {}", self.id))
+        })?;
+        // Nothing may be sent or read while the transport is replaced.
+        let mut locked = Vec::with_capacity(streams.len());
+        for stream in streams {
+            locked.push((stream.tx.lock().await, stream.lock_rx().await));
+        }
+
+        link.close().await;
+        let mut transport = dialer().await?;
+        let resume = Resume {
+            wire,
+            received: streams.iter().map(|s| s.received()).collect(),
+        };
+        let peer_resume = if link.is_server {
+            let peer_resume = Resume::read(&mut transport).await?;
+            resume.write(&mut transport).await?;
+            peer_resume
+        } else {
+            resume.write(&mut transport).await?;
+            Resume::read(&mut transport).await?
+        };
+        if peer_resume.received.len() != streams.len() {
+            return Err(MPCNetError::Protocol {
+                party: self.id,
+                reason: format!("Peer resumes {} streams", peer_resume.received.len()),
+            });
+        }
+
+        let (muxed, closer) = T::split(transport, streams.len(), link.is_server).await?;
+        let replayed = async {
+            for (((stream, (tx, rx)), mux), received) in streams
+                .iter()
+                .zip(locked.iter_mut())
+                .zip(muxed)
+                .zip(peer_resume.received)
+            {
+                FramedChannel::replace(tx, rx, mux);
+                let frames = stream.replay.lock().resume(received).map_err(|reason| {
+                    MPCNetError::Aborted {
+                        party: self.id,
+                        reason: format!("Cannot resume: {reason}"),
+                    }
+                })?;
+                stream.acked.notify_waiters();
+                for (seq, chunk) in frames {
+                    let ack = stream.received();
+                    tx.feed(Frame { seq, ack, chunk: Some(chunk) }).await?;
+                }
+                tx.flush().await?;
+            }
+            Ok::<_, MPCNetError>(())
+        };
+        if let Err(err) = replayed.await {
+            closer().await;
+            return Err(err);
+        }
+        link.reconnected(closer);
+        trace!("Reconnected to peer {}", self.id);
+        Ok(())
+    }
+}
+
+pub type WrappedMuxStream<T> = Arc<FramedChannel<MuxStream<T>>>;
+pub type WrappedChannel<T> = Arc<FramedChannel<<T as Transport>::Channel>>;
 pub const MULTIPLEXED_STREAMS: usize = 3;
 
 /// Should be called immediately after making a connection to a peer.
@@ -68,44 +524,146 @@ pub async fn multiplex_stream<T: AsyncRead + AsyncWrite + Unpin + Send + 'static
     channels: usize,
     is_server: bool,
     stream: T,
-) -> Result<Vec<TokioMutex<WrappedMuxStream<T>>>, MPCNetError> {
-    if is_server {
-        let (_connector, mut acceptor, worker) =
-            MuxBuilder::server().with_connection(stream).build();
-        tokio::spawn(worker);
-        let mut ret = Vec::new();
-        for _ in 0..channels {
-            ret.push(TokioMutex::new(wrap_stream(
-                acceptor.accept().await.ok_or_else(|| {
-                    MPCNetError::Generic("Error accepting connection".to_string())
-                })?,
-            )));
-        }
-
-        Ok(ret)
-    } else {
-        let (connector, _acceptor, worker) = MuxBuilder::client().with_connection(stream).build();
-        tokio::spawn(worker);
-        let mut ret = Vec::new();
-        for _ in 0..channels {
-            ret.push(TokioMutex::new(wrap_stream(connector.connect()?)));
+) -> Result<Vec<WrappedMuxStream<T>>, MPCNetError> {
+    let (muxed, _) = multiplex(channels, is_server, stream).await?;
+    Ok(muxed.into_iter().map(|s| Arc::new(wrap_channel(s))).collect())
+}
+
+/// Splits a connection into the channels of [`MultiplexedStreamID`], also returning how to close
+/// the connection.
+async fn split_channels<T: Transport>(
+    conn: T::Conn,
+    is_server: bool,
+) -> Result<(Vec<WrappedChannel<T>>, Closer), MPCNetError> {
+    let (channels, closer) = T::split(conn, MULTIPLEXED_STREAMS, is_server).await?;
+    let channels = channels.into_iter().map(|s| Arc::new(wrap_channel(s))).collect();
+    Ok((channels, closer))
+}
+
+/// Dials a peer, retrying while it is not listening yet, and introduces us.
+async fn dial<T: Transport>(
+    endpoint: Arc<T::Endpoint>,
+    my_id: u32,
+    peer_id: u32,
+    addr: T::Addr,
+) -> Result<T::Conn, MPCNetError> {
+    let mut res = Err(MPCNetError::Timeout { party: peer_id });
+    for _ in 0..30 {
+        res = T::connect(&endpoint, addr.clone()).await;
+        if res.is_ok() {
+            break;
         }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    let mut stream = res.map_err(|err| {
+        warn!("Error connecting to peer {peer_id}: {err:?}");
+        MPCNetError::Timeout { party: peer_id }
+    })?;
+    stream.write_u32(my_id).await?;
+    Ok(stream)
+}
 
-        Ok(ret)
+/// Exchanges the session ids handed out by the coordinator, 0 without one, and rejects a peer of
+/// another session.
+async fn check_session<IO: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut IO,
+    peer_id: u32,
+    session_id: u64,
+) -> Result<(), MPCNetError> {
+    stream.write_u64(session_id).await?;
+    let peer_session = stream.read_u64().await?;
+    if peer_session != session_id {
+        return Err(MPCNetError::Config(format!(
+            "Peer {peer_id} is in session {peer_session:016x}, expected {session_id:016x}"
+        )));
     }
+    Ok(())
+}
+
+/// A dialer waiting for the peer to dial us again.
+fn accept_dialer<IO: Send + 'static>(
+    peer_id: u32,
+    incoming: mpsc::UnboundedReceiver<IO>,
+) -> Dialer<IO> {
+    let incoming = Arc::new(TokioMutex::new(incoming));
+    Arc::new(move || {
+        let incoming = incoming.clone();
+        Box::pin(async move {
+            let mut incoming = incoming.lock().await;
+            tokio::time::timeout(RECONNECT_TIMEOUT, incoming.recv())
+                .await
+                .map_err(|_| MPCNetError::Timeout { party: peer_id })?
+                .ok_or_else(|| MPCNetError::Aborted {
+                    party: peer_id,
+                    reason: "No longer accepting connections".to_string(),
+                })
+        })
+    })
+}
+
+/// Keeps accepting connections once all peers are connected, handing them to the peer that
+/// reconnects.
+fn spawn_acceptor<T: Transport>(
+    endpoint: Arc<T::Endpoint>,
+    incoming: HashMap<u32, mpsc::UnboundedSender<T::Conn>>,
+) -> AbortHandle {
+    tokio::spawn(async move {
+        loop {
+            let mut stream = match T::accept(&endpoint).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    trace!("Error accepting connection: {err:?}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let Ok(peer_id) = stream.read_u32().await else {
+                continue;
+            };
+            match incoming.get(&peer_id) {
+                Some(sender) => {
+                    let _ = sender.send(stream);
+                }
+                None => trace!("Unexpected connection from party {peer_id}"),
+            }
+        }
+    })
+    .abort_handle()
 }
 
 #[derive(Default, Debug)]
-pub struct MPCNetConnection<IO: AsyncRead + AsyncWrite + Unpin> {
+pub struct MPCNetConnection<T: Transport = Tcp> {
     pub id: u32,
-    pub listener: Option<TcpListener>,
-    pub peers: HashMap<u32, Peer<IO>>,
+    pub listener: Option<T::Endpoint>,
+    pub peers: HashMap<u32, Peer<T>>,
     pub n_parties: usize,
     pub upload: AtomicUsize,
     pub download: AtomicUsize,
+    /// Like `upload` and `download`, including framing.
+    pub wire_upload: AtomicUsize,
+    pub wire_download: AtomicUsize,
+    pub comm: CommTracker,
+    /// The wire options of this party, announced to the peers when connecting.
+    pub wire: WireConfig,
+    /// The encoding negotiated with all peers, see [`crate::wire`].
+    pub point_encoding: PointEncoding,
+    /// Whether messages carry envelopes, negotiated with all peers, see [`crate::envelope`].
+    pub envelopes: bool,
+    /// The peers and session handed out by the coordinator, see [`crate::rendezvous`].
+    pub session: Option<PeerTable>,
+    /// The task accepting peers that reconnect.
+    acceptor: Option<AbortHandle>,
+}
+
+impl<T: Transport> Drop for MPCNetConnection<T> {
+    fn drop(&mut self) {
+        if let Some(acceptor) = self.acceptor.take() {
+            acceptor.abort();
+        }
+    }
 }
 
-impl MPCNetConnection<TcpStream> {
+impl<T: Transport> MPCNetConnection<T> {
     pub fn init_from_path(path: &PathBuf, id: u32) -> Self {
         let mut this = MPCNetConnection {
             id: 0 as u32,
@@ -114,6 +672,14 @@ impl MPCNetConnection<TcpStream> {
             n_parties: 0,
             upload: AtomicUsize::new(0),
             download: AtomicUsize::new(0),
+            wire_upload: AtomicUsize::new(0),
+            wire_download: AtomicUsize::new(0),
+            comm: CommTracker::new(),
+            wire: WireConfig::default(),
+            point_encoding: PointEncoding::default(),
+            envelopes: false,
+            session: None,
+            acceptor: None,
         };
         let f = BufReader::new(File::open(path).expect("host configuration path"));
         let mut peer_id = 0;
@@ -121,13 +687,14 @@ impl MPCNetConnection<TcpStream> {
             let line = line.unwrap();
             let trimmed = line.trim();
             if trimmed.len() > 0 {
-                let addr: SocketAddr = trimmed
-                    .parse()
-                    .unwrap_or_else(|e| panic!("bad socket address: {}:\n{}", trimmed, e));
+                let addr = T::parse_addr(trimmed)
+                    .unwrap_or_else(|e| panic!("bad address: {}:\n{}", trimmed, e));
                 let peer = Peer {
                     id: peer_id,
                     listen_addr: addr,
                     streams: None,
+                    wire: None,
+                    link: None,
                 };
                 this.peers.insert(peer_id, peer);
                 peer_id += 1;
@@ -139,28 +706,74 @@ impl MPCNetConnection<TcpStream> {
         this
     }
 
+    /// Like [`MPCNetConnection::init_from_path`], learning the peers from the coordinator at
+    /// `coordinator`, see [`crate::rendezvous`]. Listens at `listen_addr`, so only
+    /// [`MPCNetConnection::connect_to_all`] is left to call.
+    pub async fn init_from_coordinator(
+        coordinator: SocketAddr,
+        id: u32,
+        listen_addr: T::Addr,
+        public_key: Vec<u8>,
+    ) -> Result<Self, MPCNetError> {
+        let listener = T::bind(listen_addr).await?;
+        let entry = PeerEntry {
+            id,
+            addr: T::format_addr(&T::local_addr(&listener)?),
+            public_key,
+        };
+        let table = rendezvous(coordinator, &entry).await?;
+        let mut peers = HashMap::new();
+        for peer in &table.peers {
+            let peer = Peer {
+                id: peer.id,
+                listen_addr: T::parse_addr(&peer.addr)?,
+                streams: None,
+                wire: None,
+                link: None,
+            };
+            peers.insert(peer.id, peer);
+        }
+        trace!("Joined session {:016x}", table.session_id);
+        Ok(MPCNetConnection {
+            id,
+            listener: Some(listener),
+            n_parties: peers.len(),
+            peers,
+            upload: AtomicUsize::new(0),
+            download: AtomicUsize::new(0),
+            wire_upload: AtomicUsize::new(0),
+            wire_download: AtomicUsize::new(0),
+            comm: CommTracker::new(),
+            wire: WireConfig::default(),
+            point_encoding: PointEncoding::default(),
+            envelopes: false,
+            session: Some(table),
+            acceptor: None,
+        })
+    }
+
     pub async fn listen(&mut self) -> Result<(), MPCNetError> {
-        let listen_addr = self.peers.get(&self.id).unwrap().listen_addr;
-        trace!("Listening on {listen_addr}");
-        self.listener = Some(
-            TcpListener::bind(listen_addr)
-                .await
-                .unwrap(),
-        );
+        let listen_addr = self.peers.get(&self.id).unwrap().listen_addr.clone();
+        trace!("Listening on {listen_addr:?}");
+        self.listener = Some(T::bind(listen_addr).await?);
         Ok(())
     }
 
     pub async fn connect_to_all(&mut self) -> Result<(), MPCNetError> {
         let n_minus_1 = self.n_parties() - 1;
         let my_id = self.id;
+        let wire = self.wire;
+        // Parties of different sessions must not talk to each other, e.g. on reused addresses.
+        let session_id = self.session.as_ref().map_or(0, |table| table.session_id);
 
         let peer_addrs = self
             .peers
             .iter()
-            .map(|p| (*p.0, p.1.listen_addr))
+            .map(|p| (*p.0, p.1.listen_addr.clone()))
             .collect::<HashMap<_, _>>();
 
-        let listener = self.listener.take().expect("TcpListener is None");
+        let listener = Arc::new(self.listener.take().expect("listener is None"));
+        let server_listener = listener.clone();
         let new_peers = Arc::new(Mutex::new(self.peers.clone()));
         let new_peers_server = new_peers.clone();
         let new_peers_client = new_peers.clone();
@@ -174,22 +787,37 @@ impl MPCNetConnection<TcpStream> {
         let outbound_connections_i_will_make = n_minus_1 - (my_id as usize);
         let inbound_connections_i_will_make = my_id as usize;
 
+        // Peers with a lower id dial us, again when reconnecting.
+        let (incoming, mut reconnects): (HashMap<_, _>, HashMap<_, _>) = (0..my_id)
+            .map(|peer_id| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                ((peer_id, sender), (peer_id, receiver))
+            })
+            .unzip();
+
         let server_task = async move {
             for _ in 0..inbound_connections_i_will_make {
-                let (mut stream, _peer_addr) = listener.accept().await.map_err(|err| {
-                    MPCNetError::Generic(format!("Error accepting connection: {err:?}"))
-                })?;
+                let mut stream = T::accept(&server_listener).await?;
 
                 let peer_id = stream.read_u32().await?;
+                let peer_wire = WireConfig::read(&mut stream).await?;
+                wire.write(&mut stream).await?;
+                check_session(&mut stream, peer_id, session_id).await?;
                 // Now, multiplex the stream
-                let muxed = multiplex_stream(MULTIPLEXED_STREAMS, true, stream).await?;
-                new_peers_server.lock().get_mut(&peer_id).unwrap().streams = Some(muxed);
+                let (muxed, worker) = split_channels::<T>(stream, true).await?;
+                let dialer = reconnects.remove(&peer_id).map(|incoming| accept_dialer(peer_id, incoming));
+                let mut peers = new_peers_server.lock();
+                let peer = peers.get_mut(&peer_id).unwrap();
+                peer.streams = Some(muxed);
+                peer.wire = Some(peer_wire);
+                peer.link = Some(Arc::new(Link::new(true, dialer, worker)));
                 trace!("{my_id} connected to peer {peer_id}")
             }
 
             Ok::<_, MPCNetError>(())
         };
 
+        let client_listener = listener.clone();
         let client_task = async move {
             // Wait some time for the server tasks to boot up
             tokio::time::sleep(Duration::from_millis(200)).await;
@@ -199,31 +827,33 @@ impl MPCNetConnection<TcpStream> {
                 // If I am 1, I will connect to 2
                 // If I am 2, I will connect to no one (server will make the connections)
                 let next_peer_to_connect_to = my_id + conns_made as u32 + 1;
-                let peer_listen_addr = peer_addrs.get(&next_peer_to_connect_to).unwrap();
-                let mut stream = {
-                    let mut res = Err(io::Error::new(io::ErrorKind::Other, "Initial error"));
-                    for _ in 0..30 {
-                        res = TcpStream::connect(peer_listen_addr).await;
-                        if res.is_ok() {
-                            // trace!("Connected to peer {next_peer_to_connect_to}");
-                            break;
-                        }
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                    }
-                    res.map_err(|err| {
-                        MPCNetError::Generic(format!(
-                            "Error connecting to peer {next_peer_to_connect_to}: {err:?}"
-                        ))
-                    })
-                }?;
-                stream.write_u32(my_id).await.unwrap();
-
-                let muxed = multiplex_stream(MULTIPLEXED_STREAMS, false, stream).await?;
-                new_peers_client
-                    .lock()
-                    .get_mut(&next_peer_to_connect_to)
-                    .unwrap()
-                    .streams = Some(muxed);
+                let peer_listen_addr = peer_addrs.get(&next_peer_to_connect_to).unwrap().clone();
+                let endpoint = client_listener.clone();
+                let mut stream = dial::<T>(
+                    endpoint.clone(),
+                    my_id,
+                    next_peer_to_connect_to,
+                    peer_listen_addr.clone(),
+                )
+                .await?;
+                wire.write(&mut stream).await?;
+                let peer_wire = WireConfig::read(&mut stream).await?;
+                check_session(&mut stream, next_peer_to_connect_to, session_id).await?;
+
+                let (muxed, worker) = split_channels::<T>(stream, false).await?;
+                let dialer: Dialer<T::Conn> = Arc::new(move || {
+                    Box::pin(dial::<T>(
+                        endpoint.clone(),
+                        my_id,
+                        next_peer_to_connect_to,
+                        peer_listen_addr.clone(),
+                    ))
+                });
+                let mut peers = new_peers_client.lock();
+                let peer = peers.get_mut(&next_peer_to_connect_to).unwrap();
+                peer.streams = Some(muxed);
+                peer.wire = Some(peer_wire);
+                peer.link = Some(Arc::new(Link::new(false, Some(dialer), worker)));
                 trace!("{my_id} connected to peer {next_peer_to_connect_to}")
             }
 
@@ -233,7 +863,16 @@ impl MPCNetConnection<TcpStream> {
         trace!("Awaiting on client and server task to finish");
 
         tokio::try_join!(server_task, client_task)?;
+        self.acceptor = Some(spawn_acceptor::<T>(listener, incoming));
         self.peers = Arc::try_unwrap(new_peers).unwrap().into_inner();
+        let configs = self
+            .peers
+            .iter()
+            .filter(|(id, _)| **id != my_id)
+            .filter_map(|(_, peer)| peer.wire.as_ref())
+            .chain([&self.wire]);
+        self.point_encoding = WireConfig::negotiate_point_encoding(configs.clone());
+        self.envelopes = WireConfig::negotiate_envelopes(configs);
 
         trace!("All connected");
 
@@ -253,8 +892,8 @@ impl MPCNetConnection<TcpStream> {
             }
 
             if peer.1.streams.is_none() {
-                return Err(MPCNetError::Generic(format!(
-                    "Peer {} has no stream",
+                return Err(MPCNetError::Config(format!(
+                    "Not connected to party {}",
                     peer.0
                 )));
             }
@@ -265,18 +904,36 @@ impl MPCNetConnection<TcpStream> {
     }
 }
 
-pub struct LocalTestNet {
-    nodes: HashMap<usize, MPCNetConnection<TcpStream>>,
+pub struct LocalTestNet<T: Transport = Tcp> {
+    nodes: HashMap<usize, MPCNetConnection<T>>,
 }
 
 impl LocalTestNet {
     pub async fn new_local_testnet(n_parties: usize) -> Result<Self, MPCNetError> {
+        Self::new_local_testnet_with_wire(n_parties, WireConfig::default()).await
+    }
+
+    /// Like [`LocalTestNet::new_local_testnet`], every party using the given wire options.
+    pub async fn new_local_testnet_with_wire(
+        n_parties: usize,
+        wire: WireConfig,
+    ) -> Result<Self, MPCNetError> {
+        Self::new_local_testnet_with_transport(n_parties, wire).await
+    }
+}
+
+impl<T: Transport> LocalTestNet<T> {
+    /// Like [`LocalTestNet::new_local_testnet_with_wire`], the parties connected over `T`.
+    pub async fn new_local_testnet_with_transport(
+        n_parties: usize,
+        wire: WireConfig,
+    ) -> Result<Self, MPCNetError> {
         // Step 1: Generate all the Listeners for each node
         let mut listeners = HashMap::new();
         let mut listen_addrs = HashMap::new();
         for party_id in 0..n_parties {
-            let listener = TcpListener::bind("127.0.0.1:0").await?;
-            listen_addrs.insert(party_id, listener.local_addr()?);
+            let listener = T::bind(T::loopback_addr()).await?;
+            listen_addrs.insert(party_id, T::local_addr(&listener)?);
             listeners.insert(party_id, listener);
         }
 
@@ -290,16 +947,26 @@ impl LocalTestNet {
                 n_parties,
                 upload: AtomicUsize::new(0),
                 download: AtomicUsize::new(0),
+                wire_upload: AtomicUsize::new(0),
+                wire_download: AtomicUsize::new(0),
+                comm: CommTracker::new(),
+                wire,
+                point_encoding: PointEncoding::default(),
+                envelopes: false,
+                session: None,
+                acceptor: None,
             };
             for peer_id in 0..n_parties {
                 // NOTE: this is the listen addr
-                let peer_addr = listen_addrs.get(&peer_id).copied().unwrap();
+                let peer_addr = listen_addrs.get(&peer_id).cloned().unwrap();
                 connections.peers.insert(
                     peer_id as u32,
                     Peer {
                         id: peer_id as u32,
                         listen_addr: peer_addr,
                         streams: None,
+                        wire: None,
+                        link: None,
                     },
                 );
             }
@@ -311,10 +978,13 @@ impl LocalTestNet {
         trace!("Now running init");
         let futures = FuturesUnordered::new();
         for (peer_id, mut connections) in nodes.into_iter() {
-            futures.push(Box::pin(async move {
-                connections.connect_to_all().await?;
-                Ok::<_, MPCNetError>((peer_id, connections))
-            }));
+            futures.push(profiled(
+                async move {
+                    connections.connect_to_all().await?;
+                    Ok::<_, MPCNetError>((peer_id, connections))
+                },
+                Some(peer_id as u32),
+            ));
         }
 
         let nodes = futures.try_collect().await?;
@@ -327,6 +997,7 @@ impl LocalTestNet {
     ///
     /// The provided `user_data` of type U is then given to each of these futures, by cloning it.
     /// So if you have a struct that you want to pass to each of these futures, you can do that.
+    /// Every party runs in [`profiled`], so its timers nest and are profiled on their own.
     pub async fn simulate_network_round<
         F: Future<Output = K> + Send,
         K: Send + Sync + 'static,
@@ -334,16 +1005,19 @@ impl LocalTestNet {
     >(
         self,
         user_data: U,
-        f: impl Fn(MPCNetConnection<TcpStream>, U) -> F + Send + Sync + Clone + 'static,
+        f: impl Fn(MPCNetConnection<T>, U) -> F + Send + Sync + Clone + 'static,
     ) -> Vec<K> {
         let mut futures = FuturesOrdered::new();
         let mut sorted_nodes = self.nodes.into_iter().collect::<Vec<_>>();
         sorted_nodes.sort_by(|a, b| a.0.cmp(&b.0));
-        for (_, connections) in sorted_nodes {
+        for (id, connections) in sorted_nodes {
             let next_f = f.clone();
             let next_user_data = user_data.clone();
             futures.push_back(Box::pin(async move {
-                let task = async move { next_f(connections, next_user_data).await };
+                let task = profiled(
+                    async move { next_f(connections, next_user_data).await },
+                    Some(id as u32),
+                );
                 let handle = tokio::task::spawn(task);
                 handle.await.unwrap()
             }));
@@ -352,17 +1026,17 @@ impl LocalTestNet {
     }
 
     /// Get the connection for a given party ID
-    pub fn get_connection(&self, party_id: usize) -> &MPCNetConnection<TcpStream> {
+    pub fn get_connection(&self, party_id: usize) -> &MPCNetConnection<T> {
         self.nodes.get(&party_id).unwrap()
     }
 
-    pub fn get_leader(&self) -> &MPCNetConnection<TcpStream> {
+    pub fn get_leader(&self) -> &MPCNetConnection<T> {
         self.get_connection(0)
     }
 }
 
 #[async_trait]
-impl<IO: AsyncRead + AsyncWrite + Unpin + Send> MPCNet for MPCNetConnection<IO> {
+impl<T: Transport> MPCNet for MPCNetConnection<T> {
     fn n_parties(&self) -> usize {
         self.n_parties
     }
@@ -384,18 +1058,34 @@ impl<IO: AsyncRead + AsyncWrite + Unpin + Send> MPCNet for MPCNetConnection<IO>
     fn add_comm(&self, up: usize, down: usize) {
         self.upload.fetch_add(up, Ordering::Relaxed);
         self.download.fetch_add(down, Ordering::Relaxed);
+        self.wire_upload.fetch_add(up, Ordering::Relaxed);
+        self.wire_download.fetch_add(down, Ordering::Relaxed);
+        self.comm.record_simulated(up, down);
+    }
+
+    fn get_wire_comm(&self) -> (usize, usize) {
+        (
+            self.wire_upload.load(Ordering::Relaxed),
+            self.wire_download.load(Ordering::Relaxed),
+        )
+    }
+
+    fn point_encoding(&self) -> PointEncoding {
+        self.point_encoding
+    }
+
+    fn comm_tracker(&self) -> Option<&CommTracker> {
+        Some(&self.comm)
     }
 
     async fn recv_from(&self, id: u32, sid: MultiplexedStreamID) -> Result<Bytes, MPCNetError> {
         let peer = self
             .peers
             .get(&id)
-            .ok_or_else(|| MPCNetError::Generic(format!("Peer {} not found", id)))?;
-        let result = recv_stream(peer.streams.as_ref(), sid).await;
-        if let Ok(bytes) = &result {
-            self.download.fetch_add(bytes.len(), Ordering::Relaxed);
-        }
-        result
+            .ok_or(MPCNetError::BadInput { err: "recv_from called with an unknown party", })?;
+        let (bytes, wire_len) = recv_stream(peer, sid, self.wire, self.envelope(id)).await?;
+        self.record_recv(id, sid, bytes.len(), wire_len);
+        Ok(bytes)
     }
 
     async fn send_to(
@@ -407,50 +1097,396 @@ impl<IO: AsyncRead + AsyncWrite + Unpin + Send> MPCNet for MPCNetConnection<IO>
         let peer = self
             .peers
             .get(&id)
-            .ok_or_else(|| MPCNetError::Generic(format!("Peer {} not found", id)))?;
-        let len = bytes.len();
-        let result = send_stream(peer.streams.as_ref(), bytes, sid).await;
-        if let Ok(_) = &result {
-            self.upload.fetch_add(len, Ordering::Relaxed);
+            .ok_or(MPCNetError::BadInput { err: "send_to called with an unknown party", })?;
+        let (len, wire_len) = send_stream(peer, bytes, sid, self.wire, self.envelope(self.id)).await?;
+        self.record_send(id, sid, len, wire_len);
+        Ok(())
+    }
+
+    async fn send_chunks_to(
+        &self,
+        id: u32,
+        chunks: impl Iterator<Item = Bytes> + Send,
+        sid: MultiplexedStreamID,
+    ) -> Result<(), MPCNetError> {
+        let peer = self
+            .peers
+            .get(&id)
+            .ok_or(MPCNetError::BadInput { err: "send_chunks_to called with an unknown party", })?;
+        let envelope = self.envelope(self.id);
+        let (len, wire_len) = send_chunks_stream(peer, chunks, sid, self.wire, envelope).await?;
+        self.record_send(id, sid, len, wire_len);
+        Ok(())
+    }
+
+    async fn recv_chunks_from(
+        &self,
+        id: u32,
+        sid: MultiplexedStreamID,
+        on_chunk: impl FnMut(Bytes) -> Result<(), MPCNetError> + Send,
+    ) -> Result<(), MPCNetError> {
+        let peer = self
+            .peers
+            .get(&id)
+            .ok_or(MPCNetError::BadInput { err: "recv_chunks_from called with an unknown party", })?;
+        let expected = self.envelope(id);
+        let (len, wire_len) = recv_chunks_stream(peer, sid, on_chunk, self.wire, expected).await?;
+        self.record_recv(id, sid, len, wire_len);
+        Ok(())
+    }
+}
+
+impl<T: Transport> MPCNetConnection<T> {
+    /// Close the transport to a peer, as a network failure would. The connection is restored by
+    /// the next operation on it.
+    pub async fn disconnect(&self, peer: u32) {
+        if let Some(link) = self.peers.get(&peer).and_then(|p| p.link.as_ref()) {
+            link.close().await;
         }
-        result
+    }
+
+    /// The envelope of a message from `sender` in the current step, numbered when it is sent or
+    /// received.
+    fn envelope(&self, sender: u32) -> Option<Envelope> {
+        self.envelopes.then(|| Envelope {
+            step: self.comm.current_path(),
+            round: 0,
+            sender,
+            payload: envelope::payload_type().to_string(),
+        })
+    }
+
+    fn record_send(&self, id: u32, sid: MultiplexedStreamID, len: usize, wire_len: usize) {
+        self.upload.fetch_add(len, Ordering::Relaxed);
+        self.wire_upload.fetch_add(wire_len, Ordering::Relaxed);
+        self.comm.record_send(id, sid, len, wire_len);
+        profile::record_comm(len, 0);
+    }
+
+    fn record_recv(&self, id: u32, sid: MultiplexedStreamID, len: usize, wire_len: usize) {
+        self.download.fetch_add(len, Ordering::Relaxed);
+        self.wire_download.fetch_add(wire_len, Ordering::Relaxed);
+        self.comm.record_recv(id, sid, len, wire_len);
+        profile::record_comm(0, len);
     }
 }
 
-async fn send_stream<T: AsyncRead + AsyncWrite + Unpin>(
-    stream: Option<&Vec<TokioMutex<WrappedStream<T>>>>,
+async fn send_stream<T: Transport>(
+    peer: &Peer<T>,
     bytes: Bytes,
     sid: MultiplexedStreamID,
-) -> Result<(), MPCNetError> {
-    if let Some(stream) = stream.and_then(|r| r.get(sid as usize)) {
-        Ok(stream.lock().await.send(bytes).await?)
+    wire: WireConfig,
+    envelope: Option<Envelope>,
+) -> Result<(usize, usize), MPCNetError> {
+    send_chunks_stream(peer, std::iter::once(bytes), sid, wire, envelope).await
+}
+
+/// Splits a message given as chunks into frames of at most [`CHUNK_SIZE`] bytes, the last one
+/// marked. Empty chunks are skipped, an empty message is a single empty frame.
+fn split_message(chunks: impl Iterator<Item = Bytes>) -> impl Iterator<Item = Chunk> {
+    let mut chunks = chunks.peekable();
+    let mut current: Option<Bytes> = None;
+    let mut done = false;
+    std::iter::from_fn(move || loop {
+        if done {
+            return None;
+        }
+        let Some(data) = current.as_mut() else {
+            current = chunks.next();
+            if current.is_none() {
+                done = true;
+                return Some(Chunk {
+                    last: true,
+                    batch: false,
+                    data: Bytes::new(),
+                });
+            }
+            continue;
+        };
+        if data.len() > CHUNK_SIZE {
+            let data = data.split_to(CHUNK_SIZE);
+            return Some(Chunk { last: false, batch: false, data });
+        }
+        let data = current.take().unwrap();
+        if chunks.peek().is_none() {
+            done = true;
+            return Some(Chunk { last: true, batch: false, data });
+        }
+        if !data.is_empty() {
+            return Some(Chunk { last: false, batch: false, data });
+        }
+    })
+}
+
+/// Sends the chunks as a single message, split into frames of at most [`CHUNK_SIZE`] bytes.
+/// The stream stays locked until the whole message is sent, and every frame waits for room in the
+/// replay buffer and for the multiplexer to accept it, so only a bounded amount of data is
+/// buffered.
+///
+/// With coalescing, small frames are appended to a batch, sent as a single frame once the calling
+/// task yields, so that the messages sent in a burst, e.g. within a round, share a frame.
+///
+/// The envelope, if any, is numbered and sent as a frame of its own ahead of the message.
+///
+/// Returns the length of the message, without and with framing.
+async fn send_chunks_stream<T: Transport>(
+    peer: &Peer<T>,
+    chunks: impl Iterator<Item = Bytes> + Send,
+    sid: MultiplexedStreamID,
+    wire: WireConfig,
+    envelope: Option<Envelope>,
+) -> Result<(usize, usize), MPCNetError> {
+    let stream = peer.stream(sid)?;
+    let _sending = stream.sending.lock().await;
+    let round = stream.messages_sent.fetch_add(1, Ordering::AcqRel);
+    let header = envelope.map(|envelope| Envelope { round, ..envelope }.encode());
+    let header_len = header.as_ref().map_or(0, |h| h.len());
+    let mut len = 0;
+    let mut wire_len = 0;
+    for chunk in split_message(header.into_iter().chain(chunks)) {
+        len += chunk.data.len();
+        wait_for_room(peer, stream, sid, chunk.data.len(), wire).await?;
+        if wire.coalesce && chunk.data.len() <= COALESCE_LIMIT {
+            wire_len += stream.push_batched(chunk);
+            if stream.batch.lock().len() >= COALESCE_LIMIT {
+                feed_frames(peer, stream, None, wire).await?;
+            }
+            continue;
+        }
+        wire_len += chunk.data.len() + FRAME_OVERHEAD;
+        feed_frames(peer, stream, Some(chunk), wire).await?;
+    }
+    if wire.coalesce {
+        schedule_flush(stream);
     } else {
-        Err(MPCNetError::Generic("Stream is None".to_string()))
+        flush_stream(peer, stream, wire).await?;
+    }
+    Ok((len - header_len, wire_len))
+}
+
+/// Feeds the pending batch, if any, and `chunk` to the stream.
+async fn feed_frames<T: Transport>(
+    peer: &Peer<T>,
+    stream: &WrappedChannel<T>,
+    chunk: Option<Chunk>,
+    wire: WireConfig,
+) -> Result<(), MPCNetError> {
+    let mut tx = stream.tx.lock().await;
+    let generation = peer.generation();
+    let frames: Vec<Frame> = stream
+        .take_batch()
+        .into_iter()
+        .chain(chunk.map(|chunk| stream.number(chunk)))
+        .collect();
+    for frame in frames {
+        if let Err(err) = tx.feed(frame).await {
+            // The frames are resent from the replay buffer.
+            drop(tx);
+            peer.reconnect(generation, wire, err.into()).await?;
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn flush_stream<T: Transport>(
+    peer: &Peer<T>,
+    stream: &WrappedChannel<T>,
+    wire: WireConfig,
+) -> Result<(), MPCNetError> {
+    feed_frames(peer, stream, None, wire).await?;
+    let mut tx = stream.tx.lock().await;
+    let generation = peer.generation();
+    if let Err(err) = tx.flush().await {
+        drop(tx);
+        peer.reconnect(generation, wire, err.into()).await?;
     }
+    Ok(())
 }
 
-async fn recv_stream<T: AsyncRead + AsyncWrite + Unpin>(
-    stream: Option<&Vec<TokioMutex<WrappedStream<T>>>>,
+/// Waits until a frame of `len` bytes fits into the replay buffer of the stream. Unless a receive
+/// on the stream is in progress and processes them, the acknowledgements are read here, and the
+/// data frames read along with them are kept for the next receive. Once a replay buffer's worth of
+/// them is kept, no more are read until a receive takes them.
+async fn wait_for_room<T: Transport>(
+    peer: &Peer<T>,
+    stream: &WrappedChannel<T>,
     sid: MultiplexedStreamID,
-) -> Result<Bytes, MPCNetError> {
-    if let Some(stream) = stream.and_then(|r| r.get(sid as usize)) {
-        Ok(stream
-            .lock()
-            .await
-            .next()
-            .await
-            .ok_or_else(|| MPCNetError::Generic("Stream died".to_string()))??
-            .freeze())
+    len: usize,
+    wire: WireConfig,
+) -> Result<(), MPCNetError> {
+    if stream.replay.lock().has_room(len) {
+        return Ok(());
+    }
+    // The peer cannot acknowledge frames that are still buffered.
+    flush_stream(peer, stream, wire).await?;
+    loop {
+        let acked = stream.acked.notified();
+        if stream.replay.lock().has_room(len) {
+            return Ok(());
+        }
+        if stream.stashed.load(Ordering::Acquire) >= REPLAY_LIMIT {
+            acked.await;
+            continue;
+        }
+        let Some(mut rx) = stream.try_lock_rx() else {
+            acked.await;
+            continue;
+        };
+        let generation = peer.generation();
+        let err = match rx.next().await {
+            Some(Ok(frame)) => {
+                if let Some(len) = stream.take(peer.id, sid, frame)? {
+                    acknowledge(stream, len);
+                }
+                continue;
+            }
+            Some(Err(err)) => err.into(),
+            None => io::Error::from(io::ErrorKind::UnexpectedEof).into(),
+        };
+        drop(rx);
+        peer.reconnect(generation, wire, err).await?;
+    }
+}
+
+/// Sends the pending batch and flushes the stream once the current task yields, unless a flush is
+/// already pending.
+fn schedule_flush<T: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    stream: &Arc<FramedChannel<T>>,
+) {
+    if stream.flush_scheduled.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let stream = stream.clone();
+    tokio::spawn(async move {
+        tokio::task::yield_now().await;
+        let mut tx = stream.tx.lock().await;
+        // Messages batched from now on need another flush.
+        stream.flush_scheduled.store(false, Ordering::Release);
+        if let Some(frame) = stream.take_batch() {
+            // A failure shows up on the next operation on the stream, which resends the frame.
+            if let Err(err) = tx.feed(frame).await {
+                trace!("Sending coalesced messages failed: {err}");
+                return;
+            }
+        }
+        if let Err(err) = tx.flush().await {
+            trace!("Flushing coalesced messages failed: {err}");
+        }
+    });
+}
+
+async fn recv_stream<T: Transport>(
+    peer: &Peer<T>,
+    sid: MultiplexedStreamID,
+    wire: WireConfig,
+    expected: Option<Envelope>,
+) -> Result<(Bytes, usize), MPCNetError> {
+    let mut chunks = Vec::new();
+    let on_chunk = |chunk| {
+        chunks.push(chunk);
+        Ok(())
+    };
+    let (_, wire_len) = recv_chunks_stream(peer, sid, on_chunk, wire, expected).await?;
+    let bytes = if chunks.len() == 1 {
+        chunks.pop().unwrap()
     } else {
-        Err(MPCNetError::Generic("Stream is None".to_string()))
+        chunks.concat().into()
+    };
+    Ok((bytes, wire_len))
+}
+
+/// Receives a message frame by frame, handing each frame to `on_chunk` as it arrives.
+/// With an `expected` envelope, the first frame must hold a matching one.
+///
+/// Returns the length of the message, without and with framing.
+async fn recv_chunks_stream<T: Transport>(
+    peer: &Peer<T>,
+    sid: MultiplexedStreamID,
+    mut on_chunk: impl FnMut(Bytes) -> Result<(), MPCNetError>,
+    wire: WireConfig,
+    mut expected_envelope: Option<Envelope>,
+) -> Result<(usize, usize), MPCNetError> {
+    let stream = peer.stream(sid)?;
+    let mut len = 0;
+    let mut header_len = 0;
+    let mut overhead = 0;
+    let mut numbered = false;
+    loop {
+        let mut rx = stream.lock_rx().await;
+        if !numbered {
+            numbered = true;
+            let round = stream.messages_received.fetch_add(1, Ordering::AcqRel);
+            if let Some(expected) = expected_envelope.as_mut() {
+                expected.round = round;
+            }
+        }
+        let generation = peer.generation();
+        let err = loop {
+            // Frames read before come first, they are acknowledged already.
+            let Some((chunk, wire_overhead)) = stream.unstash() else {
+                let frame = match rx.next().await {
+                    Some(Ok(frame)) => frame,
+                    Some(Err(err)) => break err.into(),
+                    None => break io::Error::from(io::ErrorKind::UnexpectedEof).into(),
+                };
+                if let Some(len) = stream.take(peer.id, sid, frame)? {
+                    acknowledge(stream, len);
+                }
+                continue;
+            };
+            overhead += wire_overhead;
+            if let Some(expected) = expected_envelope.take() {
+                header_len = chunk.data.len();
+                Envelope::decode(chunk.data, peer.id)?.check(&expected)?;
+            } else {
+                len += chunk.data.len();
+                on_chunk(chunk.data)?;
+            }
+            if chunk.last {
+                return Ok((len, len + header_len + overhead));
+            }
+        };
+        drop(rx);
+        peer.reconnect(generation, wire, err).await?;
     }
 }
 
+/// Acknowledges the frames received on a stream once enough data arrived without one. The
+/// acknowledgement is sent by a task of its own, so that it never waits for the stream while the
+/// caller holds its receiving half.
+fn acknowledge<T: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    stream: &Arc<FramedChannel<T>>,
+    len: usize,
+) {
+    if stream.unacked.fetch_add(len, Ordering::AcqRel) + len < ACK_INTERVAL
+        || stream.ack_scheduled.swap(true, Ordering::AcqRel)
+    {
+        return;
+    }
+    let stream = stream.clone();
+    tokio::spawn(async move {
+        let mut tx = stream.tx.lock().await;
+        // Frames received from now on need another acknowledgement.
+        stream.ack_scheduled.store(false, Ordering::Release);
+        if stream.unacked.swap(0, Ordering::AcqRel) == 0 {
+            // A data frame carried the acknowledgement meanwhile.
+            return;
+        }
+        let ack = stream.received();
+        // A failure shows up on the next operation on the stream.
+        let _ = tx.send(Frame { seq: 0, ack, chunk: None }).await;
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use crate::multi::{recv_stream, send_stream};
-    use crate::{LocalTestNet, MultiplexedStreamID};
+    use crate::{
+        LocalTestNet, MPCNet, MPCNetError, MultiplexedStreamID, WireConfig, MAX_CONCURRENT_SENDS,
+    };
     use std::collections::HashMap;
+    use tokio_util::bytes::Bytes;
 
     #[tokio::test]
     async fn test_multiplexing() {
@@ -472,7 +1508,8 @@ mod tests {
                         continue;
                     }
                     for sid in sids {
-                        send_stream(peer.streams.as_ref(), vec![my_id as u8].into(), sid)
+                        let bytes = vec![my_id as u8].into();
+                        send_stream(peer, bytes, sid, WireConfig::default(), None)
                             .await
                             .unwrap();
                     }
@@ -485,7 +1522,8 @@ mod tests {
                         continue;
                     }
                     for sid in sids {
-                        let recv_bytes = recv_stream(peer.streams.as_ref(), sid).await.unwrap();
+                        let (recv_bytes, _) =
+                            recv_stream(peer, sid, WireConfig::default(), None).await.unwrap();
                         let decoded = recv_bytes[0] as u32;
                         ids.entry(sid).or_default().push(decoded);
                     }
@@ -497,4 +1535,352 @@ mod tests {
             })
             .await;
     }
+
+    #[tokio::test]
+    async fn test_collectives() {
+        const N_PARTIES: usize = 8;
+        // Large enough to exceed the multiplexer's buffers, so sends must overlap with receives.
+        const LEN: usize = 1 << 20;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::One;
+                let me = net.party_id() as u8;
+
+                let out = (0..N_PARTIES as u8)
+                    .map(|to| Bytes::from(vec![me * 16 + to; LEN]))
+                    .collect();
+                let received = net.all_to_all(out, sid).await.unwrap();
+                for (from, bytes) in received.iter().enumerate() {
+                    assert_eq!(bytes.len(), LEN);
+                    assert!(bytes.iter().all(|b| *b == from as u8 * 16 + me));
+                }
+
+                let bytes = (me == 3).then(|| Bytes::from_static(b"hello"));
+                let received = net.broadcast_from(bytes, 3, sid).await.unwrap();
+                assert_eq!(received, Bytes::from_static(b"hello"));
+
+                let gathered = net.all_gather(&[me], sid).await.unwrap();
+                assert_eq!(
+                    gathered,
+                    (0..N_PARTIES as u8)
+                        .map(|i| Bytes::from(vec![i]))
+                        .collect::<Vec<_>>()
+                );
+
+                let out = (0..N_PARTIES as u8).map(|to| Bytes::from(vec![me, to])).collect();
+                let reduced = net
+                    .reduce_scatter(out, sid, |v| {
+                        Bytes::from(vec![v.iter().map(|b| b[0]).sum::<u8>(), v[0][1]])
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(reduced, Bytes::from(vec![(0..N_PARTIES as u8).sum::<u8>(), me]));
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_misuse() {
+        const N_PARTIES: usize = 3;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::One;
+                fn bad_input<T>(r: Result<T, MPCNetError>) {
+                    assert!(matches!(r, Err(MPCNetError::BadInput { .. })));
+                }
+                let out = || vec![Bytes::from_static(b"ab"); N_PARTIES];
+
+                // Only the sender may provide bytes, and it must provide one message per party.
+                if !net.is_leader() {
+                    bad_input(net.dynamic_worker_receive_or_leader_send(Some(out()), 0, sid).await);
+                    bad_input(net.worker_receive_or_leader_send(Some(out()), sid).await);
+                } else {
+                    let mut uneven = out();
+                    uneven[1] = Bytes::from_static(b"a");
+                    bad_input(net.dynamic_worker_receive_or_leader_send(Some(uneven), 0, sid).await);
+                    bad_input(net.worker_receive_or_leader_send(Some(out()[1..].to_vec()), sid).await);
+                    bad_input(net.worker_receive_or_leader_send(None, sid).await);
+                }
+                bad_input(net.all_to_all(out()[1..].to_vec(), sid).await);
+
+                let unknown = N_PARTIES as u32;
+                bad_input(net.broadcast_from(None, unknown, sid).await);
+                bad_input(net.dynamic_worker_receive_or_leader_send(None, unknown, sid).await);
+                bad_input(net.dynamic_worker_send_or_leader_receive(&[], unknown, sid).await);
+                bad_input(net.recv_from(unknown, sid).await);
+
+                // Nothing was sent, the streams are still usable.
+                let r = net.leader_compute(&[1], sid, |v| v).await.unwrap();
+                assert_eq!(r, &[1][..]);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_large_messages() {
+        use super::CHUNK_SIZE;
+        let testnet = LocalTestNet::new_local_testnet(2).await.unwrap();
+        let large: Bytes = (0..2 * CHUNK_SIZE + 7).map(|i| i as u8).collect::<Vec<_>>().into();
+
+        testnet
+            .simulate_network_round(large, |net, large| async move {
+                let sid = MultiplexedStreamID::One;
+                let chunks = [0, 5, CHUNK_SIZE + 3, 0].map(|len| Bytes::from(vec![1u8; len]));
+                if net.party_id() == 0 {
+                    net.send_to(1, large.clone(), sid).await.unwrap();
+                    net.send_chunks_to(1, chunks.clone().into_iter(), sid).await.unwrap();
+                    net.send_chunks_to(1, std::iter::empty(), sid).await.unwrap();
+                    net.send_to(1, large, sid).await.unwrap();
+                } else {
+                    let (_, download) = net.get_comm();
+                    assert_eq!(net.recv_from(0, sid).await.unwrap(), large);
+                    assert_eq!(net.recv_from(0, sid).await.unwrap(), chunks.concat());
+                    assert_eq!(net.recv_from(0, sid).await.unwrap(), Bytes::new());
+
+                    let mut received = Vec::new();
+                    net.recv_chunks_from(0, sid, |chunk| {
+                        assert!(chunk.len() <= CHUNK_SIZE);
+                        received.push(chunk);
+                        Ok(())
+                    })
+                    .await
+                    .unwrap();
+                    assert_eq!(received.len(), 3);
+                    assert_eq!(received.concat(), large);
+                    assert_eq!(net.get_comm().1 - download, 2 * large.len() + CHUNK_SIZE + 8);
+                }
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_coalescing() {
+        use super::{BATCH_ENTRY_OVERHEAD, FRAME_OVERHEAD};
+        use crate::{PointEncoding, WireConfig};
+        const N_PARTIES: usize = 3;
+        const MESSAGES: usize = 20;
+        let wire = WireConfig::default()
+            .with_coalescing(true)
+            .with_point_encoding(PointEncoding::Uncompressed);
+        let testnet = LocalTestNet::new_local_testnet_with_wire(N_PARTIES, wire)
+            .await
+            .unwrap();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                assert_eq!(net.point_encoding(), PointEncoding::Uncompressed);
+                let sid = MultiplexedStreamID::One;
+                let (up, down) = net.get_wire_comm();
+                // The messages of a burst go out as a single frame.
+                let burst = MESSAGES * (1 + BATCH_ENTRY_OVERHEAD) + FRAME_OVERHEAD;
+                if net.is_leader() {
+                    for id in 1..N_PARTIES as u32 {
+                        for i in 0..MESSAGES {
+                            let received = net.recv_from(id, sid).await.unwrap();
+                            assert_eq!(received, Bytes::from(vec![i as u8]));
+                        }
+                    }
+                    assert_eq!(net.get_wire_comm().1 - down, (N_PARTIES - 1) * burst);
+                } else {
+                    for i in 0..MESSAGES {
+                        net.send_to(0, Bytes::from(vec![i as u8]), sid).await.unwrap();
+                    }
+                    assert_eq!(net.get_wire_comm().0 - up, burst);
+                }
+                assert!(burst < MESSAGES * (1 + FRAME_OVERHEAD));
+
+                // Messages of later rounds are coalesced alike.
+                for i in 0..MESSAGES {
+                    let received = net.leader_compute(&[i as u8], sid, |v| v).await.unwrap();
+                    assert_eq!(received, Bytes::from(vec![i as u8]));
+                }
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_reconnect() {
+        use super::CHUNK_SIZE;
+        const N_PARTIES: usize = 3;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+        let large: Bytes = (0..CHUNK_SIZE + 7).map(|i| i as u8).collect::<Vec<_>>().into();
+
+        testnet
+            .simulate_network_round(large, |net, large| async move {
+                let sid = MultiplexedStreamID::One;
+                for i in 0..10u8 {
+                    // Both the dialing and the accepting side of a link drop it.
+                    match (net.party_id(), i % 3) {
+                        (0, 0) => net.disconnect(1).await,
+                        (2, 1) => net.disconnect(0).await,
+                        _ => {}
+                    }
+                    let received = net.leader_compute(&[i], sid, |v| v).await.unwrap();
+                    assert_eq!(received, Bytes::from(vec![i]));
+                }
+
+                // Drop the connection while messages are in flight.
+                match net.party_id() {
+                    0 => {
+                        net.send_to(1, large.clone(), sid).await.unwrap();
+                        net.disconnect(1).await;
+                        net.send_to(1, large.clone(), sid).await.unwrap();
+                        assert_eq!(net.recv_from(1, sid).await.unwrap(), large);
+                    }
+                    1 => {
+                        assert_eq!(net.recv_from(0, sid).await.unwrap(), large);
+                        assert_eq!(net.recv_from(0, sid).await.unwrap(), large);
+                        net.send_to(0, large.clone(), sid).await.unwrap();
+                    }
+                    _ => {}
+                }
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_replay_backpressure() {
+        use crate::session::REPLAY_LIMIT;
+        const MESSAGES: usize = 48;
+        const LEN: usize = 1 << 20;
+        let testnet = LocalTestNet::new_local_testnet(2).await.unwrap();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::One;
+                if net.is_leader() {
+                    // Only sending, so the acknowledgements are read while waiting for room.
+                    let stream = net.peers[&1].stream(sid).unwrap().clone();
+                    for i in 0..MESSAGES {
+                        if i == MESSAGES / 2 {
+                            net.disconnect(1).await;
+                        }
+                        net.send_to(1, Bytes::from(vec![i as u8; LEN]), sid).await.unwrap();
+                        assert!(stream.replay_bytes() <= REPLAY_LIMIT);
+                    }
+                } else {
+                    // Start late, so that the sender has to wait.
+                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                    for i in 0..MESSAGES {
+                        assert_eq!(net.recv_from(0, sid).await.unwrap(), vec![i as u8; LEN]);
+                    }
+                }
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_stash_limit() {
+        use crate::session::REPLAY_LIMIT;
+        use std::sync::atomic::Ordering;
+        const MESSAGES: usize = 48;
+        const LEN: usize = 1 << 20;
+        let testnet = LocalTestNet::new_local_testnet(2).await.unwrap();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::One;
+                let peer = 1 - net.party_id();
+                let stream = net.peers[&peer].stream(sid).unwrap().clone();
+                // Both parties wait for room while the other one sends, and read its frames.
+                let sends = async {
+                    for i in 0..MESSAGES {
+                        net.send_to(peer, Bytes::from(vec![i as u8; LEN]), sid).await.unwrap();
+                        assert!(stream.stashed.load(Ordering::Acquire) <= REPLAY_LIMIT + LEN);
+                    }
+                };
+                let recvs = async {
+                    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+                    for i in 0..MESSAGES {
+                        assert_eq!(net.recv_from(peer, sid).await.unwrap(), vec![i as u8; LEN]);
+                    }
+                };
+                futures::join!(sends, recvs);
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_tree_aggregation() {
+        // Not a power of two, so that some subtrees are incomplete.
+        const N_PARTIES: usize = 11;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::Two;
+                let me = net.party_id() as u8;
+                // Concatenation is associative but not commutative, so this also checks the order.
+                let concat = |a: Bytes, b: Bytes| Ok([a, b].concat().into());
+
+                let received = net
+                    .tree_worker_send_or_leader_receive(&[me], sid, concat)
+                    .await
+                    .unwrap();
+                if net.is_leader() {
+                    assert_eq!(received.unwrap(), (0..N_PARTIES as u8).collect::<Vec<_>>());
+                } else {
+                    assert!(received.is_none());
+                }
+
+                let result = net
+                    .tree_leader_compute(&[me], sid, concat, |b| b.iter().rev().copied().collect())
+                    .await
+                    .unwrap();
+                assert_eq!(result, (0..N_PARTIES as u8).rev().collect::<Vec<_>>());
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_fan_out() {
+        // More parties than sends in flight, so the fan-out runs in several batches.
+        const N_PARTIES: usize = MAX_CONCURRENT_SENDS + 3;
+        let testnet = LocalTestNet::new_local_testnet(N_PARTIES).await.unwrap();
+
+        let comm = testnet
+            .simulate_network_round((), |net, _| async move {
+                let sid = MultiplexedStreamID::Zero;
+                let me = net.party_id() as usize;
+                let (up, down) = net.get_comm();
+                let out = || {
+                    (0..N_PARTIES)
+                        .map(|to| Bytes::from(vec![to as u8; to + 1]))
+                        .collect::<Vec<_>>()
+                };
+
+                let bytes_out = net.is_leader().then(out);
+                let received = net.worker_receive_or_leader_send(bytes_out, sid).await.unwrap();
+                assert_eq!(received, Bytes::from(vec![me as u8; me + 1]));
+
+                let bytes_out =
+                    (me == 5).then(|| (0..N_PARTIES).map(|to| Bytes::from(vec![to as u8; 3])).collect());
+                let received = net
+                    .dynamic_worker_receive_or_leader_send(bytes_out, 5, sid)
+                    .await
+                    .unwrap();
+                assert_eq!(received, Bytes::from(vec![me as u8; 3]));
+                let (sent, received) = net.get_comm();
+                (sent - up, received - down)
+            })
+            .await;
+
+        for (id, comm) in comm.iter().enumerate() {
+            let sent = match id {
+                0 => (2..=N_PARTIES).sum::<usize>(),
+                5 => 3 * (N_PARTIES - 1),
+                _ => 0,
+            };
+            let received = match id {
+                0 => 3,
+                5 => id + 1,
+                _ => id + 1 + 3,
+            };
+            assert_eq!(*comm, (sent, received));
+        }
+    }
 }
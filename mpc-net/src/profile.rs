@@ -0,0 +1,554 @@
+//! Profiling of the timers as a tree of spans per party.
+//!
+//! [`crate::start_timer`] opens a [`tracing`] span and [`crate::end_timer`] closes it, next to the
+//! lines they print. A [`Profiler`] installed as the subscriber records every span with its parent,
+//! the thread it started on, its start and duration, and the bytes the party sent and received
+//! while it was open, including in nested spans, and with [`crate::memory::TrackingAllocator`]
+//! installed, the memory allocated at its end and at most while it was open. The records can be written as a Chrome trace, to
+//! open in `chrome://tracing` or Perfetto, or as CSV. Both give times since the Unix epoch, so that
+//! the traces of parties on one machine line up.
+//!
+//! The open timers are kept per task rather than per thread: a future run in [`profiled`] carries
+//! its own stack of timers, entered whenever it is polled, so timers nest correctly even when tasks
+//! move between threads or share one. Outside of [`profiled`], the timers of a thread form one
+//! stack, which is right for a single task like the `main` of an example.
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::json;
+use tracing::field::{Empty, Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Span, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::memory::{self, MemoryUsage, Phase};
+use crate::MPCNetError;
+
+/// Target of the spans opened by the timers.
+pub const TIMER_TARGET: &str = "mpc_net::timer";
+/// Target of the events reporting communication, see [`record_comm`].
+pub const COMM_TARGET: &str = "mpc_net::comm";
+
+/// A span as recorded by the [`Profiler`].
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SpanRecord {
+    /// Index in [`Profiler::spans`].
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub name: String,
+    /// The party the span belongs to, when several parties run in one process.
+    pub party: Option<u32>,
+    /// The thread the span was opened on.
+    pub thread: u64,
+    /// Since the profiler was created.
+    pub start: Duration,
+    /// Up to now for spans still open.
+    pub duration: Option<Duration>,
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    /// When the allocations are tracked, see [`crate::memory`].
+    pub memory: Option<MemoryUsage>,
+}
+
+/// Records the spans of the timers, see the [module documentation](self).
+#[derive(Clone)]
+pub struct Profiler {
+    epoch: Instant,
+    /// `epoch` since the Unix epoch.
+    unix_epoch: Duration,
+    spans: Arc<Mutex<Vec<SpanRecord>>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Index of a span in the records, kept in the span's extensions.
+struct Record(usize);
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            unix_epoch: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default(),
+            spans: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Create a profiler and install it as the global subscriber.
+    pub fn init() -> Result<Self, MPCNetError> {
+        let profiler = Self::new();
+        tracing_subscriber::registry()
+            .with(profiler.clone())
+            .try_init()
+            .map_err(|err| MPCNetError::Config(format!("Cannot install the profiler: {err}")))?;
+        Ok(profiler)
+    }
+
+    /// All spans recorded so far, in the order they were opened.
+    pub fn spans(&self) -> Vec<SpanRecord> {
+        let now = self.epoch.elapsed();
+        let mut spans = self.spans.lock().clone();
+        for span in spans.iter_mut().filter(|span| span.duration.is_none()) {
+            span.duration = Some(now - span.start);
+        }
+        spans
+    }
+
+    /// When the profiler was created, since the Unix epoch. [`SpanRecord::start`] is relative to it.
+    pub fn unix_epoch(&self) -> Duration {
+        self.unix_epoch
+    }
+
+    /// The spans as Chrome trace events, in process `party` unless they name their own party.
+    pub fn chrome_trace(&self, party: u32) -> serde_json::Value {
+        let spans = self.spans();
+        let mut parties = spans.iter().map(|span| span.party.unwrap_or(party)).collect::<Vec<_>>();
+        parties.sort();
+        parties.dedup();
+        let names = parties.into_iter().map(|party| {
+            json!({
+                "name": "process_name",
+                "ph": "M",
+                "pid": party,
+                "args": { "name": format!("party {party}") },
+            })
+        });
+        let events = spans
+            .into_iter()
+            .map(|span| {
+                json!({
+                    "name": span.name,
+                    "cat": "timer",
+                    "ph": "X",
+                    "ts": (self.unix_epoch + span.start).as_secs_f64() * 1e6,
+                    "dur": span.duration.unwrap_or_default().as_secs_f64() * 1e6,
+                    "pid": span.party.unwrap_or(party),
+                    "tid": span.thread,
+                    "args": {
+                        "id": span.id,
+                        "parent": span.parent,
+                        "bytes_sent": span.bytes_sent,
+                        "bytes_received": span.bytes_received,
+                        "memory": span.memory,
+                    },
+                })
+            })
+            .chain(names)
+            .collect::<Vec<_>>();
+        json!({ "traceEvents": events, "displayTimeUnit": "ms" })
+    }
+
+    pub fn write_chrome_trace(&self, path: impl AsRef<Path>, party: u32) -> Result<(), MPCNetError> {
+        let json = serde_json::to_vec(&self.chrome_trace(party))
+            .map_err(|err| MPCNetError::Config(err.to_string()))?;
+        Ok(fs::write(path, json)?)
+    }
+
+    /// The spans as CSV, one line per span, times in microseconds and memory in bytes.
+    pub fn csv(&self) -> String {
+        let mut out = String::from(
+            "id,parent,name,party,thread,start_us,duration_us,bytes_sent,bytes_received,\
+             memory_start,memory_end,memory_peak\n",
+        );
+        let opt = |v: Option<String>| v.unwrap_or_default();
+        for span in self.spans() {
+            let _ = writeln!(
+                out,
+                "{},{},\"{}\",{},{},{},{},{},{},{},{},{}",
+                span.id,
+                opt(span.parent.map(|p| p.to_string())),
+                span.name.replace('"', "\"\""),
+                opt(span.party.map(|p| p.to_string())),
+                span.thread,
+                (self.unix_epoch + span.start).as_micros(),
+                span.duration.unwrap_or_default().as_micros(),
+                span.bytes_sent,
+                span.bytes_received,
+                opt(span.memory.map(|m| m.start.to_string())),
+                opt(span.memory.map(|m| m.end.to_string())),
+                opt(span.memory.map(|m| m.peak.to_string()))
+            );
+        }
+        out
+    }
+
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> Result<(), MPCNetError> {
+        Ok(fs::write(path, self.csv())?)
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Profiler {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = SpanFields::default();
+        attrs.record(&mut fields);
+        let mut spans = self.spans.lock();
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<Record>().map(|r| r.0));
+        let record = SpanRecord {
+            id: spans.len(),
+            parent,
+            name: fields.name.unwrap_or_else(|| attrs.metadata().name().to_string()),
+            party: fields.party.or_else(|| parent.and_then(|p| spans[p].party)),
+            thread: thread_number(),
+            start: self.epoch.elapsed(),
+            duration: None,
+            bytes_sent: 0,
+            bytes_received: 0,
+            memory: None,
+        };
+        span.extensions_mut().insert(Record(record.id));
+        spans.push(record);
+    }
+
+    fn on_close(&self, id: Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let index = span.extensions().get::<Record>().map(|r| r.0);
+        if let Some(index) = index {
+            let mut spans = self.spans.lock();
+            let record = &mut spans[index];
+            record.duration = Some(self.epoch.elapsed() - record.start);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &tracing::span::Record<'_>, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = MemoryFields::default();
+        values.record(&mut fields);
+        let index = span.extensions().get::<Record>().map(|r| r.0);
+        if let (Some(index), Some(memory)) = (index, fields.usage()) {
+            self.spans.lock()[index].memory = Some(memory);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+        if event.metadata().target() != COMM_TARGET {
+            return;
+        }
+        let mut comm = CommFields::default();
+        event.record(&mut comm);
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+        let mut spans = self.spans.lock();
+        for span in scope {
+            let index = span.extensions().get::<Record>().map(|r| r.0);
+            if let Some(index) = index {
+                spans[index].bytes_sent += comm.sent as usize;
+                spans[index].bytes_received += comm.received as usize;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct SpanFields {
+    name: Option<String>,
+    party: Option<u32>,
+}
+
+impl Visit for SpanFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "name" {
+            self.name = Some(value.to_string());
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "party" {
+            self.party = Some(value as u32);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "name" {
+            self.name = Some(format!("{value:?}"));
+        }
+    }
+}
+
+#[derive(Default)]
+struct MemoryFields {
+    start: Option<u64>,
+    end: Option<u64>,
+    peak: Option<u64>,
+}
+
+impl MemoryFields {
+    fn usage(&self) -> Option<MemoryUsage> {
+        Some(MemoryUsage {
+            start: self.start? as usize,
+            end: self.end? as usize,
+            peak: self.peak? as usize,
+        })
+    }
+}
+
+impl Visit for MemoryFields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "memory_start" => self.start = Some(value),
+            "memory_end" => self.end = Some(value),
+            "memory_peak" => self.peak = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+}
+
+#[derive(Default)]
+struct CommFields {
+    sent: u64,
+    received: u64,
+}
+
+impl Visit for CommFields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "sent" => self.sent = value,
+            "received" => self.received = value,
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+}
+
+/// A small number for the current thread, stable for its lifetime.
+fn thread_number() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    thread_local! {
+        static NUMBER: u64 = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    NUMBER.with(|n| *n)
+}
+
+/// Report bytes sent and received to the spans of the open timers.
+pub fn record_comm(sent: usize, received: usize) {
+    if tracing::dispatcher::has_been_set() {
+        tracing::event!(target: COMM_TARGET, Level::TRACE, sent = sent as u64, received = received as u64);
+    }
+}
+
+/// The timers open in a task, innermost last.
+type TimerStack = Arc<Mutex<Vec<OpenTimer>>>;
+
+struct OpenTimer {
+    token: u64,
+    span: Span,
+    print: bool,
+}
+
+thread_local! {
+    /// The stack of the task being polled, or of the thread.
+    static CURRENT: RefCell<TimerStack> = RefCell::default();
+}
+
+/// A timer opened with [`open_timer`].
+pub struct TimerHandle {
+    token: u64,
+    stack: TimerStack,
+    /// How many printing timers enclose it.
+    pub depth: usize,
+    memory: Option<Phase>,
+}
+
+/// Open a timer named `name` in the current task, entering its span. Timers with `print` set count
+/// towards the [`TimerHandle::depth`] of the timers they enclose.
+pub fn open_timer(name: &str, print: bool) -> TimerHandle {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let token = NEXT.fetch_add(1, Ordering::Relaxed);
+    let span = if tracing::dispatcher::has_been_set() {
+        tracing::span!(
+            target: TIMER_TARGET,
+            Level::INFO,
+            "timer",
+            name,
+            memory_start = Empty,
+            memory_end = Empty,
+            memory_peak = Empty
+        )
+    } else {
+        Span::none()
+    };
+    let stack = CURRENT.with(|current| current.borrow().clone());
+    let mut timers = stack.lock();
+    let depth = timers.iter().filter(|timer| timer.print).count();
+    enter(&span);
+    timers.push(OpenTimer { token, span, print });
+    drop(timers);
+    TimerHandle {
+        token,
+        stack,
+        depth,
+        memory: memory::start_phase(),
+    }
+}
+
+/// Close a timer, also when timers opened after it are still open, returning the memory of its
+/// phase if the allocations are tracked. Dropping the handle does the same, so that timers left by
+/// an early return do not stay open.
+pub fn close_timer(mut handle: TimerHandle) -> Option<MemoryUsage> {
+    handle.close()
+}
+
+impl TimerHandle {
+    fn close(&mut self) -> Option<MemoryUsage> {
+        let usage = self.memory.take().map(memory::end_phase);
+        let mut timers = self.stack.lock();
+        if let Some(pos) = timers.iter().rposition(|timer| timer.token == self.token) {
+            let timer = timers.remove(pos);
+            if let Some(usage) = usage {
+                timer.span.record("memory_start", usage.start as u64);
+                timer.span.record("memory_end", usage.end as u64);
+                timer.span.record("memory_peak", usage.peak as u64);
+            }
+            let here = CURRENT.with(|current| Arc::ptr_eq(&current.borrow(), &self.stack));
+            if here {
+                exit(&timer.span);
+            }
+        }
+        usage
+    }
+}
+
+impl Drop for TimerHandle {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn enter(span: &Span) {
+    span.with_subscriber(|(id, dispatch)| dispatch.enter(id));
+}
+
+fn exit(span: &Span) {
+    span.with_subscriber(|(id, dispatch)| dispatch.exit(id));
+}
+
+/// A future with its own stack of timers, see [`profiled`].
+pub struct Profiled<F> {
+    inner: Pin<Box<F>>,
+    stack: TimerStack,
+}
+
+/// Run `future` with its own stack of timers, so that they nest correctly wherever it is polled.
+/// With `party`, its spans are marked as spans of that party.
+pub fn profiled<F: Future>(future: F, party: Option<u32>) -> Profiled<F> {
+    let mut timers = Vec::new();
+    if let Some(party) = party.filter(|_| tracing::dispatcher::has_been_set()) {
+        let span = tracing::span!(target: TIMER_TARGET, Level::INFO, "party", party);
+        timers.push(OpenTimer {
+            token: u64::MAX,
+            span,
+            print: false,
+        });
+    }
+    Profiled {
+        inner: Box::pin(future),
+        stack: Arc::new(Mutex::new(timers)),
+    }
+}
+
+impl<F: Future> Future for Profiled<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let stack = self.stack.clone();
+        let outer = CURRENT.with(|current| current.replace(stack.clone()));
+        stack.lock().iter().for_each(|timer| enter(&timer.span));
+        let poll = self.inner.as_mut().poll(cx);
+        stack.lock().iter().rev().for_each(|timer| exit(&timer.span));
+        CURRENT.with(|current| current.replace(outer));
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tracing_subscriber::prelude::*;
+
+    use super::{profiled, record_comm, Profiler};
+    use crate::{end_timer, start_timer};
+
+    #[test]
+    fn test_profile_tasks() {
+        let profiler = Profiler::new();
+        let _guard = tracing_subscriber::registry().with(profiler.clone()).set_default();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        // Two parties interleaved on one thread, each with nested timers around awaits.
+        runtime.block_on(async {
+            let party = |id: u32| {
+                profiled(
+                    async move {
+                        let outer = start_timer!(format!("outer {id}"), false);
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                        let inner = start_timer!("inner", false);
+                        record_comm(10 + id as usize, 1);
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                        end_timer!(inner);
+                        record_comm(100, 0);
+                        end_timer!(outer);
+                    },
+                    Some(id),
+                )
+            };
+            tokio::join!(party(0), party(1));
+        });
+
+        let spans = profiler.spans();
+        assert_eq!(spans.len(), 6);
+        for id in 0..2 {
+            let root = spans.iter().find(|s| s.name == "party" && s.party == Some(id)).unwrap();
+            let outer = spans.iter().find(|s| s.name == format!("outer {id}")).unwrap();
+            let inner = spans
+                .iter()
+                .find(|s| s.name == "inner" && s.parent == Some(outer.id))
+                .unwrap();
+            assert_eq!(outer.parent, Some(root.id));
+            assert_eq!(inner.party, Some(id));
+            assert_eq!((inner.bytes_sent, inner.bytes_received), (10 + id as usize, 1));
+            assert_eq!((outer.bytes_sent, outer.bytes_received), (110 + id as usize, 1));
+            assert!(outer.duration.unwrap() >= Duration::from_millis(10));
+            assert!(inner.start >= outer.start);
+        }
+
+        let csv = profiler.csv();
+        assert_eq!(csv.lines().count(), 7);
+        assert!(csv.contains(",\"outer 1\",1,"));
+        let trace = profiler.chrome_trace(9);
+        // The spans and the names of the two parties.
+        assert_eq!(trace["traceEvents"].as_array().unwrap().len(), 8);
+        assert_eq!(trace["traceEvents"][0]["ph"], "X");
+    }
+}
@@ -0,0 +1,289 @@
+//! Peer discovery through a coordinator, instead of a host file.
+//!
+//! A [`Coordinator`] knows how many parties to expect. Every party registers with its id, the
+//! address it listens at and a public key, see [`crate::multi::MPCNetConnection::init_from_coordinator`].
+//! Once all parties joined, the coordinator sends each of them the [`PeerTable`] of all parties
+//! and a fresh session id, and the parties connect to each other as usual. Parties check the
+//! session id when connecting, so parties of different sessions do not mix.
+//!
+//! A party listening at an unspecified IP, e.g. `0.0.0.0:8000`, is announced at the IP the
+//! coordinator sees it connect from, so parties need not know their own address. A party that
+//! registers again before all parties joined replaces its earlier registration. Public keys are
+//! handed out as they are, the transports do not check them.
+//!
+//! Messages are JSON, each preceded by its length as a `u32`.
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use log::{trace, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::MPCNetError;
+
+/// How long a connecting party may take to register.
+pub const REGISTER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest message accepted.
+const MAX_MESSAGE: usize = 16 * 1024 * 1024;
+
+/// A party as registered with the coordinator.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerEntry {
+    pub id: u32,
+    /// Where the party listens, like in a host file.
+    pub addr: String,
+    pub public_key: Vec<u8>,
+}
+
+/// All parties of a session, ordered by id.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerTable {
+    pub session_id: u64,
+    pub peers: Vec<PeerEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Reply {
+    Table(PeerTable),
+    Rejected(String),
+}
+
+/// Hands out the peer table once all parties registered.
+#[derive(Debug)]
+pub struct Coordinator {
+    listener: TcpListener,
+    n_parties: usize,
+}
+
+impl Coordinator {
+    pub async fn bind(addr: SocketAddr, n_parties: usize) -> Result<Self, MPCNetError> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            n_parties,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, MPCNetError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Wait for all parties to register, then send each of them the peer table, also returned.
+    /// Can be called again for the next session.
+    pub async fn run_session(&self) -> Result<PeerTable, MPCNetError> {
+        let mut joined = HashMap::<u32, (TcpStream, PeerEntry)>::new();
+        while joined.len() < self.n_parties {
+            let (mut stream, from) = self.listener.accept().await?;
+            let entry =
+                match tokio::time::timeout(REGISTER_TIMEOUT, read_message::<PeerEntry, _>(&mut stream))
+                    .await
+                {
+                    Ok(Ok(entry)) => entry,
+                    Ok(Err(err)) => {
+                        trace!("Bad registration from {from}: {err:?}");
+                        continue;
+                    }
+                    Err(_) => {
+                        trace!("No registration from {from}");
+                        continue;
+                    }
+                };
+            if entry.id as usize >= self.n_parties {
+                let reason = format!("Party {} registered, expected {} parties", entry.id, self.n_parties);
+                let _ = write_message(&mut stream, &Reply::Rejected(reason)).await;
+                continue;
+            }
+            let entry = PeerEntry {
+                addr: announced_addr(&entry.addr, from.ip()),
+                ..entry
+            };
+            trace!("Party {} registered at {}", entry.id, entry.addr);
+            if joined.insert(entry.id, (stream, entry)).is_some() {
+                trace!("Party registered again, replacing the earlier registration");
+            }
+        }
+
+        let mut joined = joined.into_values().collect::<Vec<_>>();
+        joined.sort_by_key(|(_, entry)| entry.id);
+        let table = PeerTable {
+            session_id: rand::random(),
+            peers: joined.iter().map(|(_, entry)| entry.clone()).collect(),
+        };
+        let reply = Reply::Table(table.clone());
+        for (mut stream, entry) in joined {
+            if let Err(err) = write_message(&mut stream, &reply).await {
+                // The party fails to connect to its peers, like with a wrong host file.
+                warn!("Cannot send the peer table to party {}: {err:?}", entry.id);
+            }
+        }
+        Ok(table)
+    }
+}
+
+/// Register `entry` with the coordinator at `coordinator`, retrying while it is not up yet, and
+/// wait for the other parties.
+pub async fn rendezvous(coordinator: SocketAddr, entry: &PeerEntry) -> Result<PeerTable, MPCNetError> {
+    let mut res = TcpStream::connect(coordinator).await;
+    for _ in 0..30 {
+        if res.is_ok() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        res = TcpStream::connect(coordinator).await;
+    }
+    let mut stream = res?;
+    write_message(&mut stream, entry).await?;
+    let table = match read_message::<Reply, _>(&mut stream).await? {
+        Reply::Table(table) => table,
+        Reply::Rejected(reason) => {
+            return Err(MPCNetError::Config(format!(
+                "The coordinator rejected party {}: {reason}",
+                entry.id
+            )))
+        }
+    };
+    let consistent = table.peers.iter().enumerate().all(|(i, peer)| peer.id == i as u32)
+        && table.peers.get(entry.id as usize).map(|peer| &peer.public_key) == Some(&entry.public_key);
+    if !consistent {
+        return Err(MPCNetError::Config(
+            "The coordinator sent an inconsistent peer table".to_string(),
+        ));
+    }
+    Ok(table)
+}
+
+/// `addr`, with an unspecified IP replaced by `seen`.
+fn announced_addr(addr: &str, seen: IpAddr) -> String {
+    let (scheme, rest) = match addr.split_once(':') {
+        Some((scheme, rest)) if scheme == "tcp" || scheme == "quic" => (Some(scheme), rest),
+        _ => (None, addr),
+    };
+    match rest.parse::<SocketAddr>() {
+        Ok(mut socket) if socket.ip().is_unspecified() => {
+            socket.set_ip(seen);
+            match scheme {
+                Some(scheme) => format!("{scheme}:{socket}"),
+                None => socket.to_string(),
+            }
+        }
+        _ => addr.to_string(),
+    }
+}
+
+async fn write_message<T: Serialize, W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    message: &T,
+) -> Result<(), MPCNetError> {
+    let bytes = serde_json::to_vec(message).map_err(|err| MPCNetError::Config(err.to_string()))?;
+    stream.write_u32(bytes.len() as u32).await?;
+    stream.write_all(&bytes).await?;
+    Ok(())
+}
+
+async fn read_message<T: DeserializeOwned, R: AsyncRead + Unpin>(
+    stream: &mut R,
+) -> Result<T, MPCNetError> {
+    let len = stream.read_u32().await? as usize;
+    if len > MAX_MESSAGE {
+        return Err(MPCNetError::Config(format!("Rendezvous message of {len} bytes")));
+    }
+    let mut bytes = vec![0; len];
+    stream.read_exact(&mut bytes).await?;
+    serde_json::from_slice(&bytes)
+        .map_err(|err| MPCNetError::Config(format!("Bad rendezvous message: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use futures::future::try_join_all;
+    use tokio_util::bytes::Bytes;
+
+    use super::{announced_addr, rendezvous, Coordinator, PeerEntry};
+    use crate::multi::MPCNetConnection;
+    use crate::{MPCNet, MPCNetError, MultiplexedStreamID, Tcp};
+
+    #[tokio::test]
+    async fn test_rendezvous() {
+        const N_PARTIES: u32 = 4;
+        let coordinator = Coordinator::bind(([127, 0, 0, 1], 0).into(), N_PARTIES as usize)
+            .await
+            .unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let session = tokio::spawn(async move { coordinator.run_session().await.unwrap() });
+
+        let stranger = PeerEntry {
+            id: N_PARTIES,
+            ..Default::default()
+        };
+        assert!(matches!(
+            rendezvous(addr, &stranger).await,
+            Err(MPCNetError::Config(_))
+        ));
+
+        let parties = (0..N_PARTIES).map(|id| {
+            tokio::spawn(async move {
+                let listen: SocketAddr = ([0, 0, 0, 0], 0).into();
+                let mut net =
+                    MPCNetConnection::<Tcp>::init_from_coordinator(addr, id, listen, vec![id as u8])
+                        .await?;
+                net.connect_to_all().await?;
+                let sum = net
+                    .leader_compute(&[id as u8], MultiplexedStreamID::Zero, |v| {
+                        let sum = v.iter().map(|b| b[0]).sum::<u8>();
+                        vec![Bytes::from(vec![sum]); v.len()]
+                    })
+                    .await?;
+                assert_eq!(sum, Bytes::from(vec![6]));
+                Ok::<_, MPCNetError>(net.session.clone().unwrap())
+            })
+        });
+        let tables = try_join_all(parties).await.unwrap();
+        let table = session.await.unwrap();
+        for (id, peer) in table.peers.iter().enumerate() {
+            assert_eq!(peer.id, id as u32);
+            assert_eq!(peer.public_key, vec![id as u8]);
+            assert!(peer.addr.starts_with("127.0.0.1:"));
+        }
+        for party in tables {
+            assert_eq!(party.unwrap(), table);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_mismatch() {
+        let coordinator = Coordinator::bind(([127, 0, 0, 1], 0).into(), 2).await.unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let session = tokio::spawn(async move { coordinator.run_session().await.unwrap() });
+        let parties = (0..2).map(|id| {
+            MPCNetConnection::<Tcp>::init_from_coordinator(
+                addr,
+                id,
+                ([127, 0, 0, 1], 0).into(),
+                vec![],
+            )
+        });
+        let mut nets = try_join_all(parties).await.unwrap();
+        session.await.unwrap();
+
+        // Party 1 is left over from an earlier session.
+        nets[1].session.as_mut().unwrap().session_id ^= 1;
+        let results =
+            futures::future::join_all(nets.iter_mut().map(|net| net.connect_to_all())).await;
+        for result in results {
+            assert!(matches!(result, Err(MPCNetError::Config(_))), "{result:?}");
+        }
+    }
+
+    #[test]
+    fn test_announced_addr() {
+        let seen = [10, 0, 0, 7].into();
+        assert_eq!(announced_addr("0.0.0.0:8000", seen), "10.0.0.7:8000");
+        assert_eq!(announced_addr("quic:0.0.0.0:8000", seen), "quic:10.0.0.7:8000");
+        assert_eq!(announced_addr("10.0.0.1:8000", seen), "10.0.0.1:8000");
+        assert_eq!(announced_addr("unix:/tmp/0.sock", seen), "unix:/tmp/0.sock");
+    }
+}
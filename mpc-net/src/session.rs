@@ -0,0 +1,241 @@
+//! Session resumption after a dropped connection.
+//!
+//! Every data frame sent on a stream carries a sequence number, and every frame carries the number
+//! of data frames received so far in the opposite direction, acknowledging them. Senders keep the
+//! frames not acknowledged yet in a [`ReplayBuffer`]. When the connection to a peer drops, the
+//! party with the lower id dials again, both parties exchange how many frames they received on
+//! each stream, and resend the frames the other side is missing. A transient network failure is
+//! thus invisible to the protocol running on top.
+//!
+//! Replay buffers are bounded by [`REPLAY_LIMIT`]: a sender whose peer has not acknowledged that
+//! much yet waits for it, reading the acknowledgements itself if no receive on the stream is in
+//! progress. Frames are never dropped before they are acknowledged, so every connection can be
+//! resumed, and a party keeps at most a few replay buffers' worth of frames per peer.
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use parking_lot::Mutex;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex as TokioMutex;
+
+use crate::multi::Chunk;
+use crate::{MPCNetError, WireConfig};
+
+/// Bytes of unacknowledged frames kept per stream for replay. A single frame may exceed it,
+/// see [`ReplayBuffer::has_room`].
+pub const REPLAY_LIMIT: usize = 4 * ACK_INTERVAL;
+/// A receiver acknowledges explicitly after this many bytes if it has nothing to send.
+pub const ACK_INTERVAL: usize = 1024 * 1024;
+/// How long to wait for a peer to come back.
+pub const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Data frames sent on a stream and not yet acknowledged by the peer.
+#[derive(Debug, Default)]
+pub struct ReplayBuffer {
+    /// Sequence number of the first frame in `frames`.
+    first: u64,
+    frames: VecDeque<Chunk>,
+    bytes: usize,
+}
+
+impl ReplayBuffer {
+    /// Whether a frame of `len` bytes may be sent without waiting for acknowledgements. Up to
+    /// [`ACK_INTERVAL`] bytes the peer may not acknowledge at all, so another frame is always
+    /// allowed then, however large.
+    pub fn has_room(&self, len: usize) -> bool {
+        self.bytes <= ACK_INTERVAL || self.bytes + len <= REPLAY_LIMIT
+    }
+
+    /// Bytes of the frames kept.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Store a frame, returning its sequence number.
+    pub fn push(&mut self, chunk: Chunk) -> u64 {
+        let seq = self.first + self.frames.len() as u64;
+        self.bytes += chunk.data.len();
+        self.frames.push_back(chunk);
+        seq
+    }
+
+    /// The peer received all frames before `received`. Returns whether frames were released.
+    pub fn ack(&mut self, received: u64) -> bool {
+        let first = self.first;
+        while self.first < received && !self.frames.is_empty() {
+            self.pop();
+        }
+        self.first != first
+    }
+
+    /// The frames to resend to a peer that received all frames before `received`.
+    pub fn resume(&mut self, received: u64) -> Result<Vec<(u64, Chunk)>, String> {
+        if received < self.first {
+            return Err(format!(
+                "frames {}..{} were acknowledged before",
+                received, self.first
+            ));
+        }
+        self.ack(received);
+        Ok((self.first..)
+            .zip(self.frames.iter())
+            .map(|(seq, chunk)| (seq, chunk.clone()))
+            .collect())
+    }
+
+    fn pop(&mut self) {
+        if let Some(chunk) = self.frames.pop_front() {
+            self.bytes -= chunk.data.len();
+            self.first += 1;
+        }
+    }
+}
+
+/// Obtains a new transport to a peer: by dialing it, or by waiting for it to dial us.
+pub type Dialer<IO> = Arc<dyn Fn() -> BoxFuture<'static, Result<IO, MPCNetError>> + Send + Sync>;
+
+/// Tears down a transport.
+pub type Closer = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+
+/// State of the connection to a peer that survives reconnections.
+pub struct Link<IO> {
+    /// Whether the peer dials us.
+    pub is_server: bool,
+    dialer: Option<Dialer<IO>>,
+    /// Bumped on every reconnection.
+    generation: AtomicU64,
+    /// Held while reconnecting.
+    reconnecting: TokioMutex<()>,
+    /// Closes the current transport.
+    closer: Mutex<Option<Closer>>,
+}
+
+impl<IO> Link<IO> {
+    pub fn new(is_server: bool, dialer: Option<Dialer<IO>>, closer: Closer) -> Self {
+        Self {
+            is_server,
+            dialer,
+            generation: AtomicU64::new(0),
+            reconnecting: TokioMutex::new(()),
+            closer: Mutex::new(Some(closer)),
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn dialer(&self) -> Option<&Dialer<IO>> {
+        self.dialer.as_ref()
+    }
+
+    /// Serializes reconnections. Returns `None` if the link was already restored since
+    /// `generation`.
+    pub async fn lock_for_reconnect(
+        &self,
+        generation: u64,
+    ) -> Option<tokio::sync::MutexGuard<'_, ()>> {
+        let guard = self.reconnecting.lock().await;
+        (self.generation() == generation).then_some(guard)
+    }
+
+    /// Switch to a new transport.
+    pub fn reconnected(&self, closer: Closer) {
+        *self.closer.lock() = Some(closer);
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Tear down the current transport, so that the peer notices as well.
+    pub async fn close(&self) {
+        let closer = self.closer.lock().take();
+        if let Some(closer) = closer {
+            closer().await;
+        }
+    }
+}
+
+/// What a party tells its peer when resuming: its wire options and how many data frames it
+/// received on every stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resume {
+    pub wire: WireConfig,
+    pub received: Vec<u64>,
+}
+
+impl Resume {
+    pub async fn write<W: AsyncWrite + Unpin>(&self, stream: &mut W) -> Result<(), MPCNetError> {
+        self.wire.write(stream).await?;
+        stream.write_u8(self.received.len() as u8).await?;
+        for received in &self.received {
+            stream.write_u64(*received).await?;
+        }
+        stream.flush().await?;
+        Ok(())
+    }
+
+    pub async fn read<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Self, MPCNetError> {
+        let wire = WireConfig::read(stream).await?;
+        let n = stream.read_u8().await?;
+        let mut received = Vec::with_capacity(n as usize);
+        for _ in 0..n {
+            received.push(stream.read_u64().await?);
+        }
+        Ok(Self { wire, received })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_util::bytes::Bytes;
+
+    use super::{ReplayBuffer, Resume, ACK_INTERVAL, REPLAY_LIMIT};
+    use crate::multi::Chunk;
+    use crate::WireConfig;
+
+    fn chunk(last: bool, data: Vec<u8>) -> Chunk {
+        Chunk {
+            last,
+            batch: false,
+            data: Bytes::from(data),
+        }
+    }
+
+    #[test]
+    fn test_replay_buffer() {
+        let mut replay = ReplayBuffer::default();
+        for i in 0..4u8 {
+            assert_eq!(replay.push(chunk(i == 3, vec![i])), i as u64);
+        }
+        replay.ack(1);
+        let frames = replay.resume(2).unwrap();
+        assert_eq!(frames, vec![(2, chunk(false, vec![2])), (3, chunk(true, vec![3]))]);
+        assert!(replay.resume(1).is_err());
+
+        // Frames are kept until acknowledged, the sender waits for room beyond the limit.
+        assert!(replay.has_room(REPLAY_LIMIT));
+        replay.push(chunk(false, vec![0; REPLAY_LIMIT]));
+        assert!(!replay.has_room(1));
+        replay.push(chunk(true, vec![0; 1]));
+        assert_eq!(replay.resume(4).unwrap().len(), 2);
+        assert!(replay.ack(5));
+        assert!(!replay.ack(5));
+        // Frames the peer may never acknowledge do not hold back even a large one.
+        assert!(replay.has_room(2 * REPLAY_LIMIT));
+        replay.push(chunk(false, vec![0; ACK_INTERVAL]));
+        assert!(!replay.has_room(REPLAY_LIMIT));
+    }
+
+    #[tokio::test]
+    async fn test_resume_handshake() {
+        let (mut a, mut b) = tokio::io::duplex(64);
+        let resume = Resume {
+            wire: WireConfig::default().with_coalescing(true),
+            received: vec![1, 0, 1 << 40],
+        };
+        resume.write(&mut a).await.unwrap();
+        assert_eq!(Resume::read(&mut b).await.unwrap(), resume);
+    }
+}
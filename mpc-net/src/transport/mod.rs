@@ -0,0 +1,163 @@
+//! Transports carrying the traffic between parties.
+//!
+//! A [`Transport`] connects a party to a peer and splits the connection into independent
+//! channels, one per [`crate::MultiplexedStreamID`]. Before the split, the connection carries the
+//! handshake: the id of the dialing party, the [`crate::WireConfig`]s and, when resuming, the
+//! [`crate::session::Resume`] messages. The party with the lower id dials.
+//!
+//! [`Tcp`] multiplexes the channels over a single TCP stream, so a lost packet stalls all of them.
+//! [`Quic`] maps every channel to a native QUIC stream, which avoids this head-of-line blocking
+//! on lossy links. Parties on the same machine can skip the network stack: [`Unix`] multiplexes
+//! the channels over a Unix domain socket, and [`Shm`] moves the data through ring buffers in
+//! shared memory.
+//!
+//! A host file selects the transport by the scheme of its addresses, see [`TransportKind`].
+mod quic;
+mod shm;
+mod tcp;
+mod unix;
+
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::net::SocketAddr;
+use std::path::Path;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+pub use quic::{Quic, QuicChannel, QuicConnection};
+pub use shm::{Shm, ShmChannel};
+pub use tcp::Tcp;
+pub(crate) use tcp::multiplex;
+pub use unix::{Unix, UnixEndpoint};
+
+use crate::session::Closer;
+use crate::MPCNetError;
+
+#[async_trait]
+pub trait Transport: Send + Sync + Sized + 'static {
+    /// Accepts connections from peers, and dials them for transports that keep local state.
+    type Endpoint: Debug + Send + Sync + 'static;
+    /// A connection to a peer, carrying the handshake.
+    type Conn: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+    /// One of the channels a connection is split into.
+    type Channel: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+    /// Where a party listens.
+    type Addr: Clone + Debug + Send + Sync + 'static;
+
+    /// Parse an address of a host file. Addresses may start with the scheme of the transport.
+    fn parse_addr(addr: &str) -> Result<Self::Addr, MPCNetError>;
+
+    /// Format an address like in a host file, with the scheme of the transport if it needs one.
+    fn format_addr(addr: &Self::Addr) -> String;
+
+    /// An address for a party of a test network on this machine, see [`crate::LocalTestNet`].
+    fn loopback_addr() -> Self::Addr;
+
+    async fn bind(addr: Self::Addr) -> Result<Self::Endpoint, MPCNetError>;
+
+    /// The address peers reach the endpoint at.
+    fn local_addr(endpoint: &Self::Endpoint) -> Result<Self::Addr, MPCNetError>;
+
+    /// Wait for a peer to connect.
+    async fn accept(endpoint: &Self::Endpoint) -> Result<Self::Conn, MPCNetError>;
+
+    async fn connect(endpoint: &Self::Endpoint, addr: Self::Addr)
+        -> Result<Self::Conn, MPCNetError>;
+
+    /// Split a connection into `channels` channels, also returning how to close the connection.
+    /// `is_server` is set on the accepting side.
+    async fn split(
+        conn: Self::Conn,
+        channels: usize,
+        is_server: bool,
+    ) -> Result<(Vec<Self::Channel>, Closer), MPCNetError>;
+}
+
+/// The transports a host file can select.
+///
+/// Every line of a host file is the address of a party, e.g. `10.0.0.1:8000`, `quic:10.0.0.1:8000`,
+/// `unix:/tmp/party0.sock` or `shm:/tmp/party0.sock`. Addresses without a scheme are TCP. All
+/// parties must use the same transport.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransportKind {
+    Tcp,
+    Quic,
+    Unix,
+    Shm,
+}
+
+impl TransportKind {
+    /// The transport selected by the scheme of an address.
+    pub fn of_addr(addr: &str) -> Self {
+        match addr.trim().split_once(':').map(|(scheme, _)| scheme) {
+            Some("quic") => Self::Quic,
+            Some("unix") => Self::Unix,
+            Some("shm") => Self::Shm,
+            _ => Self::Tcp,
+        }
+    }
+
+    /// The transport selected by the first address of a host file.
+    pub fn of_host_file(path: &Path) -> io::Result<Self> {
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                return Ok(Self::of_addr(&line));
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "host file lists no parties",
+        ))
+    }
+}
+
+/// Strip `scheme:` from an address, failing if `required` and the address lacks it.
+fn strip_scheme<'a>(addr: &'a str, scheme: &str, required: bool) -> Result<&'a str, MPCNetError> {
+    let addr = addr.trim();
+    match addr.strip_prefix(scheme).and_then(|rest| rest.strip_prefix(':')) {
+        Some(rest) => Ok(rest),
+        None if !required => Ok(addr),
+        None => Err(MPCNetError::Config(format!(
+            "Address {addr} does not start with {scheme}:"
+        ))),
+    }
+}
+
+/// Parse a socket address, possibly prefixed with `scheme:`.
+fn parse_socket_addr(addr: &str, scheme: &str) -> Result<SocketAddr, MPCNetError> {
+    let addr = strip_scheme(addr, scheme, false)?;
+    addr.parse()
+        .map_err(|err| MPCNetError::Config(format!("Bad socket address {addr}: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Quic, Shm, Tcp, Transport, TransportKind, Unix};
+
+    #[test]
+    fn test_addresses() {
+        assert_eq!(TransportKind::of_addr("10.0.0.1:8000"), TransportKind::Tcp);
+        assert_eq!(TransportKind::of_addr("[::1]:8000"), TransportKind::Tcp);
+        assert_eq!(TransportKind::of_addr("quic:10.0.0.1:8000"), TransportKind::Quic);
+        assert_eq!(TransportKind::of_addr(" unix:/tmp/0.sock"), TransportKind::Unix);
+        assert_eq!(TransportKind::of_addr("shm:/tmp/0.sock"), TransportKind::Shm);
+
+        let addr = "10.0.0.1:8000".parse().unwrap();
+        assert_eq!(Tcp::parse_addr("10.0.0.1:8000").unwrap(), addr);
+        assert_eq!(Tcp::parse_addr("tcp:10.0.0.1:8000").unwrap(), addr);
+        assert_eq!(Quic::parse_addr("quic:10.0.0.1:8000").unwrap(), addr);
+        assert!(Tcp::parse_addr("unix:/tmp/0.sock").is_err());
+        assert_eq!(
+            Unix::parse_addr("unix:/tmp/0.sock").unwrap(),
+            PathBuf::from("/tmp/0.sock")
+        );
+        assert_eq!(Shm::parse_addr("shm:run/0.sock").unwrap(), PathBuf::from("run/0.sock"));
+        assert!(Unix::parse_addr("/tmp/0.sock").is_err());
+        assert!(Shm::parse_addr("unix:/tmp/0.sock").is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use quinn::rustls::crypto::{ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use quinn::rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use quinn::rustls::{self, DigitallySignedStruct, SignatureScheme};
+use quinn::{
+    ClientConfig, Connection, Endpoint, RecvStream, SendStream, ServerConfig, TransportConfig,
+    VarInt,
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use super::{parse_socket_addr, Transport};
+use crate::session::Closer;
+use crate::MPCNetError;
+
+/// Name the certificates are issued for.
+const SERVER_NAME: &str = "mpc-net";
+/// Keeps connections open while the parties compute without talking.
+const KEEP_ALIVE: Duration = Duration::from_secs(5);
+/// Flow control windows of a stream and of a connection, large enough to keep a fast link busy.
+const STREAM_WINDOW: u32 = 16 * 1024 * 1024;
+const CONNECTION_WINDOW: u32 = 64 * 1024 * 1024;
+/// How long a dropped channel keeps the connection open for the peer to receive what was sent.
+const LINGER: Duration = Duration::from_secs(10);
+
+/// QUIC with `quinn`, every channel a native stream.
+///
+/// Every endpoint presents a fresh self-signed certificate and accepts any certificate of its
+/// peers: like over TCP, parties do not authenticate each other.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Quic;
+
+#[async_trait]
+impl Transport for Quic {
+    type Endpoint = Endpoint;
+    type Conn = QuicConnection;
+    type Channel = QuicChannel;
+    type Addr = SocketAddr;
+
+    fn parse_addr(addr: &str) -> Result<SocketAddr, MPCNetError> {
+        parse_socket_addr(addr, "quic")
+    }
+
+    fn format_addr(addr: &SocketAddr) -> String {
+        format!("quic:{addr}")
+    }
+
+    fn loopback_addr() -> SocketAddr {
+        ([127, 0, 0, 1], 0).into()
+    }
+
+    async fn bind(addr: SocketAddr) -> Result<Endpoint, MPCNetError> {
+        let transport = Arc::new(transport_config());
+        let cert = rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()])
+            .map_err(|err| MPCNetError::Config(format!("Cannot issue a certificate: {err}")))?;
+        let key = PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+        let mut server = ServerConfig::with_single_cert(vec![cert.cert.der().clone()], key.into())
+            .map_err(|err| MPCNetError::Config(format!("Bad certificate: {err}")))?;
+        server.transport_config(transport.clone());
+
+        let provider = Arc::new(ring::default_provider());
+        let crypto = rustls::ClientConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(&[&rustls::version::TLS13])
+            .map_err(|err| MPCNetError::Config(err.to_string()))?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+            .with_no_client_auth();
+        let crypto = QuicClientConfig::try_from(crypto)
+            .map_err(|err| MPCNetError::Config(err.to_string()))?;
+        let mut client = ClientConfig::new(Arc::new(crypto));
+        client.transport_config(transport);
+
+        let mut endpoint = Endpoint::server(server, addr)?;
+        endpoint.set_default_client_config(client);
+        Ok(endpoint)
+    }
+
+    fn local_addr(endpoint: &Endpoint) -> Result<SocketAddr, MPCNetError> {
+        Ok(endpoint.local_addr()?)
+    }
+
+    async fn accept(endpoint: &Endpoint) -> Result<QuicConnection, MPCNetError> {
+        let incoming = endpoint
+            .accept()
+            .await
+            .ok_or_else(|| io::Error::from(io::ErrorKind::ConnectionAborted))?;
+        let connection = incoming.await.map_err(io::Error::from)?;
+        // Opened by the dialing side, which talks first.
+        let (send, recv) = connection.accept_bi().await.map_err(io::Error::from)?;
+        Ok(QuicConnection {
+            connection,
+            control: QuicChannel { send, recv },
+        })
+    }
+
+    async fn connect(endpoint: &Endpoint, addr: SocketAddr) -> Result<QuicConnection, MPCNetError> {
+        let connection = endpoint
+            .connect(addr, SERVER_NAME)
+            .map_err(io::Error::other)?
+            .await
+            .map_err(io::Error::from)?;
+        let (send, recv) = connection.open_bi().await.map_err(io::Error::from)?;
+        Ok(QuicConnection {
+            connection,
+            control: QuicChannel { send, recv },
+        })
+    }
+
+    async fn split(
+        conn: QuicConnection,
+        channels: usize,
+        is_server: bool,
+    ) -> Result<(Vec<QuicChannel>, Closer), MPCNetError> {
+        let QuicConnection { connection, .. } = conn;
+        let mut ret = Vec::with_capacity(channels);
+        for _ in 0..channels {
+            // A stream only reaches the peer with its first byte, so the client sends one on
+            // every stream it opens. Streams are accepted in the order they were opened.
+            ret.push(if is_server {
+                let (send, mut recv) = connection.accept_bi().await.map_err(io::Error::from)?;
+                recv.read_u8().await?;
+                QuicChannel { send, recv }
+            } else {
+                let (mut send, recv) = connection.open_bi().await.map_err(io::Error::from)?;
+                send.write_u8(0).await?;
+                QuicChannel { send, recv }
+            });
+        }
+        let closer: Closer = Box::new(move || {
+            Box::pin(async move {
+                connection.close(VarInt::from_u32(0), b"closed");
+            })
+        });
+        Ok((ret, closer))
+    }
+}
+
+fn transport_config() -> TransportConfig {
+    let mut config = TransportConfig::default();
+    config
+        .keep_alive_interval(Some(KEEP_ALIVE))
+        .stream_receive_window(VarInt::from_u32(STREAM_WINDOW))
+        .receive_window(VarInt::from_u32(CONNECTION_WINDOW))
+        .send_window(CONNECTION_WINDOW as u64);
+    config
+}
+
+/// A connection to a peer, reading and writing the stream carrying the handshake.
+pub struct QuicConnection {
+    connection: Connection,
+    control: QuicChannel,
+}
+
+impl AsyncRead for QuicConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.control).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for QuicConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.control).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.control).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.control).poll_shutdown(cx)
+    }
+}
+
+/// A bidirectional QUIC stream.
+pub struct QuicChannel {
+    send: SendStream,
+    recv: RecvStream,
+}
+
+impl AsyncRead for QuicChannel {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.recv).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for QuicChannel {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.send)
+            .poll_write(cx, buf)
+            .map_err(io::Error::from)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.send).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.send).poll_shutdown(cx)
+    }
+}
+
+impl Drop for QuicChannel {
+    fn drop(&mut self) {
+        // A connection without streams is closed at once, dropping the data the peer did not
+        // acknowledge yet. Keep it open until the peer has everything, unlike a TCP socket.
+        let stopped = self.send.stopped();
+        let _ = self.send.finish();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(tokio::time::timeout(LINGER, stopped));
+        }
+    }
+}
+
+/// Accepts the certificate of any peer, checking only that it signed the handshake.
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_util::bytes::Bytes;
+
+    use super::Quic;
+    use crate::multi::CHUNK_SIZE;
+    use crate::{LocalTestNet, MPCNet, MultiplexedStreamID, WireConfig};
+
+    #[tokio::test]
+    async fn test_quic_loopback() {
+        const N_PARTIES: usize = 4;
+        let testnet =
+            LocalTestNet::<Quic>::new_local_testnet_with_transport(N_PARTIES, WireConfig::default())
+                .await
+                .unwrap();
+        let large: Bytes = (0..CHUNK_SIZE + 7).map(|i| i as u8).collect::<Vec<_>>().into();
+
+        testnet
+            .simulate_network_round(large, |net, large| async move {
+                let me = net.party_id() as u8;
+                let out = (0..N_PARTIES as u8)
+                    .map(|to| Bytes::from(vec![me * 16 + to; 1 << 16]))
+                    .collect();
+                let received = net.all_to_all(out, MultiplexedStreamID::Zero).await.unwrap();
+                for (from, bytes) in received.iter().enumerate() {
+                    assert!(bytes.iter().all(|b| *b == from as u8 * 16 + me));
+                }
+
+                // A large message on one stream does not hold back the others.
+                let sid = MultiplexedStreamID::One;
+                match me {
+                    0 => {
+                        let send = net.send_to(1, large.clone(), MultiplexedStreamID::Two);
+                        let small = async {
+                            net.send_to(1, Bytes::from_static(b"small"), sid).await.unwrap();
+                        };
+                        let (sent, ()) = tokio::join!(send, small);
+                        sent.unwrap();
+                    }
+                    1 => {
+                        assert_eq!(net.recv_from(0, sid).await.unwrap(), &b"small"[..]);
+                        let received = net.recv_from(0, MultiplexedStreamID::Two).await.unwrap();
+                        assert_eq!(received, large);
+                    }
+                    _ => {}
+                }
+
+                // Connections are restored over QUIC as well.
+                for i in 0..4u8 {
+                    if me == 0 && i % 2 == 1 {
+                        net.disconnect(3).await;
+                    }
+                    let received = net.leader_compute(&[i], sid, |v| v).await.unwrap();
+                    assert_eq!(received, Bytes::from(vec![i]));
+                }
+            })
+            .await;
+    }
+}
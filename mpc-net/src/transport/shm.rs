@@ -0,0 +1,548 @@
+use std::ffi::OsStr;
+use std::fs::{File, OpenOptions};
+use std::mem::size_of;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use async_trait::async_trait;
+use futures::task::AtomicWaker;
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+
+use super::unix::{bind, loopback_path, UnixEndpoint};
+use super::{strip_scheme, Transport, Unix};
+use crate::session::Closer;
+use crate::MPCNetError;
+
+/// Bytes a ring buffer holds, per channel and direction.
+const RING_CAPACITY: usize = 1 << 20;
+const RING_SIZE: usize = size_of::<RingHeader>() + RING_CAPACITY;
+/// Segments are named after this prefix, and only such segments are mapped.
+const SEGMENT_PREFIX: &str = "mpc-net-";
+
+/// Shared memory ring buffers, for parties on the same machine.
+///
+/// Parties connect over a Unix domain socket, as for [`Unix`], and addresses are paths, e.g.
+/// `shm:/tmp/party0.sock`. The dialing party then creates a segment with two ring buffers per
+/// channel, one for each direction, in `/dev/shm`. The socket only carries notifications for a
+/// peer waiting on a ring, and tells when the peer is gone.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Shm;
+
+#[async_trait]
+impl Transport for Shm {
+    type Endpoint = UnixEndpoint;
+    type Conn = UnixStream;
+    type Channel = ShmChannel;
+    type Addr = PathBuf;
+
+    fn parse_addr(addr: &str) -> Result<PathBuf, MPCNetError> {
+        Ok(strip_scheme(addr, "shm", true)?.into())
+    }
+
+    fn format_addr(addr: &PathBuf) -> String {
+        format!("shm:{}", addr.display())
+    }
+
+    fn loopback_addr() -> PathBuf {
+        loopback_path()
+    }
+
+    async fn bind(addr: PathBuf) -> Result<UnixEndpoint, MPCNetError> {
+        bind(addr)
+    }
+
+    fn local_addr(endpoint: &UnixEndpoint) -> Result<PathBuf, MPCNetError> {
+        Unix::local_addr(endpoint)
+    }
+
+    async fn accept(endpoint: &UnixEndpoint) -> Result<UnixStream, MPCNetError> {
+        Unix::accept(endpoint).await
+    }
+
+    async fn connect(endpoint: &UnixEndpoint, addr: PathBuf) -> Result<UnixStream, MPCNetError> {
+        Unix::connect(endpoint, addr).await
+    }
+
+    async fn split(
+        mut conn: UnixStream,
+        channels: usize,
+        is_server: bool,
+    ) -> Result<(Vec<ShmChannel>, Closer), MPCNetError> {
+        if channels * 2 > u8::MAX as usize {
+            return Err(MPCNetError::Config(format!("Cannot share {channels} channels")));
+        }
+        let len = channels * 2 * RING_SIZE;
+        let segment = if is_server {
+            open_segment(&mut conn, len).await?
+        } else {
+            create_segment(&mut conn, len).await?
+        };
+
+        let (doorbell, mut notifications) = mpsc::unbounded_channel::<u8>();
+        let shared = Arc::new(Shared {
+            segment,
+            wakers: (0..channels * 2).map(|_| AtomicWaker::new()).collect(),
+            doorbell,
+            hung_up: AtomicBool::new(false),
+        });
+        let (mut read_half, mut write_half) = conn.into_split();
+        let weak = Arc::downgrade(&shared);
+        let reader = tokio::spawn(async move {
+            let mut buf = [0u8; 64];
+            loop {
+                let n = read_half.read(&mut buf).await.unwrap_or(0);
+                let Some(shared) = weak.upgrade() else {
+                    return;
+                };
+                if n == 0 {
+                    shared.hang_up();
+                    return;
+                }
+                for ring in &buf[..n] {
+                    if let Some(waker) = shared.wakers.get(*ring as usize) {
+                        waker.wake();
+                    }
+                }
+            }
+        })
+        .abort_handle();
+        // Ends once all channels are dropped, hanging up on the peer.
+        let writer = tokio::spawn(async move {
+            let mut batch = Vec::new();
+            while let Some(ring) = notifications.recv().await {
+                batch.push(ring);
+                while let Ok(ring) = notifications.try_recv() {
+                    batch.push(ring);
+                }
+                if write_half.write_all(&batch).await.is_err() {
+                    return;
+                }
+                batch.clear();
+            }
+        })
+        .abort_handle();
+
+        let ret = (0..channels)
+            .map(|channel| ShmChannel {
+                shared: shared.clone(),
+                tx: 2 * channel + is_server as usize,
+                rx: 2 * channel + !is_server as usize,
+            })
+            .collect();
+        let weak = Arc::downgrade(&shared);
+        let closer: Closer = Box::new(move || {
+            Box::pin(async move {
+                reader.abort();
+                writer.abort();
+                if let Some(shared) = weak.upgrade() {
+                    shared.hang_up();
+                }
+            })
+        });
+        Ok((ret, closer))
+    }
+}
+
+/// Where segments are created, `/dev/shm` unless the system lacks it.
+fn segment_dir() -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    if shm.is_dir() {
+        shm.to_path_buf()
+    } else {
+        std::env::temp_dir()
+    }
+}
+
+/// Create a segment of `len` bytes and have the peer map it too, see [`open_segment`].
+async fn create_segment(conn: &mut UnixStream, len: usize) -> Result<Segment, MPCNetError> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let name = format!("{SEGMENT_PREFIX}{}-{n}.shm", std::process::id());
+    let path = segment_dir().join(&name);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    let shared = async {
+        file.set_len(len as u64)?;
+        let segment = Segment::map(&file, len)?;
+        conn.write_u16(name.len() as u16).await?;
+        conn.write_all(name.as_bytes()).await?;
+        conn.read_u8().await?;
+        Ok(segment)
+    };
+    let segment = shared.await;
+    // Both parties mapped the segment, or gave up. It is freed once they unmap it.
+    let _ = std::fs::remove_file(&path);
+    segment
+}
+
+/// Map the segment of `len` bytes created by the peer.
+async fn open_segment(conn: &mut UnixStream, len: usize) -> Result<Segment, MPCNetError> {
+    let mut name = vec![0u8; conn.read_u16().await? as usize];
+    conn.read_exact(&mut name).await?;
+    let name = OsStr::from_bytes(&name);
+    let path = Path::new(name);
+    if path.components().count() != 1 || !name.as_bytes().starts_with(SEGMENT_PREFIX.as_bytes()) {
+        return Err(MPCNetError::Config(format!(
+            "Peer shares the unexpected segment {path:?}"
+        )));
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(segment_dir().join(path))?;
+    if file.metadata()?.len() != len as u64 {
+        return Err(MPCNetError::Config(format!(
+            "Peer shares {} bytes, expected {len}",
+            file.metadata()?.len()
+        )));
+    }
+    let segment = Segment::map(&file, len)?;
+    conn.write_u8(1).await?;
+    Ok(segment)
+}
+
+#[repr(C, align(64))]
+struct CacheLine<T>(T);
+
+/// At the start of every ring. A new segment is zeroed, an empty ring.
+#[repr(C)]
+struct RingHeader {
+    /// Bytes written so far, advanced by the writer.
+    head: CacheLine<AtomicU64>,
+    /// Bytes read so far, advanced by the reader.
+    tail: CacheLine<AtomicU64>,
+    flags: CacheLine<RingFlags>,
+}
+
+#[repr(C)]
+struct RingFlags {
+    /// The reader waits for data and has to be notified.
+    reader_waiting: AtomicBool,
+    /// The writer waits for space and has to be notified.
+    writer_waiting: AtomicBool,
+    /// The writer is done, the reader sees the end of the stream after the data left.
+    closed: AtomicBool,
+    /// The reader is gone, writing fails.
+    abandoned: AtomicBool,
+}
+
+/// Shared memory mapped into this process.
+struct Segment {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// The segment is only accessed through atomics and the ring protocol.
+unsafe impl Send for Segment {}
+unsafe impl Sync for Segment {}
+
+impl Segment {
+    fn map(file: &File, len: usize) -> io::Result<Self> {
+        // SAFETY: maps a new region, not aliasing any memory of this process.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let ptr = NonNull::new(ptr.cast()).ok_or_else(io::Error::last_os_error)?;
+        Ok(Self { ptr, len })
+    }
+
+    fn header(&self, ring: usize) -> &RingHeader {
+        debug_assert!((ring + 1) * RING_SIZE <= self.len);
+        // SAFETY: the ring is within the mapping, page aligned rings of a size multiple of 64.
+        unsafe { &*self.ptr.as_ptr().add(ring * RING_SIZE).cast() }
+    }
+
+    fn data(&self, ring: usize) -> *mut u8 {
+        // SAFETY: within the mapping, see `header`.
+        unsafe {
+            self.ptr
+                .as_ptr()
+                .add(ring * RING_SIZE + size_of::<RingHeader>())
+        }
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        // SAFETY: mapped by `map`, and no references outlive the segment.
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+    }
+}
+
+/// The state of a connection, shared by its channels.
+struct Shared {
+    segment: Segment,
+    /// Woken on notifications from the peer, one per ring: a party either reads or writes it.
+    wakers: Vec<AtomicWaker>,
+    /// Notifications for the peer, the rings to look at.
+    doorbell: mpsc::UnboundedSender<u8>,
+    /// The peer hung up, or the connection was closed.
+    hung_up: AtomicBool,
+}
+
+impl Shared {
+    fn hang_up(&self) {
+        self.hung_up.store(true, Ordering::SeqCst);
+        for waker in &self.wakers {
+            waker.wake();
+        }
+    }
+
+    fn notify(&self, ring: usize) {
+        let _ = self.doorbell.send(ring as u8);
+    }
+}
+
+fn broken_pipe() -> io::Error {
+    io::Error::from(io::ErrorKind::BrokenPipe)
+}
+
+/// Bytes between the `tail` and the `head` of a ring. They are checked, as the peer may have
+/// corrupted them.
+fn ring_len(head: u64, tail: u64) -> io::Result<usize> {
+    let len = head.wrapping_sub(tail);
+    if len > RING_CAPACITY as u64 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupted ring buffer"));
+    }
+    Ok(len as usize)
+}
+
+/// A channel of a [`Shm`] connection, writing one ring and reading another.
+pub struct ShmChannel {
+    shared: Arc<Shared>,
+    tx: usize,
+    rx: usize,
+}
+
+impl ShmChannel {
+    /// Mark the ring this channel writes as done.
+    fn close(&self) {
+        let flags = &self.shared.segment.header(self.tx).flags.0;
+        flags.closed.store(true, Ordering::SeqCst);
+        if flags.reader_waiting.swap(false, Ordering::SeqCst) {
+            self.shared.notify(self.tx);
+        }
+    }
+}
+
+impl AsyncRead for ShmChannel {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let shared = &self.shared;
+        let ring = shared.segment.header(self.rx);
+        let flags = &ring.flags.0;
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let tail = ring.tail.0.load(Ordering::Relaxed);
+        let mut head = ring.head.0.load(Ordering::Acquire);
+        if head == tail {
+            shared.wakers[self.rx].register(cx.waker());
+            flags.reader_waiting.store(true, Ordering::SeqCst);
+            // The writer sets `closed` after writing its last bytes.
+            let closed = flags.closed.load(Ordering::SeqCst);
+            head = ring.head.0.load(Ordering::SeqCst);
+            if head == tail {
+                return if closed {
+                    Poll::Ready(Ok(()))
+                } else if shared.hung_up.load(Ordering::SeqCst) {
+                    Poll::Ready(Err(broken_pipe()))
+                } else {
+                    Poll::Pending
+                };
+            }
+            flags.reader_waiting.store(false, Ordering::Relaxed);
+        }
+
+        let n = ring_len(head, tail)?.min(buf.remaining());
+        let offset = tail as usize % RING_CAPACITY;
+        let first = n.min(RING_CAPACITY - offset);
+        let data = shared.segment.data(self.rx);
+        // SAFETY: the writer does not touch the bytes between `tail` and `head`.
+        unsafe {
+            buf.put_slice(std::slice::from_raw_parts(data.add(offset), first));
+            buf.put_slice(std::slice::from_raw_parts(data, n - first));
+        }
+        ring.tail.0.store(tail.wrapping_add(n as u64), Ordering::SeqCst);
+        if flags.writer_waiting.swap(false, Ordering::SeqCst) {
+            shared.notify(self.rx);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ShmChannel {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let shared = &self.shared;
+        let ring = shared.segment.header(self.tx);
+        let flags = &ring.flags.0;
+        if flags.closed.load(Ordering::Relaxed) || flags.abandoned.load(Ordering::SeqCst) {
+            return Poll::Ready(Err(broken_pipe()));
+        }
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let head = ring.head.0.load(Ordering::Relaxed);
+        let mut tail = ring.tail.0.load(Ordering::Acquire);
+        if ring_len(head, tail)? == RING_CAPACITY {
+            shared.wakers[self.tx].register(cx.waker());
+            flags.writer_waiting.store(true, Ordering::SeqCst);
+            tail = ring.tail.0.load(Ordering::SeqCst);
+            if ring_len(head, tail)? == RING_CAPACITY {
+                return if shared.hung_up.load(Ordering::SeqCst)
+                    || flags.abandoned.load(Ordering::SeqCst)
+                {
+                    Poll::Ready(Err(broken_pipe()))
+                } else {
+                    Poll::Pending
+                };
+            }
+            flags.writer_waiting.store(false, Ordering::Relaxed);
+        }
+
+        let n = (RING_CAPACITY - ring_len(head, tail)?).min(buf.len());
+        let offset = head as usize % RING_CAPACITY;
+        let first = n.min(RING_CAPACITY - offset);
+        let data = shared.segment.data(self.tx);
+        // SAFETY: the reader does not touch the bytes from `head` on until it is advanced.
+        unsafe {
+            std::ptr::copy_nonoverlapping(buf.as_ptr(), data.add(offset), first);
+            std::ptr::copy_nonoverlapping(buf.as_ptr().add(first), data, n - first);
+        }
+        ring.head.0.store(head.wrapping_add(n as u64), Ordering::SeqCst);
+        if flags.reader_waiting.swap(false, Ordering::SeqCst) {
+            shared.notify(self.tx);
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for ShmChannel {
+    fn drop(&mut self) {
+        self.close();
+        let flags = &self.shared.segment.header(self.rx).flags.0;
+        flags.abandoned.store(true, Ordering::SeqCst);
+        if flags.writer_waiting.swap(false, Ordering::SeqCst) {
+            self.shared.notify(self.rx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixStream;
+    use tokio_util::bytes::Bytes;
+
+    use super::{segment_dir, Shm, RING_CAPACITY, SEGMENT_PREFIX};
+    use crate::transport::Transport;
+    use crate::{LocalTestNet, MPCNet, MultiplexedStreamID, WireConfig};
+
+    #[tokio::test]
+    async fn test_shm_loopback() {
+        const N_PARTIES: usize = 4;
+        let testnet =
+            LocalTestNet::<Shm>::new_local_testnet_with_transport(N_PARTIES, WireConfig::default())
+                .await
+                .unwrap();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                let me = net.party_id() as u8;
+                // Larger than a ring, so writers wait for readers and the rings wrap around.
+                let out = (0..N_PARTIES as u8)
+                    .map(|to| Bytes::from(vec![me * 16 + to; 3 * RING_CAPACITY + 5]))
+                    .collect();
+                let received = net.all_to_all(out, MultiplexedStreamID::Zero).await.unwrap();
+                for (from, bytes) in received.iter().enumerate() {
+                    assert_eq!(bytes.len(), 3 * RING_CAPACITY + 5);
+                    assert!(bytes.iter().all(|b| *b == from as u8 * 16 + me));
+                }
+
+                let sid = MultiplexedStreamID::One;
+                for i in 0..4u8 {
+                    if me == 0 && i % 2 == 1 {
+                        net.disconnect(3).await;
+                    }
+                    let received = net.leader_compute(&[i], sid, |v| v).await.unwrap();
+                    assert_eq!(received, Bytes::from(vec![i]));
+                }
+            })
+            .await;
+
+        // Segments are unlinked once mapped.
+        let prefix = format!("{SEGMENT_PREFIX}{}-", std::process::id());
+        let left = std::fs::read_dir(segment_dir())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_string_lossy().starts_with(&prefix)
+            })
+            .count();
+        assert_eq!(left, 0);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_ring() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let (dialer, accepter) = tokio::join!(Shm::split(a, 1, false), Shm::split(b, 1, true));
+        let (mut a, _) = dialer.unwrap();
+        let (mut b, _) = accepter.unwrap();
+        let (mut a, mut b) = (a.pop().unwrap(), b.pop().unwrap());
+        a.write_all(b"ok").await.unwrap();
+        let mut buf = [0u8; 2];
+        b.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ok");
+
+        // A head more than a ring ahead of the tail, or behind it, is rejected.
+        for head in [2 + RING_CAPACITY as u64 + 1, 1] {
+            let ring = b.shared.segment.header(b.rx);
+            ring.head.0.store(head, Ordering::SeqCst);
+            let err = b.read(&mut buf).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+            let err = a.write(b"x").await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::net::SocketAddr;
+
+use async_smux::{MuxBuilder, MuxStream};
+use async_trait::async_trait;
+use tokio::io::{self, AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+
+use super::{parse_socket_addr, Transport};
+use crate::session::Closer;
+use crate::MPCNetError;
+
+/// TCP, the channels multiplexed over a single stream with `async_smux`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tcp;
+
+#[async_trait]
+impl Transport for Tcp {
+    type Endpoint = TcpListener;
+    type Conn = TcpStream;
+    type Channel = MuxStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    fn parse_addr(addr: &str) -> Result<SocketAddr, MPCNetError> {
+        parse_socket_addr(addr, "tcp")
+    }
+
+    fn format_addr(addr: &SocketAddr) -> String {
+        addr.to_string()
+    }
+
+    fn loopback_addr() -> SocketAddr {
+        ([127, 0, 0, 1], 0).into()
+    }
+
+    async fn bind(addr: SocketAddr) -> Result<TcpListener, MPCNetError> {
+        Ok(TcpListener::bind(addr).await?)
+    }
+
+    fn local_addr(endpoint: &TcpListener) -> Result<SocketAddr, MPCNetError> {
+        Ok(endpoint.local_addr()?)
+    }
+
+    async fn accept(endpoint: &TcpListener) -> Result<TcpStream, MPCNetError> {
+        let (stream, _) = endpoint.accept().await?;
+        Ok(stream)
+    }
+
+    async fn connect(_: &TcpListener, addr: SocketAddr) -> Result<TcpStream, MPCNetError> {
+        Ok(TcpStream::connect(addr).await?)
+    }
+
+    async fn split(
+        conn: TcpStream,
+        channels: usize,
+        is_server: bool,
+    ) -> Result<(Vec<MuxStream<TcpStream>>, Closer), MPCNetError> {
+        multiplex(channels, is_server, conn).await
+    }
+}
+
+/// Multiplex `channels` streams over `stream`.
+pub(crate) async fn multiplex<T: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
+    channels: usize,
+    is_server: bool,
+    stream: T,
+) -> Result<(Vec<MuxStream<T>>, Closer), MPCNetError> {
+    let mut ret = Vec::new();
+    let (mut connector, mut acceptor, worker) = if is_server {
+        MuxBuilder::server().with_connection(stream).build()
+    } else {
+        MuxBuilder::client().with_connection(stream).build()
+    };
+    let worker = tokio::spawn(worker).abort_handle();
+    for _ in 0..channels {
+        ret.push(if is_server {
+            acceptor
+                .accept()
+                .await
+                .ok_or_else(|| io::Error::from(io::ErrorKind::ConnectionAborted))?
+        } else {
+            connector.connect().map_err(io::Error::other)?
+        });
+    }
+    // The streams share the connection, closing it fails them all.
+    let closer: Closer = Box::new(move || {
+        Box::pin(async move {
+            worker.abort();
+            let _ = connector.close().await;
+        })
+    });
+    Ok((ret, closer))
+}
@@ -0,0 +1,143 @@
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_smux::MuxStream;
+use async_trait::async_trait;
+use tokio::io;
+use tokio::net::{UnixListener, UnixStream};
+
+use super::{multiplex, strip_scheme, Transport};
+use crate::session::Closer;
+use crate::MPCNetError;
+
+/// Unix domain sockets, the channels multiplexed over a single stream like for [`super::Tcp`].
+///
+/// Addresses are paths, e.g. `unix:/tmp/party0.sock`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Unix;
+
+/// A listening Unix domain socket, removed when dropped.
+#[derive(Debug)]
+pub struct UnixEndpoint {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Drop for UnixEndpoint {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[async_trait]
+impl Transport for Unix {
+    type Endpoint = UnixEndpoint;
+    type Conn = UnixStream;
+    type Channel = MuxStream<UnixStream>;
+    type Addr = PathBuf;
+
+    fn parse_addr(addr: &str) -> Result<PathBuf, MPCNetError> {
+        Ok(strip_scheme(addr, "unix", true)?.into())
+    }
+
+    fn format_addr(addr: &PathBuf) -> String {
+        format!("unix:{}", addr.display())
+    }
+
+    fn loopback_addr() -> PathBuf {
+        loopback_path()
+    }
+
+    async fn bind(addr: PathBuf) -> Result<UnixEndpoint, MPCNetError> {
+        bind(addr)
+    }
+
+    fn local_addr(endpoint: &UnixEndpoint) -> Result<PathBuf, MPCNetError> {
+        Ok(endpoint.path.clone())
+    }
+
+    async fn accept(endpoint: &UnixEndpoint) -> Result<UnixStream, MPCNetError> {
+        let (stream, _) = endpoint.listener.accept().await?;
+        Ok(stream)
+    }
+
+    async fn connect(_: &UnixEndpoint, addr: PathBuf) -> Result<UnixStream, MPCNetError> {
+        Ok(UnixStream::connect(addr).await?)
+    }
+
+    async fn split(
+        conn: UnixStream,
+        channels: usize,
+        is_server: bool,
+    ) -> Result<(Vec<MuxStream<UnixStream>>, Closer), MPCNetError> {
+        multiplex(channels, is_server, conn).await
+    }
+}
+
+/// A fresh socket path in the temporary directory.
+pub(super) fn loopback_path() -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("mpc-net-{}-{}.sock", std::process::id(), n))
+}
+
+/// Listen at `path`, replacing the socket a previous run left behind.
+pub(super) fn bind(path: PathBuf) -> Result<UnixEndpoint, MPCNetError> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    remove_stale(&path)?;
+    let listener = UnixListener::bind(&path)?;
+    Ok(UnixEndpoint { listener, path })
+}
+
+/// Remove the socket at `path`, leaving alone anything else found there.
+fn remove_stale(path: &Path) -> io::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path),
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio_util::bytes::Bytes;
+
+    use super::Unix;
+    use crate::{LocalTestNet, MPCNet, MultiplexedStreamID, WireConfig};
+
+    #[tokio::test]
+    async fn test_unix_loopback() {
+        let testnet = LocalTestNet::<Unix>::new_local_testnet_with_transport(3, WireConfig::default())
+            .await
+            .unwrap();
+        let paths = (0..3)
+            .map(|id| testnet.get_connection(id).peers[&(id as u32)].listen_addr.clone())
+            .collect::<Vec<_>>();
+
+        testnet
+            .simulate_network_round((), |net, _| async move {
+                let me = net.party_id() as u8;
+                let sid = MultiplexedStreamID::Two;
+                let sum = net
+                    .leader_compute(&[me], sid, |v| {
+                        let sum = v.iter().map(|b| b[0]).sum::<u8>();
+                        vec![Bytes::from(vec![sum]); v.len()]
+                    })
+                    .await
+                    .unwrap();
+                assert_eq!(sum, Bytes::from(vec![3]));
+                if me == 0 {
+                    net.disconnect(2).await;
+                }
+                let received = net.leader_compute(&[me], sid, |v| v).await.unwrap();
+                assert_eq!(received, Bytes::from(vec![me]));
+            })
+            .await;
+
+        // Sockets are removed with the parties.
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
+}
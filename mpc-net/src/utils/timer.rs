@@ -1,5 +1,7 @@
 // This part comes from ark. Add a few tweaks to accommodate to multithread environment.
-// The indent still don't quite work for multithread environment. In fact I'm not sure how such thing can be illustrated linearly in multithread environment.
+// The indent follows the timers open in the current task, see `crate::profile`, and every timer is
+// also recorded as a span there. With the allocations tracked, see `crate::memory`, the end of a
+// timer also prints the peak and current allocation of its phase.
 pub use colored::Colorize;
 
 use std::cmp::min;
@@ -9,10 +11,12 @@ pub use std::{
     string::{String, ToString},
     sync::atomic::{AtomicUsize, Ordering},
     thread,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-pub static NUM_INDENT: AtomicUsize = AtomicUsize::new(0);
+use crate::memory::mib;
+use crate::profile::{close_timer, open_timer, TimerHandle};
+
 pub const PAD_CHAR: &str = "·";
 
 pub struct TimerInfo {
@@ -20,133 +24,43 @@ pub struct TimerInfo {
     pub time: Instant,
     pub print: bool,
     pub indent: usize,
+    pub handle: TimerHandle,
 }
 
-#[cfg(not(feature = "report"))]
-#[macro_export]
-macro_rules! start_timer {
-    ($msg:expr) => {{
-        start_timer!($msg, true)
-    }};
-    ($msg:expr, $print:expr) => {{
-        use $crate::utils::timer::{
-            compute_indent, thread, Colorize, Instant, Ordering, ToString, NUM_INDENT,
-        };
-
-        let msg = $msg;
-        if !$print {
-            $crate::utils::timer::TimerInfo {
-                msg: msg.to_string(),
-                time: Instant::now(),
-                print: $print,
-                indent: 0,
-            }
-        } else {
-            let start_info = "Start:".yellow().bold();
-            let indent_amount = NUM_INDENT.fetch_add(1, Ordering::Relaxed);
-            let indent = compute_indent(indent_amount);
-
-            let msg = format!("{} (thread {:?})", &msg, thread::current().id());
-
-            println!("{}{:8} {}", indent, start_info, msg);
-            $crate::utils::timer::TimerInfo {
-                msg: msg.to_string(),
-                time: Instant::now(),
-                print: $print,
-                indent: indent_amount
-            }
-        }
-    }};
-}
-
-#[cfg(feature = "report")]
-#[macro_export]
-macro_rules! start_timer {
-    ($msg:expr) => {{
-        start_timer!($msg, true)
-    }};
-    ($msg:expr, $print:expr) => {{
-        use $crate::utils::timer::{
-            compute_indent, thread, Colorize, Instant, Ordering, ToString, NUM_INDENT,
-        };
-
-        let msg = $msg;
-        let start_info = "Start:".yellow().bold();
-        let indent_amount = NUM_INDENT.fetch_add(1, Ordering::Relaxed);
-        let indent = compute_indent(indent_amount);
-
-        let msg = format!("{} (thread {:?})", &msg, thread::current().id());
-
-        println!("{}{:8} {}", indent, start_info, msg);
-        $crate::utils::timer::TimerInfo {
-            msg: msg.to_string(),
+/// Open a timer, printing its start if `print` is set or with the `report` feature.
+pub fn start(msg: String, print: bool) -> TimerInfo {
+    let print = print || cfg!(feature = "report");
+    let handle = open_timer(&msg, print);
+    if !print {
+        return TimerInfo {
+            msg,
             time: Instant::now(),
-            print: $print,
-            indent: indent_amount,
-        }
-    }};
-}
-
-#[cfg(not(feature = "report"))]
-#[macro_export]
-macro_rules! end_timer {
-    ($time:expr) => {{
-        end_timer!($time, "")
-    }};
-    ($time:expr, $msg:expr) => {{
-        use $crate::utils::timer::{compute_indent, format, Colorize, Ordering, NUM_INDENT};
-
-        let time = $time.time;
-        let final_time = time.elapsed();
-        if $time.print {
-            let final_time_str = {
-                let secs = final_time.as_secs();
-                let millis = final_time.subsec_millis();
-                let micros = final_time.subsec_micros() % 1000;
-                let nanos = final_time.subsec_nanos() % 1000;
-                if secs != 0 {
-                    format!("{}.{:03}s", secs, millis).bold()
-                } else if millis > 0 {
-                    format!("{}.{:03}ms", millis, micros).bold()
-                } else if micros > 0 {
-                    format!("{}.{:03}µs", micros, nanos).bold()
-                } else {
-                    format!("{}ns", final_time.subsec_nanos()).bold()
-                }
-            };
-
-            let end_info = "End:".green().bold();
-            let message = format!("{} {}", $time.msg, $msg);
-            NUM_INDENT.fetch_sub(1, Ordering::Relaxed);
-            let indent_amount = $time.indent;
-            let indent = compute_indent(indent_amount);
-
-            // Todo: Recursively ensure that *entire* string is of appropriate
-            // width (not just message).
-            println!(
-                "{}{:8} {:.<pad$}{}",
-                indent,
-                end_info,
-                message,
-                final_time_str,
-                pad = 5
-            );
-        }
-        final_time
-    }};
+            print,
+            indent: 0,
+            handle,
+        };
+    }
+    let start_info = "Start:".yellow().bold();
+    let indent_amount = handle.depth;
+    let indent = compute_indent(indent_amount);
+
+    let msg = format!("{} (thread {:?})", msg, thread::current().id());
+
+    println!("{}{:8} {}", indent, start_info, msg);
+    TimerInfo {
+        msg,
+        time: Instant::now(),
+        print,
+        indent: indent_amount,
+        handle,
+    }
 }
 
-#[cfg(feature = "report")]
-#[macro_export]
-macro_rules! end_timer {
-    ($time:expr) => {{
-        end_timer!($time, "")
-    }};
-    ($time:expr, $msg:expr) => {{
-        use $crate::utils::timer::{compute_indent, format, Colorize, Ordering, NUM_INDENT};
-
-        let time = $time.time;
-        let final_time = time.elapsed();
+/// Close a timer, printing its time after `msg` if its start was printed.
+pub fn end(timer: TimerInfo, msg: &str) -> Duration {
+    let final_time = timer.time.elapsed();
+    let memory = close_timer(timer.handle);
+    if timer.print {
         let final_time_str = {
             let secs = final_time.as_secs();
             let millis = final_time.subsec_millis();
@@ -164,22 +78,45 @@ macro_rules! end_timer {
         };
 
         let end_info = "End:".green().bold();
-        let message = format!("{} {}", $time.msg, $msg);
-        NUM_INDENT.fetch_sub(1, Ordering::Relaxed);
-        let indent_amount = $time.indent;
-        let indent = compute_indent(indent_amount);
+        let message = format!("{} {}", timer.msg, msg);
+        let indent = compute_indent(timer.indent);
 
         // Todo: Recursively ensure that *entire* string is of appropriate
         // width (not just message).
+        let memory = memory.map_or(String::new(), |usage| {
+            format!(" [peak {:.3} MiB, current {:.3} MiB]", mib(usage.peak), mib(usage.end))
+        });
         println!(
-            "{}{:8} {:.<pad$}{}",
+            "{}{:8} {:.<pad$}{}{}",
             indent,
             end_info,
             message,
             final_time_str,
+            memory,
             pad = 5
         );
-        final_time
+    }
+    final_time
+}
+
+#[macro_export]
+macro_rules! start_timer {
+    ($msg:expr) => {{
+        start_timer!($msg, true)
+    }};
+    ($msg:expr, $print:expr) => {{
+        use $crate::utils::timer::ToString;
+        $crate::utils::timer::start(($msg).to_string(), $print)
+    }};
+}
+
+#[macro_export]
+macro_rules! end_timer {
+    ($time:expr) => {{
+        end_timer!($time, "")
+    }};
+    ($time:expr, $msg:expr) => {{
+        $crate::utils::timer::end($time, &$crate::utils::timer::format!("{}", $msg))
     }};
 }
 
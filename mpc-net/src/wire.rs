@@ -0,0 +1,154 @@
+//! Wire format options, negotiated when connections are established.
+//!
+//! Every party announces its [`WireConfig`] in the connection handshake. Parties running different
+//! versions of the frame format refuse to connect. Group points are sent uncompressed only if all
+//! parties ask for it, since the serialized bytes of an element are often shared by several peers.
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::MPCNetError;
+
+/// Version of the frame format, bumped on incompatible changes.
+pub const WIRE_VERSION: u8 = 2;
+
+const COALESCE: u8 = 1;
+const UNCOMPRESSED: u8 = 2;
+const ENVELOPES: u8 = 4;
+
+/// How group points and field elements are serialized by `MPCSerializeNet`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PointEncoding {
+    /// Smallest encoding. Decoding a point costs a square root.
+    #[default]
+    Compressed,
+    /// About twice as large for points, but much faster to decode.
+    Uncompressed,
+}
+
+/// Wire options of a party.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WireConfig {
+    /// Hold back small messages so that those sent in a burst, e.g. within a round, are sent to
+    /// the peer as a single frame. Sends then return before the bytes are written, so write errors
+    /// show up on a later operation.
+    pub coalesce: bool,
+    /// Preferred encoding, used only if all parties prefer it.
+    pub point_encoding: PointEncoding,
+    /// Check every message against a header, see [`crate::envelope`]. Used only if all parties
+    /// ask for it.
+    pub envelopes: bool,
+}
+
+impl WireConfig {
+    pub fn with_coalescing(mut self, coalesce: bool) -> Self {
+        self.coalesce = coalesce;
+        self
+    }
+
+    pub fn with_point_encoding(mut self, point_encoding: PointEncoding) -> Self {
+        self.point_encoding = point_encoding;
+        self
+    }
+
+    pub fn with_envelopes(mut self, envelopes: bool) -> Self {
+        self.envelopes = envelopes;
+        self
+    }
+
+    /// Announce the configuration to a peer.
+    pub async fn write<W: AsyncWrite + Unpin>(&self, stream: &mut W) -> Result<(), MPCNetError> {
+        let mut flags = 0;
+        if self.coalesce {
+            flags |= COALESCE;
+        }
+        if self.point_encoding == PointEncoding::Uncompressed {
+            flags |= UNCOMPRESSED;
+        }
+        if self.envelopes {
+            flags |= ENVELOPES;
+        }
+        stream.write_all(&[WIRE_VERSION, flags]).await?;
+        Ok(())
+    }
+
+    /// Read the configuration announced by a peer.
+    pub async fn read<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Self, MPCNetError> {
+        let mut buf = [0u8; 2];
+        stream.read_exact(&mut buf).await?;
+        let [version, flags] = buf;
+        if version != WIRE_VERSION {
+            return Err(MPCNetError::Config(format!(
+                "Peer uses wire version {}, expected {}",
+                version, WIRE_VERSION
+            )));
+        }
+        Ok(Self {
+            coalesce: flags & COALESCE != 0,
+            point_encoding: if flags & UNCOMPRESSED != 0 {
+                PointEncoding::Uncompressed
+            } else {
+                PointEncoding::Compressed
+            },
+            envelopes: flags & ENVELOPES != 0,
+        })
+    }
+
+    /// The encoding all parties agree on, given the configurations of all of them.
+    pub fn negotiate_point_encoding<'a>(
+        configs: impl IntoIterator<Item = &'a WireConfig>,
+    ) -> PointEncoding {
+        if configs
+            .into_iter()
+            .all(|c| c.point_encoding == PointEncoding::Uncompressed)
+        {
+            PointEncoding::Uncompressed
+        } else {
+            PointEncoding::Compressed
+        }
+    }
+
+    /// Whether messages carry envelopes, given the configurations of all parties.
+    pub fn negotiate_envelopes<'a>(configs: impl IntoIterator<Item = &'a WireConfig>) -> bool {
+        configs.into_iter().all(|c| c.envelopes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PointEncoding, WireConfig, WIRE_VERSION};
+    use crate::MPCNetError;
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let (mut a, mut b) = tokio::io::duplex(64);
+        let config = WireConfig::default()
+            .with_coalescing(true)
+            .with_point_encoding(PointEncoding::Uncompressed)
+            .with_envelopes(true);
+        config.write(&mut a).await.unwrap();
+        assert_eq!(WireConfig::read(&mut b).await.unwrap(), config);
+
+        tokio::io::AsyncWriteExt::write_all(&mut a, &[WIRE_VERSION + 1, 0])
+            .await
+            .unwrap();
+        assert!(matches!(
+            WireConfig::read(&mut b).await,
+            Err(MPCNetError::Config(_))
+        ));
+
+        // A peer hanging up during the handshake is a transport error.
+        drop(a);
+        let err = WireConfig::read(&mut b).await.unwrap_err();
+        assert!(matches!(err, MPCNetError::Io(_)));
+        assert!(std::error::Error::source(&err).is_some());
+
+        let uncompressed = WireConfig::default().with_point_encoding(PointEncoding::Uncompressed);
+        assert_eq!(
+            WireConfig::negotiate_point_encoding(&[uncompressed, uncompressed]),
+            PointEncoding::Uncompressed
+        );
+        assert_eq!(
+            WireConfig::negotiate_point_encoding(&[uncompressed, WireConfig::default()]),
+            PointEncoding::Compressed
+        );
+    }
+}
@@ -98,18 +98,12 @@ impl<F: FftField> PackedSharingParams<F> {
         self.share.fft_in_place(secrets);
     }
 
-    /// Packs secret into shares in place
+    /// Packs `secret` into every slot. The packing is deterministic and public, anyone can unpack
+    /// a single share, so it must not be used where the secret has to stay hidden, see
+    /// [`PackedSharingParams::pack_from_public_rand`].
     #[allow(unused)]
     pub fn pack_single<G: DomainCoeff<F>>(&self, secret: G) -> Vec<G> {
-        // interpolating on secrets domain
-        let mut secrets = vec![secret];
-        self.secret.ifft_in_place(&mut secrets);
-
-        // evaluate on share domain
-        self.share.fft_in_place(&mut secrets);
-
-        self.pack_from_public_in_place(&mut secrets);
-        secrets
+        self.pack_from_public(vec![secret; self.l])
     }
 
     /// Unpacks shares of degree t+l into secrets
@@ -169,6 +163,26 @@ impl<F: FftField> PackedSharingParams<F> {
         // WTF this is too ugly
         *shares = shares[0..2 * self.l].iter().step_by(2).copied().collect();
     }
+
+    /// Unpacks shares of a product of `factors` sharings, of degree factors*(t+l), into secrets
+    pub fn unpack_product<G: DomainCoeff<F>>(&self, mut shares: Vec<G>, factors: usize) -> Vec<G> {
+        debug_assert!(shares.len() == self.n, "Shares length mismatch");
+        assert!(factors * (self.t + self.l) < self.n, "Too many factors to unpack");
+        match factors {
+            0 | 1 => self.unpack_in_place(&mut shares),
+            2 => self.unpack2_in_place(&mut shares),
+            _ => {
+                // interpolating on share domain
+                self.share.ifft_in_place(&mut shares);
+                // evaluate on the coset of the share domain, every n/(t+l+1)-th point is a secret
+                let coset = self.share.get_coset(F::GENERATOR).unwrap();
+                coset.fft_in_place(&mut shares);
+                let step = self.n / self.secret.size();
+                shares = shares.into_iter().step_by(step).take(self.l).collect();
+            }
+        }
+        shares
+    }
 }
 
 // Tests
@@ -215,6 +229,18 @@ mod tests {
         assert_eq!(expected, secrets);
     }
 
+    #[test]
+    fn test_pack_single() {
+        let pp = PackedSharingParams::<F>::new(L);
+
+        let rng = &mut ark_std::test_rng();
+        let secret = F::rand(rng);
+
+        let shares = pp.pack_single(secret);
+        assert_eq!(shares.len(), N);
+        assert_eq!(pp.unpack(shares), vec![secret; L]);
+    }
+
     #[test]
     fn test_multiplication() {
         let pp = PackedSharingParams::<F>::new(L);
@@ -286,6 +312,24 @@ mod tests {
 
         assert_eq!(expected, shares);
     }
+    #[test]
+    fn test_unpack_product() {
+        let pp = PackedSharingParams::<F>::new(L);
+
+        let rng = &mut ark_std::test_rng();
+        let mut expected = vec![F::from(1u64); L];
+        let mut shares = vec![F::from(1u64); N];
+        for factors in 1..=4 {
+            let secrets: [F; L] = UniformRand::rand(rng);
+            let secrets = secrets.to_vec();
+            expected.iter_mut().zip(&secrets).for_each(|(e, s)| *e *= s);
+            let packed = pp.pack_from_public_rand(secrets);
+            shares.iter_mut().zip(&packed).for_each(|(share, p)| *share *= p);
+
+            assert_eq!(expected, pp.unpack_product(shares.clone(), factors));
+        }
+    }
+
     #[bench]
     fn bench_packing(b: &mut Bencher) {
         let pp = PackedSharingParams::<F>::new(L);
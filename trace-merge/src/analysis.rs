@@ -0,0 +1,359 @@
+//! Aligns the parties by protocol round and finds where the time goes.
+//!
+//! A round is a `Comm:` timer that is not inside another one, and the k-th round of every party
+//! is taken to be the same exchange. A party arriving at a round waits until the last party, the
+//! straggler, arrives too, so the time of a round up to that release is idle. The critical path
+//! walks back from the party ending last: through its own rounds, and through the straggler of
+//! every round it waited in.
+use std::fmt::Write;
+
+use serde_json::{json, Value};
+
+use crate::trace::{PartyTrace, Span};
+
+/// The prefix of the timers of the collectives.
+pub const COMM_PREFIX: &str = "Comm:";
+
+/// One round, times in microseconds, indexed like the parties.
+#[derive(Clone, Debug)]
+pub struct Round {
+    pub index: usize,
+    /// As named by the first party.
+    pub name: String,
+    pub arrivals: Vec<f64>,
+    pub ends: Vec<f64>,
+    /// When the last party arrived.
+    pub release: f64,
+    /// The index of the last party to arrive.
+    pub straggler: usize,
+    pub waits: Vec<f64>,
+}
+
+impl Round {
+    /// From the first to the last arrival.
+    pub fn spread(&self) -> f64 {
+        self.release - self.arrivals.iter().copied().fold(f64::INFINITY, f64::min)
+    }
+}
+
+/// Where the time of a party goes, in microseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartyStats {
+    pub party: u32,
+    pub total: f64,
+    /// The time outside the rounds.
+    pub compute: f64,
+    /// The time in the rounds, including waiting.
+    pub comm: f64,
+    /// The time in the rounds waiting for the straggler.
+    pub idle: f64,
+    pub straggled: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SegmentKind {
+    Compute,
+    Comm,
+}
+
+/// A piece of the critical path.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    pub kind: SegmentKind,
+    pub party: u32,
+    pub round: Option<usize>,
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    pub parties: Vec<PartyStats>,
+    pub rounds: Vec<Round>,
+    /// The number of rounds of every party, they differ when the traces do not line up.
+    pub round_counts: Vec<usize>,
+    /// In order of time.
+    pub critical_path: Vec<Segment>,
+}
+
+/// The rounds of a party, ordered by start.
+fn rounds_of(trace: &PartyTrace) -> Vec<&Span> {
+    let is_comm = |span: &Span| span.name.starts_with(COMM_PREFIX);
+    trace
+        .spans
+        .iter()
+        .filter(|span| is_comm(span) && !trace.any_ancestor(span, is_comm))
+        .collect()
+}
+
+pub fn analyze(parties: &[PartyTrace]) -> Analysis {
+    let own_rounds = parties.iter().map(rounds_of).collect::<Vec<_>>();
+    let round_counts = own_rounds.iter().map(Vec::len).collect::<Vec<_>>();
+    let n_rounds = round_counts.iter().copied().min().unwrap_or(0);
+
+    let rounds = (0..n_rounds)
+        .map(|k| {
+            let arrivals = own_rounds.iter().map(|r| r[k].start).collect::<Vec<_>>();
+            let ends = own_rounds.iter().map(|r| r[k].end).collect::<Vec<_>>();
+            let (straggler, release) = arrivals
+                .iter()
+                .copied()
+                .enumerate()
+                .fold((0, f64::NEG_INFINITY), |max, (i, a)| if a > max.1 { (i, a) } else { max });
+            let waits = arrivals
+                .iter()
+                .zip(&ends)
+                .map(|(&a, &e)| (e.min(release) - a).max(0.0))
+                .collect();
+            Round {
+                index: k,
+                name: own_rounds[0][k].name.clone(),
+                arrivals,
+                ends,
+                release,
+                straggler,
+                waits,
+            }
+        })
+        .collect::<Vec<Round>>();
+
+    let stats = parties
+        .iter()
+        .enumerate()
+        .map(|(i, trace)| {
+            let (start, end) = trace.bounds();
+            let total = (end - start).max(0.0);
+            let comm = rounds.iter().map(|r| r.ends[i] - r.arrivals[i]).sum::<f64>();
+            PartyStats {
+                party: trace.party,
+                total,
+                compute: total - comm,
+                comm,
+                idle: rounds.iter().map(|r| r.waits[i]).sum(),
+                straggled: rounds.iter().filter(|r| r.straggler == i).count(),
+            }
+        })
+        .collect();
+
+    Analysis {
+        parties: stats,
+        critical_path: critical_path(parties, &rounds),
+        rounds,
+        round_counts,
+    }
+}
+
+fn critical_path(parties: &[PartyTrace], rounds: &[Round]) -> Vec<Segment> {
+    let bounds = parties.iter().map(PartyTrace::bounds).collect::<Vec<_>>();
+    let Some((mut p, &(_, mut t))) =
+        bounds.iter().enumerate().max_by(|a, b| a.1 .1.total_cmp(&b.1 .1))
+    else {
+        return vec![];
+    };
+    let mut path = vec![];
+    let mut push = |kind, p: usize, round, start: f64, end: f64| {
+        if end > start {
+            path.push(Segment { kind, party: parties[p].party, round, start, end });
+        }
+    };
+    for round in rounds.iter().rev() {
+        push(SegmentKind::Compute, p, None, round.ends[p], t);
+        // Waiting, the time before the release is spent by the straggler.
+        if round.straggler != p && round.waits[p] > 0.0 {
+            push(SegmentKind::Comm, p, Some(round.index), round.release, round.ends[p]);
+            p = round.straggler;
+            t = round.release;
+        } else {
+            push(SegmentKind::Comm, p, Some(round.index), round.arrivals[p], round.ends[p]);
+            t = round.arrivals[p];
+        }
+    }
+    push(SegmentKind::Compute, p, None, bounds[p].0, t);
+    path.reverse();
+    path
+}
+
+fn ms(us: f64) -> f64 {
+    us / 1e3
+}
+
+/// A text report, listing the `top` rounds with the largest spread.
+pub fn render(analysis: &Analysis, top: usize) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{:<6} {:>12} {:>12} {:>12} {:>12} {:>6} {:>10}",
+        "party", "total ms", "compute ms", "comm ms", "idle ms", "idle%", "straggler"
+    );
+    for party in &analysis.parties {
+        let idle = if party.total > 0.0 { 100.0 * party.idle / party.total } else { 0.0 };
+        let _ = writeln!(
+            out,
+            "{:<6} {:>12.3} {:>12.3} {:>12.3} {:>12.3} {:>5.1}% {:>10}",
+            party.party,
+            ms(party.total),
+            ms(party.compute),
+            ms(party.comm),
+            ms(party.idle),
+            idle,
+            party.straggled
+        );
+    }
+
+    let _ = writeln!(out);
+    let counts = &analysis.round_counts;
+    if counts.windows(2).any(|w| w[0] != w[1]) {
+        let _ = writeln!(
+            out,
+            "warning: the parties have different numbers of rounds {counts:?}, aligned the first {}",
+            analysis.rounds.len()
+        );
+    }
+    let mut rounds = analysis.rounds.iter().collect::<Vec<_>>();
+    rounds.sort_by(|a, b| b.spread().total_cmp(&a.spread()));
+    let _ = writeln!(out, "{:<6} {:<50} {:>10} {:>10}", "round", "name", "spread ms", "straggler");
+    for round in rounds.into_iter().take(top) {
+        let _ = writeln!(
+            out,
+            "{:<6} {:<50} {:>10.3} {:>10}",
+            round.index,
+            round.name,
+            ms(round.spread()),
+            analysis.parties[round.straggler].party
+        );
+    }
+
+    let _ = writeln!(out);
+    let path = &analysis.critical_path;
+    let length = match (path.first(), path.last()) {
+        (Some(first), Some(last)) => last.end - first.start,
+        _ => 0.0,
+    };
+    let sum = |kind| path.iter().filter(|s| s.kind == kind).map(|s| s.end - s.start).sum::<f64>();
+    let _ = writeln!(
+        out,
+        "critical path {:.3} ms: compute {:.3} ms, comm {:.3} ms",
+        ms(length),
+        ms(sum(SegmentKind::Compute)),
+        ms(sum(SegmentKind::Comm))
+    );
+    for party in &analysis.parties {
+        let on_path = path
+            .iter()
+            .filter(|s| s.party == party.party && s.kind == SegmentKind::Compute)
+            .map(|s| s.end - s.start)
+            .sum::<f64>();
+        if on_path > 0.0 {
+            let _ = writeln!(out, "  party {:<6} computes {:>12.3} ms of it", party.party, ms(on_path));
+        }
+    }
+    out
+}
+
+/// The critical path as Chrome trace events of an extra process after the parties.
+pub fn critical_path_events(analysis: &Analysis) -> Vec<Value> {
+    let pid = analysis.parties.iter().map(|p| p.party + 1).max().unwrap_or(0);
+    let name = json!({
+        "name": "process_name",
+        "ph": "M",
+        "pid": pid,
+        "args": { "name": "critical path" },
+    });
+    analysis
+        .critical_path
+        .iter()
+        .map(|segment| {
+            let name = match (segment.kind, segment.round) {
+                (SegmentKind::Comm, Some(round)) => analysis.rounds[round].name.clone(),
+                _ => format!("compute on party {}", segment.party),
+            };
+            json!({
+                "name": name,
+                "cat": "critical path",
+                "ph": "X",
+                "ts": segment.start,
+                "dur": segment.end - segment.start,
+                "pid": pid,
+                "tid": 0,
+                "args": { "party": segment.party, "round": segment.round },
+            })
+        })
+        .chain([name])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(id: usize, parent: Option<usize>, name: &str, start: f64, end: f64) -> Span {
+        Span {
+            id,
+            parent,
+            name: name.to_string(),
+            start,
+            end,
+            bytes_sent: 0,
+            bytes_received: 0,
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        // Party 1 is late for both rounds, party 0 computes the longest at the end.
+        let parties = vec![
+            PartyTrace {
+                party: 0,
+                spans: vec![
+                    span(1, None, "prove", 0.0, 100.0),
+                    span(2, Some(1), "Comm: from leader to all", 10.0, 30.0),
+                    span(3, Some(2), "Comm: nested", 12.0, 14.0),
+                    span(4, Some(1), "Comm: from leader to all", 40.0, 60.0),
+                ],
+            },
+            PartyTrace {
+                party: 1,
+                spans: vec![
+                    span(1, None, "prove", 0.0, 70.0),
+                    span(2, Some(1), "Comm: from leader to 1", 25.0, 30.0),
+                    span(3, Some(1), "Comm: from leader to 1", 50.0, 60.0),
+                ],
+            },
+        ];
+        let analysis = analyze(&parties);
+        assert_eq!(analysis.round_counts, vec![2, 2]);
+        let round = &analysis.rounds[0];
+        assert_eq!((round.straggler, round.release, round.spread()), (1, 25.0, 15.0));
+        assert_eq!(round.waits, vec![15.0, 0.0]);
+        assert_eq!(analysis.rounds[1].waits, vec![10.0, 0.0]);
+
+        let stats = &analysis.parties;
+        assert_eq!(stats[0], PartyStats {
+            party: 0,
+            total: 100.0,
+            compute: 60.0,
+            comm: 40.0,
+            idle: 25.0,
+            straggled: 0,
+        });
+        assert_eq!((stats[1].idle, stats[1].straggled), (0.0, 2));
+
+        let path = analysis
+            .critical_path
+            .iter()
+            .map(|s| (s.kind, s.party, s.start, s.end))
+            .collect::<Vec<_>>();
+        use SegmentKind::*;
+        assert_eq!(path, vec![
+            (Compute, 1, 0.0, 25.0),
+            (Comm, 1, 25.0, 30.0),
+            (Compute, 1, 30.0, 50.0),
+            (Comm, 0, 50.0, 60.0),
+            (Compute, 0, 60.0, 100.0),
+        ]);
+
+        let report = render(&analysis, 1);
+        assert!(report.contains("critical path 0.100 ms: compute 0.085 ms, comm 0.015 ms"));
+        assert_eq!(critical_path_events(&analysis).len(), 6);
+    }
+}
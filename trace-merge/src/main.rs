@@ -0,0 +1,76 @@
+//! Merges the traces the parties wrote with `--profile` and reports where they wait.
+//!
+//! The parties are aligned by their rounds of communication. The report lists the time every
+//! party computes, communicates and sits idle waiting for the others, the rounds with the
+//! largest spread between the first and the last party to arrive, and the critical path through
+//! the parties. The merged trace has all parties and the critical path as processes, to open in
+//! `chrome://tracing` or Perfetto. For example:
+//!
+//! ```bash
+//! just run --release -p launcher -- --example bench_hyperplonk --l 2 --profile -- --n 12
+//! just run --release -p trace-merge -- --out output/merged.json output/party-*.json
+//! ```
+mod analysis;
+mod trace;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use serde_json::{json, Value};
+
+#[derive(Parser)]
+struct Cli {
+    /// Where the merged Chrome trace goes.
+    #[arg(long)]
+    out: Option<PathBuf>,
+    /// Where the report goes, besides the standard output.
+    #[arg(long)]
+    report: Option<PathBuf>,
+    /// Report this many rounds with the largest spread.
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+    /// The Chrome traces of the parties.
+    #[arg(required = true)]
+    traces: Vec<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let args = Cli::parse();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("trace-merge: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Cli) -> Result<(), String> {
+    let mut events = vec![];
+    let mut traces = vec![];
+    for path in &args.traces {
+        let error = |err: &dyn std::fmt::Display| format!("{}: {err}", path.display());
+        let bytes = fs::read(path).map_err(|err| error(&err))?;
+        let trace: Value = serde_json::from_slice(&bytes).map_err(|err| error(&err))?;
+        traces.push(trace::parse(&trace).map_err(|err| error(&err))?);
+        if let Some(Value::Array(trace_events)) = trace.get("traceEvents").or(Some(&trace)) {
+            events.extend(trace_events.iter().cloned());
+        }
+    }
+    let parties = trace::merge(traces)?;
+    let analysis = analysis::analyze(&parties);
+
+    let report = analysis::render(&analysis, args.top);
+    print!("{report}");
+    if let Some(path) = &args.report {
+        fs::write(path, &report).map_err(|err| format!("{}: {err}", path.display()))?;
+    }
+    if let Some(path) = &args.out {
+        events.extend(analysis::critical_path_events(&analysis));
+        let merged = json!({ "traceEvents": events, "displayTimeUnit": "ms" });
+        fs::write(path, merged.to_string()).map_err(|err| format!("{}: {err}", path.display()))?;
+    }
+    Ok(())
+}
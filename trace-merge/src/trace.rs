@@ -0,0 +1,91 @@
+//! Reads the Chrome traces written by `mpc_net::profile::Profiler`.
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+/// A span of a party, times in microseconds since the Unix epoch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub name: String,
+    pub start: f64,
+    pub end: f64,
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+}
+
+/// The spans of one party, ordered by start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartyTrace {
+    pub party: u32,
+    pub spans: Vec<Span>,
+}
+
+impl PartyTrace {
+    /// Whether `span` or one of its ancestors satisfies `pred`.
+    pub fn any_ancestor(&self, span: &Span, pred: impl Fn(&Span) -> bool) -> bool {
+        let by_id = |id| self.spans.iter().find(|span| span.id == id);
+        let mut parent = span.parent.and_then(by_id);
+        while let Some(span) = parent {
+            if pred(span) {
+                return true;
+            }
+            parent = span.parent.and_then(by_id);
+        }
+        false
+    }
+
+    /// From the start of the first span to the end of the last.
+    pub fn bounds(&self) -> (f64, f64) {
+        let start = self.spans.iter().map(|s| s.start).fold(f64::INFINITY, f64::min);
+        let end = self.spans.iter().map(|s| s.end).fold(f64::NEG_INFINITY, f64::max);
+        (start, end)
+    }
+}
+
+/// The parties of a trace, a trace may hold several of them.
+pub fn parse(trace: &Value) -> Result<Vec<PartyTrace>, String> {
+    let events = trace
+        .get("traceEvents")
+        .or(Some(trace))
+        .and_then(Value::as_array)
+        .ok_or("not a Chrome trace")?;
+    let mut parties = BTreeMap::<u32, Vec<Span>>::new();
+    for event in events.iter().filter(|e| e["ph"] == "X") {
+        let field = |name: &str| event[name].as_f64().ok_or(format!("event without {name}"));
+        let party = field("pid")? as u32;
+        let start = field("ts")?;
+        let args = &event["args"];
+        let count = |name: &str| args[name].as_u64().unwrap_or(0) as usize;
+        parties.entry(party).or_default().push(Span {
+            id: args["id"].as_u64().ok_or("event without id")? as usize,
+            parent: args["parent"].as_u64().map(|p| p as usize),
+            name: event["name"].as_str().unwrap_or_default().to_string(),
+            start,
+            end: start + field("dur")?,
+            bytes_sent: count("bytes_sent"),
+            bytes_received: count("bytes_received"),
+        });
+    }
+    Ok(parties
+        .into_iter()
+        .map(|(party, mut spans)| {
+            spans.sort_by(|a, b| a.start.total_cmp(&b.start));
+            PartyTrace { party, spans }
+        })
+        .collect())
+}
+
+/// Merge the parties of several traces, each party must be in one of them.
+pub fn merge(traces: Vec<Vec<PartyTrace>>) -> Result<Vec<PartyTrace>, String> {
+    let mut parties = HashMap::new();
+    for party in traces.into_iter().flatten() {
+        if parties.insert(party.party, party).is_some() {
+            return Err("a party is in several traces".to_string());
+        }
+    }
+    let mut parties = parties.into_values().collect::<Vec<_>>();
+    parties.sort_by_key(|party| party.party);
+    Ok(parties)
+}